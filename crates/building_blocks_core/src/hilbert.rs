@@ -0,0 +1,281 @@
+//! Hilbert curve indices for 2D and 3D integer points.
+//!
+//! Like Morton order, Hilbert order visits each aligned power-of-2 cube of the lattice as one
+//! contiguous run. Unlike Morton order, consecutive points on the Hilbert curve are always
+//! neighbors, so it does a better job of keeping spatially close data close together, e.g. when
+//! streaming or serializing chunks.
+//!
+//! Points have the same valid range as `MortonEncode`.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//!
+//! let p = PointN([3, -7, 12]);
+//! assert_eq!(Point3i::hilbert_decode(p.hilbert_encode()), p);
+//!
+//! // Every step of the curve moves to a neighboring point.
+//! let extent = Extent2i::from_min_and_shape(PointN([0, 0]), PointN([4, 4]));
+//! let points: Vec<_> = extent.iter_points_hilbert().collect();
+//! for pair in points.windows(2) {
+//!     let d = pair[1] - pair[0];
+//!     assert_eq!(d.x().abs() + d.y().abs(), 1);
+//! }
+//! ```
+
+use crate::{
+    morton::{
+        bias_2, bias_3, compact_by_2, compact_by_3, low_bits, spread_by_2, spread_by_3, unbias_2,
+        unbias_3,
+    },
+    ExtentN, IntegerExtent, MortonEncode, Ones, Point, Point2i, Point3i, PointN,
+};
+
+use num::Zero;
+
+/// Points that can be converted to and from an index on the Hilbert curve.
+pub trait HilbertEncode: MortonEncode {
+    fn hilbert_encode(&self) -> u64;
+
+    fn hilbert_decode(index: u64) -> Self;
+}
+
+impl HilbertEncode for Point2i {
+    fn hilbert_encode(&self) -> u64 {
+        let [x, y] = self.0;
+        let mut axes = [bias_2(x), bias_2(y)];
+        axes_to_transpose(&mut axes, Self::LEVELS);
+
+        spread_by_2(axes[0]) << 1 | spread_by_2(axes[1])
+    }
+
+    fn hilbert_decode(index: u64) -> Self {
+        let mut axes = [compact_by_2(index >> 1), compact_by_2(index)];
+        transpose_to_axes(&mut axes, Self::LEVELS);
+
+        PointN([unbias_2(axes[0]), unbias_2(axes[1])])
+    }
+}
+
+impl HilbertEncode for Point3i {
+    fn hilbert_encode(&self) -> u64 {
+        let [x, y, z] = self.0;
+        let mut axes = [bias_3(x), bias_3(y), bias_3(z)];
+        axes_to_transpose(&mut axes, Self::LEVELS);
+
+        spread_by_3(axes[0]) << 2 | spread_by_3(axes[1]) << 1 | spread_by_3(axes[2])
+    }
+
+    fn hilbert_decode(index: u64) -> Self {
+        let mut axes = [
+            compact_by_3(index >> 2),
+            compact_by_3(index >> 1),
+            compact_by_3(index),
+        ];
+        transpose_to_axes(&mut axes, Self::LEVELS);
+
+        PointN([unbias_3(axes[0]), unbias_3(axes[1]), unbias_3(axes[2])])
+    }
+}
+
+// These two functions are from John Skilling, "Programming the Hilbert curve" (2004). The
+// "transpose" holds the bits of the Hilbert index spread across the axes, most significant in
+// `axes[0]`.
+
+fn axes_to_transpose(axes: &mut [u32], bits: u32) {
+    let n = axes.len();
+    let m = 1 << (bits - 1);
+
+    // Inverse undo.
+    let mut q = m;
+    while q > 1 {
+        let p = q - 1;
+        for i in 0..n {
+            if axes[i] & q != 0 {
+                axes[0] ^= p;
+            } else {
+                let t = (axes[0] ^ axes[i]) & p;
+                axes[0] ^= t;
+                axes[i] ^= t;
+            }
+        }
+        q >>= 1;
+    }
+
+    // Gray encode.
+    for i in 1..n {
+        axes[i] ^= axes[i - 1];
+    }
+    let mut t = 0;
+    let mut q = m;
+    while q > 1 {
+        if axes[n - 1] & q != 0 {
+            t ^= q - 1;
+        }
+        q >>= 1;
+    }
+    for a in axes.iter_mut() {
+        *a ^= t;
+    }
+}
+
+fn transpose_to_axes(axes: &mut [u32], bits: u32) {
+    let n = axes.len();
+
+    // Gray decode.
+    let t = axes[n - 1] >> 1;
+    for i in (1..n).rev() {
+        axes[i] ^= axes[i - 1];
+    }
+    axes[0] ^= t;
+
+    // Undo excess work.
+    for k in 1..bits {
+        let q = 1 << k;
+        let p = q - 1;
+        for i in (0..n).rev() {
+            if axes[i] & q != 0 {
+                axes[0] ^= p;
+            } else {
+                let t = (axes[0] ^ axes[i]) & p;
+                axes[0] ^= t;
+                axes[i] ^= t;
+            }
+        }
+    }
+}
+
+impl<N> ExtentN<N>
+where
+    PointN<N>: HilbertEncode + Point + Ones,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Iterate over all points in the extent in Hilbert order.
+    ///
+    /// Like `iter_points_morton`, this recursively subdivides the curve and skips any nodes that
+    /// don't overlap the extent.
+    pub fn iter_points_hilbert(&self) -> HilbertPointIter<N> {
+        let mut stack = Vec::new();
+        if self.shape > PointN::zero() {
+            let diff = self.minimum.morton_encode() ^ self.max().morton_encode();
+            let level = if diff == 0 {
+                0
+            } else {
+                (63 - diff.leading_zeros()) / PointN::<N>::DIMENSIONS + 1
+            };
+            let node_bits = level * PointN::<N>::DIMENSIONS;
+            stack.push((self.minimum.hilbert_encode() & !low_bits(node_bits), level));
+        }
+
+        HilbertPointIter {
+            minimum: self.minimum,
+            max: self.max(),
+            stack,
+        }
+    }
+}
+
+/// An iterator over all points in an `ExtentN<N>` in Hilbert order. See
+/// `ExtentN::iter_points_hilbert`.
+pub struct HilbertPointIter<N> {
+    minimum: PointN<N>,
+    max: PointN<N>,
+    // The first index and level of each node of the Hilbert curve that remains to be visited.
+    stack: Vec<(u64, u32)>,
+}
+
+impl<N> Iterator for HilbertPointIter<N>
+where
+    PointN<N>: HilbertEncode + Copy + PartialOrd,
+{
+    type Item = PointN<N>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let dims = PointN::<N>::DIMENSIONS;
+        while let Some((index, level)) = self.stack.pop() {
+            if level == 0 {
+                // Only nodes that overlap the extent are pushed.
+                return Some(PointN::hilbert_decode(index));
+            }
+
+            // Push the children in reverse so they're popped in index order.
+            let child_level = level - 1;
+            let child_bits = child_level * dims;
+            for child in (0..1u64 << dims).rev() {
+                let child_index = index | child << child_bits;
+                // Each node covers the same aligned cube as some node of the Z-order curve, which
+                // makes its bounds easy to find.
+                let code = PointN::hilbert_decode(child_index).morton_encode();
+                let child_min = PointN::morton_decode(code & !low_bits(child_bits));
+                let child_max = PointN::morton_decode(code | low_bits(child_bits));
+                if child_min <= self.max && self.minimum <= child_max {
+                    self.stack.push((child_index, child_level));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{Extent2i, Extent3i};
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for p in [
+            PointN([0, 0]),
+            PointN([-1, 1]),
+            PointN([i32::MIN, i32::MAX]),
+            PointN([123_456, -654_321]),
+        ]
+        .iter()
+        {
+            assert_eq!(Point2i::hilbert_decode(p.hilbert_encode()), *p);
+        }
+        for p in [
+            PointN([0, 0, 0]),
+            PointN([-1, 1, -1]),
+            PointN([-(1 << 20), (1 << 20) - 1, 5]),
+        ]
+        .iter()
+        {
+            assert_eq!(Point3i::hilbert_decode(p.hilbert_encode()), *p);
+        }
+    }
+
+    #[test]
+    fn consecutive_indices_are_neighbors() {
+        let start = PointN([-5, 3, 9]).hilbert_encode();
+        for index in start..start + 1000 {
+            let p = Point3i::hilbert_decode(index);
+            let q = Point3i::hilbert_decode(index + 1);
+            let d = q - p;
+            assert_eq!(d.x().abs() + d.y().abs() + d.z().abs(), 1);
+        }
+    }
+
+    #[test]
+    fn hilbert_iter_matches_sorted_points() {
+        let extent = Extent3i::from_min_and_shape(PointN([-3, -1, 2]), PointN([5, 3, 4]));
+        let mut expected: Vec<_> = extent.iter_points().collect();
+        expected.sort_by_key(|p| p.hilbert_encode());
+        let points: Vec<_> = extent.iter_points_hilbert().collect();
+        assert_eq!(points, expected);
+
+        let extent = Extent2i::from_min_and_shape(PointN([-2, -2]), PointN([7, 3]));
+        let mut expected: Vec<_> = extent.iter_points().collect();
+        expected.sort_by_key(|p| p.hilbert_encode());
+        let points: Vec<_> = extent.iter_points_hilbert().collect();
+        assert_eq!(points, expected);
+    }
+}
@@ -1,30 +1,51 @@
 //! The core data types for defining 2D and 3D integer lattices:
 //! - `PointN`: an N-dimensional point, most importantly `Point2i` and `Point3i`
 //! - `ExtentN`: an N-dimensional extent, most importantly `Extent2i` and `Extent3i`
+//! - `Orientation2` and `Orientation3`: the axis-aligned rotations and reflections of the lattice
+//! - `MortonEncode` and `HilbertEncode`: space-filling curve indices for points, and iteration of
+//!   extents in those orders
+//! - `LinePointIter` and `RayVoxelIter`: rasterization of segments and rays onto the lattice
+//! - `LatticeShape`: iteration over the points in balls, shells, capsules, cylinders and disks
 
 pub mod extent;
 pub mod extent2;
 pub mod extent3;
+pub mod hilbert;
+pub mod line;
+pub mod morton;
+pub mod orientation;
 pub mod point;
 pub mod point2;
 pub mod point3;
+pub mod shape;
 
 pub use extent::{bounding_extent, Extent, ExtentN, IntegerExtent};
 pub use extent2::{Extent2, Extent2i};
 pub use extent3::{Extent3, Extent3i};
+pub use hilbert::{HilbertEncode, HilbertPointIter};
+pub use line::{
+    Connectivity, LinePointIter, LinePointIter2, LinePointIter3, RayVoxel, RayVoxelIter,
+};
+pub use morton::{MortonEncode, MortonPointIter};
+pub use orientation::{
+    AxisPermutation2, AxisPermutation3, Orientation, Orientation2, Orientation3,
+};
 pub use point::{
     Bounded, Distance, DotProduct, IntegerPoint, Norm, Ones, Point, PointN, SmallZero,
 };
 pub use point2::{Point2, Point2f, Point2i};
 pub use point3::{Point3, Point3f, Point3i};
+pub use shape::{Ball, Capsule, Cylinder, Disk, LatticeShape, RowSpans, ShapePointIter, Shell};
 
 pub use num;
 
 pub mod prelude {
     pub use super::{
-        Bounded, Distance, DotProduct, Extent, Extent2, Extent2i, Extent3, Extent3i, ExtentN,
-        IntegerExtent, IntegerPoint, Norm, Ones, Point, Point2, Point2f, Point2i, Point3, Point3f,
-        Point3i, PointN, SmallZero,
+        AxisPermutation2, AxisPermutation3, Ball, Bounded, Capsule, Connectivity, Cylinder, Disk,
+        Distance, DotProduct, Extent, Extent2, Extent2i, Extent3, Extent3i, ExtentN, HilbertEncode,
+        IntegerExtent, IntegerPoint, LatticeShape, LinePointIter2, LinePointIter3, MortonEncode,
+        Norm, Ones, Orientation, Orientation2, Orientation3, Point, Point2, Point2f, Point2i,
+        Point3, Point3f, Point3i, PointN, RayVoxel, RayVoxelIter, Shell, SmallZero,
     };
 }
 
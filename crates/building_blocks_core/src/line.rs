@@ -0,0 +1,440 @@
+//! Rasterization of line segments and rays onto the integer lattice.
+//!
+//! `LinePointIter` enumerates the lattice points on the segment between two integer points,
+//! Bresenham-style. The path can either be connected by faces (4-connected in 2D, 6-connected in
+//! 3D) or by faces, edges and corners (8-connected in 2D, 26-connected in 3D). Either way, the
+//! path starts at the first point and ends at the second.
+//!
+//! `RayVoxelIter` is an Amanatides-Woo traversal of the voxels pierced by a floating point ray. The
+//! voxel at integer point `p` covers the unit cube `[p, p + 1)`.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//!
+//! let points: Vec<_> =
+//!     LinePointIter2::new(PointN([0, 0]), PointN([3, 1]), Connectivity::Moore).collect();
+//! assert_eq!(
+//!     points,
+//!     vec![PointN([0, 0]), PointN([1, 0]), PointN([2, 1]), PointN([3, 1])]
+//! );
+//!
+//! let voxels: Vec<_> = RayVoxelIter::new(PointN([0.5, 0.5, 0.5]), PointN([1.0, 0.0, 0.0]), 2.0)
+//!     .map(|v| v.point)
+//!     .collect();
+//! assert_eq!(voxels, vec![PointN([0, 0, 0]), PointN([1, 0, 0]), PointN([2, 0, 0])]);
+//! ```
+
+use crate::{Point2i, Point3f, Point3i, PointN};
+
+use core::marker::PhantomData;
+
+/// How consecutive points of a rasterized line are connected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Connectivity {
+    /// Consecutive points share a face, i.e. they differ by one of
+    /// `IntegerPoint::von_neumann_offsets`. Paths are longer, but they never cut corners.
+    VonNeumann,
+    /// Consecutive points differ by one of `IntegerPoint::moore_offsets`. This gives the shortest
+    /// paths.
+    Moore,
+}
+
+/// An iterator over the lattice points on the segment between two points, inclusive. See the
+/// module docs.
+pub struct LinePointIter<N> {
+    current: [i32; 3],
+    // Includes the current point.
+    num_remaining: usize,
+    sign: [i32; 3],
+    abs_delta: [i64; 3],
+    dims: usize,
+    state: LineState,
+    _n: PhantomData<N>,
+}
+
+pub type LinePointIter2 = LinePointIter<[i32; 2]>;
+pub type LinePointIter3 = LinePointIter<[i32; 3]>;
+
+enum LineState {
+    // The number of steps taken along each axis so far.
+    VonNeumann {
+        steps: [i64; 3],
+    },
+    // The Bresenham error terms of each axis, relative to the driving axis.
+    Moore {
+        driving_axis: usize,
+        error: [i64; 3],
+    },
+}
+
+impl LinePointIter<[i32; 2]> {
+    pub fn new(start: Point2i, end: Point2i, connectivity: Connectivity) -> Self {
+        Self::from_coords(
+            [start.x(), start.y(), 0],
+            [end.x(), end.y(), 0],
+            2,
+            connectivity,
+        )
+    }
+}
+
+impl LinePointIter<[i32; 3]> {
+    pub fn new(start: Point3i, end: Point3i, connectivity: Connectivity) -> Self {
+        Self::from_coords(start.0, end.0, 3, connectivity)
+    }
+}
+
+impl<N> LinePointIter<N> {
+    fn from_coords(
+        start: [i32; 3],
+        end: [i32; 3],
+        dims: usize,
+        connectivity: Connectivity,
+    ) -> Self {
+        let mut sign = [0; 3];
+        let mut abs_delta = [0; 3];
+        for i in 0..dims {
+            let delta = end[i] as i64 - start[i] as i64;
+            sign[i] = delta.signum() as i32;
+            abs_delta[i] = delta.abs();
+        }
+
+        let (num_steps, state) = match connectivity {
+            Connectivity::VonNeumann => (
+                abs_delta.iter().sum::<i64>(),
+                LineState::VonNeumann { steps: [0; 3] },
+            ),
+            Connectivity::Moore => {
+                let driving_axis = (0..dims).max_by_key(|&i| abs_delta[i]).unwrap();
+                let driving_delta = abs_delta[driving_axis];
+                let mut error = [0; 3];
+                for (e, d) in error.iter_mut().zip(abs_delta.iter()) {
+                    *e = 2 * d - driving_delta;
+                }
+
+                (
+                    driving_delta,
+                    LineState::Moore {
+                        driving_axis,
+                        error,
+                    },
+                )
+            }
+        };
+
+        Self {
+            current: start,
+            num_remaining: num_steps as usize + 1,
+            sign,
+            abs_delta,
+            dims,
+            state,
+            _n: PhantomData,
+        }
+    }
+
+    fn next_coords(&mut self) -> Option<[i32; 3]> {
+        if self.num_remaining == 0 {
+            return None;
+        }
+
+        let coords = self.current;
+        self.num_remaining -= 1;
+        if self.num_remaining > 0 {
+            self.advance();
+        }
+
+        Some(coords)
+    }
+
+    fn advance(&mut self) {
+        let dims = self.dims;
+        let abs_delta = self.abs_delta;
+        match &mut self.state {
+            LineState::VonNeumann { steps } => {
+                // Step along the axis whose next cell boundary is closest. The segment crosses the
+                // k-th boundary of axis i at t = (2k + 1) / (2 * |delta_i|).
+                let mut best_axis = None;
+                for i in 0..dims {
+                    if steps[i] == abs_delta[i] {
+                        continue;
+                    }
+                    best_axis = match best_axis {
+                        Some(b)
+                            if (2 * steps[b] + 1) * abs_delta[i]
+                                <= (2 * steps[i] + 1) * abs_delta[b] =>
+                        {
+                            Some(b)
+                        }
+                        _ => Some(i),
+                    };
+                }
+                let axis = best_axis.unwrap();
+                steps[axis] += 1;
+                self.current[axis] += self.sign[axis];
+            }
+            LineState::Moore {
+                driving_axis,
+                error,
+            } => {
+                let driving_delta = abs_delta[*driving_axis];
+                for i in 0..dims {
+                    if i == *driving_axis {
+                        self.current[i] += self.sign[i];
+                        continue;
+                    }
+                    if error[i] > 0 {
+                        self.current[i] += self.sign[i];
+                        error[i] -= 2 * driving_delta;
+                    }
+                    error[i] += 2 * abs_delta[i];
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for LinePointIter<[i32; 2]> {
+    type Item = Point2i;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_coords().map(|[x, y, _]| PointN([x, y]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.num_remaining, Some(self.num_remaining))
+    }
+}
+
+impl Iterator for LinePointIter<[i32; 3]> {
+    type Item = Point3i;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_coords().map(PointN)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.num_remaining, Some(self.num_remaining))
+    }
+}
+
+/// A voxel visited by a `RayVoxelIter`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayVoxel {
+    pub point: Point3i,
+    /// The ray parameter where the ray enters this voxel, in units of the ray direction's length.
+    pub t_entry: f32,
+    /// The outward normal of the face where the ray entered this voxel. It's zero for the voxel
+    /// containing the ray origin.
+    pub normal: Point3i,
+}
+
+/// An Amanatides-Woo traversal of all voxels pierced by a ray, in order, up to some maximum ray
+/// parameter `t`.
+pub struct RayVoxelIter {
+    next_voxel: Option<RayVoxel>,
+    step: [i32; 3],
+    // The value of t where the ray crosses the next cell boundary along each axis.
+    t_max: [f32; 3],
+    // How much t changes while crossing a whole cell along each axis.
+    t_delta: [f32; 3],
+    max_t: f32,
+}
+
+impl RayVoxelIter {
+    /// Visits every voxel pierced by the points `origin + t * direction` for `0 <= t <= max_t`.
+    pub fn new(origin: Point3f, direction: Point3f, max_t: f32) -> Self {
+        let mut point = [0; 3];
+        let mut step = [0; 3];
+        let mut t_max = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+        for i in 0..3 {
+            let o = origin.0[i];
+            let d = direction.0[i];
+            let cell = o.floor();
+            point[i] = cell as i32;
+            if d > 0.0 {
+                step[i] = 1;
+                t_max[i] = (cell + 1.0 - o) / d;
+                t_delta[i] = 1.0 / d;
+            } else if d < 0.0 {
+                step[i] = -1;
+                t_max[i] = (cell - o) / d;
+                t_delta[i] = -1.0 / d;
+            }
+        }
+
+        Self {
+            next_voxel: Some(RayVoxel {
+                point: PointN(point),
+                t_entry: 0.0,
+                normal: PointN([0; 3]),
+            }),
+            step,
+            t_max,
+            t_delta,
+            max_t,
+        }
+    }
+}
+
+impl Iterator for RayVoxelIter {
+    type Item = RayVoxel;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let voxel = self.next_voxel?;
+
+        let mut axis = 0;
+        for i in 1..3 {
+            if self.t_max[i] < self.t_max[axis] {
+                axis = i;
+            }
+        }
+        let t_entry = self.t_max[axis];
+        // A zero direction never crosses a boundary, so t_entry stays infinite even when max_t is.
+        self.next_voxel = if t_entry.is_finite() && t_entry <= self.max_t {
+            self.t_max[axis] += self.t_delta[axis];
+            let mut point = voxel.point;
+            point.0[axis] += self.step[axis];
+            let mut normal = PointN([0; 3]);
+            normal.0[axis] = -self.step[axis];
+
+            Some(RayVoxel {
+                point,
+                t_entry,
+                normal,
+            })
+        } else {
+            None
+        };
+
+        Some(voxel)
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn moore_line_takes_diagonal_steps() {
+        let start = PointN([-2, 1, 0]);
+        let end = PointN([5, -2, 3]);
+        let points: Vec<_> = LinePointIter3::new(start, end, Connectivity::Moore).collect();
+
+        assert_eq!(points.len(), 8);
+        assert_eq!(points.first(), Some(&start));
+        assert_eq!(points.last(), Some(&end));
+        for pair in points.windows(2) {
+            let d = pair[1] - pair[0];
+            assert_eq!(d.x().abs().max(d.y().abs()).max(d.z().abs()), 1);
+        }
+    }
+
+    #[test]
+    fn von_neumann_line_takes_face_steps() {
+        let start = PointN([0, 0, 0]);
+        let end = PointN([3, -2, 1]);
+        let points: Vec<_> = LinePointIter3::new(start, end, Connectivity::VonNeumann).collect();
+
+        assert_eq!(points.len(), 7);
+        assert_eq!(points.first(), Some(&start));
+        assert_eq!(points.last(), Some(&end));
+        for pair in points.windows(2) {
+            let d = pair[1] - pair[0];
+            assert_eq!(d.x().abs() + d.y().abs() + d.z().abs(), 1);
+        }
+
+        let points: Vec<_> =
+            LinePointIter2::new(PointN([0, 0]), PointN([2, 1]), Connectivity::VonNeumann).collect();
+        assert_eq!(
+            points,
+            vec![
+                PointN([0, 0]),
+                PointN([1, 0]),
+                PointN([1, 1]),
+                PointN([2, 1])
+            ]
+        );
+    }
+
+    #[test]
+    fn degenerate_line_is_one_point() {
+        let p = PointN([4, 4]);
+        for connectivity in [Connectivity::VonNeumann, Connectivity::Moore].iter() {
+            let points: Vec<_> = LinePointIter2::new(p, p, *connectivity).collect();
+            assert_eq!(points, vec![p]);
+        }
+    }
+
+    #[test]
+    fn ray_visits_voxels_in_order() {
+        let voxels: Vec<_> =
+            RayVoxelIter::new(PointN([0.5, 0.5, 0.5]), PointN([1.0, 0.5, 0.0]), 3.0).collect();
+
+        let x = PointN([-1, 0, 0]);
+        let y = PointN([0, -1, 0]);
+        let expected = vec![
+            (PointN([0, 0, 0]), 0.0, PointN([0; 3])),
+            (PointN([1, 0, 0]), 0.5, x),
+            (PointN([1, 1, 0]), 1.0, y),
+            (PointN([2, 1, 0]), 1.5, x),
+            (PointN([3, 1, 0]), 2.5, x),
+            (PointN([3, 2, 0]), 3.0, y),
+        ];
+        let actual: Vec<_> = voxels
+            .into_iter()
+            .map(|v| (v.point, v.t_entry, v.normal))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn ray_with_negative_coordinates() {
+        let voxels: Vec<_> =
+            RayVoxelIter::new(PointN([-0.5, 0.5, 0.5]), PointN([-1.0, 0.0, 0.0]), 1.0).collect();
+
+        assert_eq!(
+            voxels,
+            vec![
+                RayVoxel {
+                    point: PointN([-1, 0, 0]),
+                    t_entry: 0.0,
+                    normal: PointN([0; 3]),
+                },
+                RayVoxel {
+                    point: PointN([-2, 0, 0]),
+                    t_entry: 0.5,
+                    normal: PointN([1, 0, 0]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ray_without_direction_stays_put() {
+        let voxels: Vec<_> = RayVoxelIter::new(PointN([0.5; 3]), PointN([0.0; 3]), 10.0).collect();
+        assert_eq!(voxels.len(), 1);
+
+        let voxels: Vec<_> =
+            RayVoxelIter::new(PointN([0.5; 3]), PointN([0.0; 3]), f32::INFINITY).collect();
+        assert_eq!(voxels.len(), 1);
+    }
+
+    #[test]
+    fn unbounded_ray_keeps_going() {
+        let voxels: Vec<_> =
+            RayVoxelIter::new(PointN([0.5; 3]), PointN([1.0, 0.0, 0.0]), f32::INFINITY)
+                .take(100)
+                .collect();
+        assert_eq!(voxels.len(), 100);
+        assert_eq!(voxels[99].point, PointN([99, 0, 0]));
+    }
+}
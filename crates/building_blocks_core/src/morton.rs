@@ -0,0 +1,310 @@
+//! Morton (Z-order) codes for 2D and 3D integer points.
+//!
+//! A Morton code interleaves the bits of each coordinate, so points that are close on the lattice
+//! tend to be close in code order. Visiting points in Morton order gives better cache locality
+//! than row-major order for algorithms that recursively subdivide space, like octree
+//! construction, or that look at neighborhoods.
+//!
+//! Coordinates are biased so that codes preserve the order of negative and positive points alike.
+//! 2D points use all 32 bits of each coordinate. 3D points only have room for 21 bits per
+//! coordinate, so they must lie in `[-2^20, 2^20)`.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//!
+//! let p = PointN([3, -7, 12]);
+//! assert_eq!(Point3i::morton_decode(p.morton_encode()), p);
+//!
+//! let extent = Extent2i::from_min_and_shape(PointN([0, 0]), PointN([2, 2]));
+//! let points: Vec<_> = extent.iter_points_morton().collect();
+//! assert_eq!(
+//!     points,
+//!     vec![PointN([0, 0]), PointN([1, 0]), PointN([0, 1]), PointN([1, 1])]
+//! );
+//! ```
+
+use crate::{ExtentN, IntegerExtent, Ones, Point, Point2i, Point3i, PointN};
+
+use num::Zero;
+
+/// Points that can be converted to and from a Morton code.
+///
+/// The lowest bit of each group of `DIMENSIONS` bits comes from the x coordinate, so the children
+/// of a node in the Z-order curve are numbered the same way as `IntegerPoint::corner_offsets`.
+pub trait MortonEncode: Sized {
+    /// The number of bits in each level of the Z-order curve.
+    const DIMENSIONS: u32;
+    /// The number of bits used from each coordinate.
+    const LEVELS: u32;
+
+    fn morton_encode(&self) -> u64;
+
+    fn morton_decode(code: u64) -> Self;
+}
+
+const BIAS_3: u32 = 1 << 20;
+const MASK_3: u64 = 0x1f_ffff;
+
+impl MortonEncode for Point2i {
+    const DIMENSIONS: u32 = 2;
+    const LEVELS: u32 = 32;
+
+    fn morton_encode(&self) -> u64 {
+        let [x, y] = self.0;
+
+        spread_by_2(bias_2(x)) | spread_by_2(bias_2(y)) << 1
+    }
+
+    fn morton_decode(code: u64) -> Self {
+        PointN([
+            unbias_2(compact_by_2(code)),
+            unbias_2(compact_by_2(code >> 1)),
+        ])
+    }
+}
+
+impl MortonEncode for Point3i {
+    const DIMENSIONS: u32 = 3;
+    const LEVELS: u32 = 21;
+
+    fn morton_encode(&self) -> u64 {
+        let [x, y, z] = self.0;
+
+        spread_by_3(bias_3(x)) | spread_by_3(bias_3(y)) << 1 | spread_by_3(bias_3(z)) << 2
+    }
+
+    fn morton_decode(code: u64) -> Self {
+        PointN([
+            unbias_3(compact_by_3(code)),
+            unbias_3(compact_by_3(code >> 1)),
+            unbias_3(compact_by_3(code >> 2)),
+        ])
+    }
+}
+
+// Flipping the sign bit maps i32 onto u32 while preserving order.
+pub(crate) fn bias_2(c: i32) -> u32 {
+    c as u32 ^ 0x8000_0000
+}
+
+pub(crate) fn unbias_2(c: u32) -> i32 {
+    (c ^ 0x8000_0000) as i32
+}
+
+pub(crate) fn bias_3(c: i32) -> u32 {
+    debug_assert!(
+        -(BIAS_3 as i32) <= c && c < BIAS_3 as i32,
+        "coordinate {} out of range for a 3D Morton code",
+        c
+    );
+
+    (c as u32).wrapping_add(BIAS_3)
+}
+
+pub(crate) fn unbias_3(c: u32) -> i32 {
+    c.wrapping_sub(BIAS_3) as i32
+}
+
+pub(crate) fn spread_by_2(c: u32) -> u64 {
+    let mut x = c as u64;
+    x = (x | x << 16) & 0x0000_ffff_0000_ffff;
+    x = (x | x << 8) & 0x00ff_00ff_00ff_00ff;
+    x = (x | x << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+    x = (x | x << 2) & 0x3333_3333_3333_3333;
+    x = (x | x << 1) & 0x5555_5555_5555_5555;
+
+    x
+}
+
+pub(crate) fn compact_by_2(code: u64) -> u32 {
+    let mut x = code & 0x5555_5555_5555_5555;
+    x = (x | x >> 1) & 0x3333_3333_3333_3333;
+    x = (x | x >> 2) & 0x0f0f_0f0f_0f0f_0f0f;
+    x = (x | x >> 4) & 0x00ff_00ff_00ff_00ff;
+    x = (x | x >> 8) & 0x0000_ffff_0000_ffff;
+    x = (x | x >> 16) & 0x0000_0000_ffff_ffff;
+
+    x as u32
+}
+
+pub(crate) fn spread_by_3(c: u32) -> u64 {
+    let mut x = c as u64 & MASK_3;
+    x = (x | x << 32) & 0x001f_0000_0000_ffff;
+    x = (x | x << 16) & 0x001f_0000_ff00_00ff;
+    x = (x | x << 8) & 0x100f_00f0_0f00_f00f;
+    x = (x | x << 4) & 0x10c3_0c30_c30c_30c3;
+    x = (x | x << 2) & 0x1249_2492_4924_9249;
+
+    x
+}
+
+pub(crate) fn compact_by_3(code: u64) -> u32 {
+    let mut x = code & 0x1249_2492_4924_9249;
+    x = (x | x >> 2) & 0x10c3_0c30_c30c_30c3;
+    x = (x | x >> 4) & 0x100f_00f0_0f00_f00f;
+    x = (x | x >> 8) & 0x001f_0000_ff00_00ff;
+    x = (x | x >> 16) & 0x001f_0000_0000_ffff;
+    x = (x | x >> 32) & MASK_3;
+
+    x as u32
+}
+
+/// The mask of the lowest `bits` bits.
+pub(crate) fn low_bits(bits: u32) -> u64 {
+    if bits == 0 {
+        0
+    } else {
+        u64::MAX >> (64 - bits)
+    }
+}
+
+impl<N> ExtentN<N>
+where
+    PointN<N>: MortonEncode + Point + Ones,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Iterate over all points in the extent in Morton order.
+    ///
+    /// The extent is recursively subdivided along the Z-order curve, skipping any nodes that don't
+    /// overlap the extent, so this costs about the same as `iter_points`.
+    pub fn iter_points_morton(&self) -> MortonPointIter<N> {
+        let mut stack = Vec::new();
+        if self.shape > PointN::zero() {
+            let min_code = self.minimum.morton_encode();
+            let max_code = self.max().morton_encode();
+            let diff = min_code ^ max_code;
+            let level = if diff == 0 {
+                0
+            } else {
+                (63 - diff.leading_zeros()) / PointN::<N>::DIMENSIONS + 1
+            };
+            let node_bits = level * PointN::<N>::DIMENSIONS;
+            stack.push((min_code & !low_bits(node_bits), level));
+        }
+
+        MortonPointIter {
+            minimum: self.minimum,
+            max: self.max(),
+            stack,
+        }
+    }
+}
+
+/// An iterator over all points in an `ExtentN<N>` in Morton order. See
+/// `ExtentN::iter_points_morton`.
+pub struct MortonPointIter<N> {
+    minimum: PointN<N>,
+    max: PointN<N>,
+    // The first code and level of each node of the Z-order curve that remains to be visited.
+    stack: Vec<(u64, u32)>,
+}
+
+impl<N> Iterator for MortonPointIter<N>
+where
+    PointN<N>: MortonEncode + Copy + PartialOrd,
+{
+    type Item = PointN<N>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let dims = PointN::<N>::DIMENSIONS;
+        while let Some((code, level)) = self.stack.pop() {
+            let node_min = PointN::morton_decode(code);
+            if level == 0 {
+                // Only nodes that overlap the extent are pushed.
+                return Some(node_min);
+            }
+
+            // Push the children in reverse so they're popped in code order.
+            let child_level = level - 1;
+            let child_bits = child_level * dims;
+            for child in (0..1u64 << dims).rev() {
+                let child_code = code | child << child_bits;
+                let child_min = PointN::morton_decode(child_code);
+                let child_max = PointN::morton_decode(child_code | low_bits(child_bits));
+                if child_min <= self.max && self.minimum <= child_max {
+                    self.stack.push((child_code, child_level));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{Extent2i, Extent3i};
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for p in [
+            PointN([0, 0]),
+            PointN([-1, 1]),
+            PointN([i32::MIN, i32::MAX]),
+            PointN([123_456, -654_321]),
+        ]
+        .iter()
+        {
+            assert_eq!(Point2i::morton_decode(p.morton_encode()), *p);
+        }
+        for p in [
+            PointN([0, 0, 0]),
+            PointN([-1, 1, -1]),
+            PointN([-(1 << 20), (1 << 20) - 1, 5]),
+        ]
+        .iter()
+        {
+            assert_eq!(Point3i::morton_decode(p.morton_encode()), *p);
+        }
+    }
+
+    #[test]
+    fn codes_interleave_bits() {
+        assert_eq!(
+            PointN([1, 0, 0]).morton_encode() - PointN([0, 0, 0]).morton_encode(),
+            0b001
+        );
+        assert_eq!(
+            PointN([0, 1, 0]).morton_encode() - PointN([0, 0, 0]).morton_encode(),
+            0b010
+        );
+        assert_eq!(
+            PointN([0, 0, 1]).morton_encode() - PointN([0, 0, 0]).morton_encode(),
+            0b100
+        );
+        assert_eq!(
+            PointN([2, 0, 0]).morton_encode() - PointN([0, 0, 0]).morton_encode(),
+            0b1000
+        );
+    }
+
+    #[test]
+    fn morton_iter_matches_sorted_points() {
+        let extent = Extent3i::from_min_and_shape(PointN([-3, -1, 2]), PointN([5, 3, 4]));
+        let mut expected: Vec<_> = extent.iter_points().collect();
+        expected.sort_by_key(|p| p.morton_encode());
+        let points: Vec<_> = extent.iter_points_morton().collect();
+        assert_eq!(points, expected);
+
+        let extent = Extent2i::from_min_and_shape(PointN([-2, -2]), PointN([7, 3]));
+        let mut expected: Vec<_> = extent.iter_points().collect();
+        expected.sort_by_key(|p| p.morton_encode());
+        let points: Vec<_> = extent.iter_points_morton().collect();
+        assert_eq!(points, expected);
+    }
+
+    #[test]
+    fn morton_iter_of_empty_extent_is_empty() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([0, 2, 2]));
+        assert_eq!(extent.iter_points_morton().count(), 0);
+    }
+}
@@ -0,0 +1,484 @@
+//! Axis-aligned orientations of the integer lattice, i.e. the symmetries of a square or a cube.
+//!
+//! Every orientation is a permutation of the axes followed by a reflection of some of the axes.
+//! There are 8 such orientations in 2D and 48 in 3D. The ones that don't mirror the lattice are the
+//! rotations, of which there are 4 in 2D and 24 in 3D.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//!
+//! // A quarter turn about the Z axis takes the X axis to the Y axis.
+//! let quarter_turn = Orientation3::quarter_turn_z();
+//! assert_eq!(quarter_turn.apply(&PointN([1, 0, 0])), PointN([0, 1, 0]));
+//!
+//! // Orientations can be composed and inverted.
+//! let half_turn = quarter_turn.then(&quarter_turn);
+//! assert_eq!(half_turn.apply(&PointN([1, 2, 3])), PointN([-1, -2, 3]));
+//! assert_eq!(quarter_turn.then(&quarter_turn.inverse()), Orientation3::IDENTITY);
+//!
+//! assert_eq!(Orientation3::rotations().len(), 24);
+//! assert_eq!(Orientation3::all().len(), 48);
+//! ```
+//!
+//! Extents can also be oriented. The result is the smallest extent containing all of the oriented
+//! points.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! let extent = Extent3i::from_min_and_shape(PointN([0, 0, 0]), PointN([1, 2, 3]));
+//! let oriented = Orientation3::from_permutation(AxisPermutation3::Zyx).apply_to_extent(&extent);
+//! assert_eq!(oriented, Extent3i::from_min_and_shape(PointN([0, 0, 0]), PointN([3, 2, 1])));
+//! ```
+
+use crate::{ExtentN, IntegerExtent, IntegerPoint, Point2i, Point3i, PointN};
+
+/// An axis-aligned orientation of the N-dimensional integer lattice.
+pub trait Orientation<N>: Sized {
+    /// Orients the point `p`.
+    fn apply(&self, p: &PointN<N>) -> PointN<N>;
+
+    /// The orientation that undoes this one.
+    fn inverse(&self) -> Self;
+
+    /// Returns `true` iff this orientation is a rotation, i.e. it doesn't mirror the lattice.
+    fn is_rotation(&self) -> bool;
+
+    /// The smallest extent containing all of the oriented points of `extent`.
+    fn apply_to_extent(&self, extent: &ExtentN<N>) -> ExtentN<N>
+    where
+        PointN<N>: IntegerPoint,
+        ExtentN<N>: IntegerExtent<N>,
+    {
+        let a = self.apply(&extent.minimum);
+        let b = self.apply(&extent.max());
+
+        ExtentN::from_min_and_max(a.meet(&b), a.join(&b))
+    }
+}
+
+/// One of the 2 permutations of the axes of 2D space, named by the swizzle it applies.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AxisPermutation2 {
+    Xy,
+    Yx,
+}
+
+impl AxisPermutation2 {
+    pub const ALL: [Self; 2] = [Self::Xy, Self::Yx];
+
+    /// For each output axis, the input axis it's taken from.
+    pub fn source_axes(&self) -> [usize; 2] {
+        match self {
+            Self::Xy => [0, 1],
+            Self::Yx => [1, 0],
+        }
+    }
+
+    fn from_source_axes(axes: [usize; 2]) -> Self {
+        match axes {
+            [0, 1] => Self::Xy,
+            [1, 0] => Self::Yx,
+            _ => panic!("Not a permutation: {:?}", axes),
+        }
+    }
+
+    /// Returns `true` iff this permutation is made of an even number of swaps.
+    pub fn is_even(&self) -> bool {
+        *self == Self::Xy
+    }
+}
+
+impl Orientation<[i32; 2]> for AxisPermutation2 {
+    fn apply(&self, p: &Point2i) -> Point2i {
+        match self {
+            Self::Xy => *p,
+            Self::Yx => p.yx(),
+        }
+    }
+
+    fn inverse(&self) -> Self {
+        *self
+    }
+
+    fn is_rotation(&self) -> bool {
+        self.is_even()
+    }
+}
+
+/// One of the 6 permutations of the axes of 3D space, named by the swizzle it applies.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AxisPermutation3 {
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+impl AxisPermutation3 {
+    pub const ALL: [Self; 6] = [
+        Self::Xyz,
+        Self::Xzy,
+        Self::Yxz,
+        Self::Yzx,
+        Self::Zxy,
+        Self::Zyx,
+    ];
+
+    /// For each output axis, the input axis it's taken from.
+    pub fn source_axes(&self) -> [usize; 3] {
+        match self {
+            Self::Xyz => [0, 1, 2],
+            Self::Xzy => [0, 2, 1],
+            Self::Yxz => [1, 0, 2],
+            Self::Yzx => [1, 2, 0],
+            Self::Zxy => [2, 0, 1],
+            Self::Zyx => [2, 1, 0],
+        }
+    }
+
+    fn from_source_axes(axes: [usize; 3]) -> Self {
+        match axes {
+            [0, 1, 2] => Self::Xyz,
+            [0, 2, 1] => Self::Xzy,
+            [1, 0, 2] => Self::Yxz,
+            [1, 2, 0] => Self::Yzx,
+            [2, 0, 1] => Self::Zxy,
+            [2, 1, 0] => Self::Zyx,
+            _ => panic!("Not a permutation: {:?}", axes),
+        }
+    }
+
+    /// Returns `true` iff this permutation is made of an even number of swaps.
+    pub fn is_even(&self) -> bool {
+        matches!(self, Self::Xyz | Self::Yzx | Self::Zxy)
+    }
+}
+
+impl Orientation<[i32; 3]> for AxisPermutation3 {
+    fn apply(&self, p: &Point3i) -> Point3i {
+        match self {
+            Self::Xyz => *p,
+            Self::Xzy => p.xzy(),
+            Self::Yxz => p.yxz(),
+            Self::Yzx => p.yzx(),
+            Self::Zxy => p.zxy(),
+            Self::Zyx => p.zyx(),
+        }
+    }
+
+    fn inverse(&self) -> Self {
+        match self {
+            Self::Yzx => Self::Zxy,
+            Self::Zxy => Self::Yzx,
+            other => *other,
+        }
+    }
+
+    fn is_rotation(&self) -> bool {
+        self.is_even()
+    }
+}
+
+/// An orientation of 2D space: permute the axes, then negate the axes where `flips` is `true`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Orientation2 {
+    pub permutation: AxisPermutation2,
+    pub flips: [bool; 2],
+}
+
+impl Orientation2 {
+    pub const IDENTITY: Self = Self {
+        permutation: AxisPermutation2::Xy,
+        flips: [false; 2],
+    };
+
+    pub fn from_permutation(permutation: AxisPermutation2) -> Self {
+        Self {
+            permutation,
+            flips: [false; 2],
+        }
+    }
+
+    /// Mirrors the axes where `flips` is `true`.
+    pub fn from_flips(flips: [bool; 2]) -> Self {
+        Self {
+            permutation: AxisPermutation2::Xy,
+            flips,
+        }
+    }
+
+    /// The counter-clockwise quarter turn, which takes the X axis to the Y axis.
+    pub fn quarter_turn() -> Self {
+        Self {
+            permutation: AxisPermutation2::Yx,
+            flips: [true, false],
+        }
+    }
+
+    /// All 8 orientations.
+    pub fn all() -> Vec<Self> {
+        let mut all = Vec::with_capacity(8);
+        for permutation in AxisPermutation2::ALL.iter() {
+            for i in 0..4 {
+                all.push(Self {
+                    permutation: *permutation,
+                    flips: [i & 1 != 0, i & 2 != 0],
+                });
+            }
+        }
+
+        all
+    }
+
+    /// All 4 rotations.
+    pub fn rotations() -> Vec<Self> {
+        Self::all()
+            .into_iter()
+            .filter(|o| o.is_rotation())
+            .collect()
+    }
+
+    /// The orientation that applies `self` and then `other`.
+    pub fn then(&self, other: &Self) -> Self {
+        // The image of each basis vector determines the orientation.
+        let image = other.apply(&self.apply(&PointN([1, 2])));
+
+        Self {
+            permutation: AxisPermutation2::from_source_axes([
+                (image.x().abs() - 1) as usize,
+                (image.y().abs() - 1) as usize,
+            ]),
+            flips: [image.x() < 0, image.y() < 0],
+        }
+    }
+}
+
+impl Orientation<[i32; 2]> for Orientation2 {
+    fn apply(&self, p: &Point2i) -> Point2i {
+        let p = self.permutation.apply(p);
+
+        PointN([
+            if self.flips[0] { -p.x() } else { p.x() },
+            if self.flips[1] { -p.y() } else { p.y() },
+        ])
+    }
+
+    fn inverse(&self) -> Self {
+        let inverse_permutation = self.permutation.inverse();
+        let sources = inverse_permutation.source_axes();
+
+        Self {
+            permutation: inverse_permutation,
+            flips: [self.flips[sources[0]], self.flips[sources[1]]],
+        }
+    }
+
+    fn is_rotation(&self) -> bool {
+        let num_flips = self.flips.iter().filter(|f| **f).count();
+
+        self.permutation.is_even() == (num_flips % 2 == 0)
+    }
+}
+
+/// An orientation of 3D space: permute the axes, then negate the axes where `flips` is `true`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Orientation3 {
+    pub permutation: AxisPermutation3,
+    pub flips: [bool; 3],
+}
+
+impl Orientation3 {
+    pub const IDENTITY: Self = Self {
+        permutation: AxisPermutation3::Xyz,
+        flips: [false; 3],
+    };
+
+    pub fn from_permutation(permutation: AxisPermutation3) -> Self {
+        Self {
+            permutation,
+            flips: [false; 3],
+        }
+    }
+
+    /// Mirrors the axes where `flips` is `true`.
+    pub fn from_flips(flips: [bool; 3]) -> Self {
+        Self {
+            permutation: AxisPermutation3::Xyz,
+            flips,
+        }
+    }
+
+    /// The right-handed quarter turn about the X axis, which takes the Y axis to the Z axis.
+    pub fn quarter_turn_x() -> Self {
+        Self {
+            permutation: AxisPermutation3::Xzy,
+            flips: [false, true, false],
+        }
+    }
+
+    /// The right-handed quarter turn about the Y axis, which takes the Z axis to the X axis.
+    pub fn quarter_turn_y() -> Self {
+        Self {
+            permutation: AxisPermutation3::Zyx,
+            flips: [false, false, true],
+        }
+    }
+
+    /// The right-handed quarter turn about the Z axis, which takes the X axis to the Y axis.
+    pub fn quarter_turn_z() -> Self {
+        Self {
+            permutation: AxisPermutation3::Yxz,
+            flips: [true, false, false],
+        }
+    }
+
+    /// All 48 orientations.
+    pub fn all() -> Vec<Self> {
+        let mut all = Vec::with_capacity(48);
+        for permutation in AxisPermutation3::ALL.iter() {
+            for i in 0..8 {
+                all.push(Self {
+                    permutation: *permutation,
+                    flips: [i & 1 != 0, i & 2 != 0, i & 4 != 0],
+                });
+            }
+        }
+
+        all
+    }
+
+    /// All 24 rotations.
+    pub fn rotations() -> Vec<Self> {
+        Self::all()
+            .into_iter()
+            .filter(|o| o.is_rotation())
+            .collect()
+    }
+
+    /// The orientation that applies `self` and then `other`.
+    pub fn then(&self, other: &Self) -> Self {
+        // The image of each basis vector determines the orientation.
+        let image = other.apply(&self.apply(&PointN([1, 2, 3])));
+
+        Self {
+            permutation: AxisPermutation3::from_source_axes([
+                (image.x().abs() - 1) as usize,
+                (image.y().abs() - 1) as usize,
+                (image.z().abs() - 1) as usize,
+            ]),
+            flips: [image.x() < 0, image.y() < 0, image.z() < 0],
+        }
+    }
+}
+
+impl Orientation<[i32; 3]> for Orientation3 {
+    fn apply(&self, p: &Point3i) -> Point3i {
+        let p = self.permutation.apply(p);
+
+        PointN([
+            if self.flips[0] { -p.x() } else { p.x() },
+            if self.flips[1] { -p.y() } else { p.y() },
+            if self.flips[2] { -p.z() } else { p.z() },
+        ])
+    }
+
+    fn inverse(&self) -> Self {
+        let inverse_permutation = self.permutation.inverse();
+        let sources = inverse_permutation.source_axes();
+
+        Self {
+            permutation: inverse_permutation,
+            flips: [
+                self.flips[sources[0]],
+                self.flips[sources[1]],
+                self.flips[sources[2]],
+            ],
+        }
+    }
+
+    fn is_rotation(&self) -> bool {
+        let num_flips = self.flips.iter().filter(|f| **f).count();
+
+        self.permutation.is_even() == (num_flips % 2 == 0)
+    }
+}
+
+impl From<AxisPermutation2> for Orientation2 {
+    fn from(permutation: AxisPermutation2) -> Self {
+        Self::from_permutation(permutation)
+    }
+}
+
+impl From<AxisPermutation3> for Orientation3 {
+    fn from(permutation: AxisPermutation3) -> Self {
+        Self::from_permutation(permutation)
+    }
+}
+
+// ████████╗███████╗███████╗████████╗███████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝██╔════╝
+//    ██║   █████╗  ███████╗   ██║   ███████╗
+//    ██║   ██╔══╝  ╚════██║   ██║   ╚════██║
+//    ██║   ███████╗███████║   ██║   ███████║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝   ╚══════╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Extent3i;
+
+    #[test]
+    fn inverse_undoes_orientation() {
+        let p = PointN([1, 2, 3]);
+        for o in Orientation3::all() {
+            assert_eq!(o.inverse().apply(&o.apply(&p)), p);
+            assert_eq!(o.then(&o.inverse()), Orientation3::IDENTITY);
+        }
+
+        let p = PointN([1, 2]);
+        for o in Orientation2::all() {
+            assert_eq!(o.inverse().apply(&o.apply(&p)), p);
+            assert_eq!(o.then(&o.inverse()), Orientation2::IDENTITY);
+        }
+    }
+
+    #[test]
+    fn quarter_turns_generate_all_rotations() {
+        let generators = [
+            Orientation3::quarter_turn_x(),
+            Orientation3::quarter_turn_y(),
+            Orientation3::quarter_turn_z(),
+        ];
+        let mut generated = vec![Orientation3::IDENTITY];
+        let mut i = 0;
+        while i < generated.len() {
+            for g in generators.iter() {
+                let o = generated[i].then(g);
+                assert!(o.is_rotation());
+                if !generated.contains(&o) {
+                    generated.push(o);
+                }
+            }
+            i += 1;
+        }
+
+        assert_eq!(generated.len(), 24);
+        for o in Orientation3::rotations() {
+            assert!(generated.contains(&o));
+        }
+    }
+
+    #[test]
+    fn oriented_extent_contains_oriented_points() {
+        let extent = Extent3i::from_min_and_shape(PointN([-1, 2, 5]), PointN([2, 3, 4]));
+        for o in Orientation3::all() {
+            let oriented = o.apply_to_extent(&extent);
+            assert_eq!(oriented.num_points(), extent.num_points());
+            for p in extent.iter_points() {
+                assert!(oriented.contains(&o.apply(&p)));
+            }
+        }
+    }
+}
@@ -68,6 +68,14 @@ where
         PointN([self.x(), self.z()])
     }
 
+    pub fn xzy(&self) -> Point3<T> {
+        PointN([self.x(), self.z(), self.y()])
+    }
+
+    pub fn yxz(&self) -> Point3<T> {
+        PointN([self.y(), self.x(), self.z()])
+    }
+
     pub fn yzx(&self) -> Point3<T> {
         PointN([self.y(), self.z(), self.x()])
     }
@@ -0,0 +1,566 @@
+//! Iterators over the lattice points inside of simple geometric shapes, like balls, shells,
+//! capsules, cylinders and disks.
+//!
+//! Every shape implements `LatticeShape`, which knows the shape's bounding extent and can find the
+//! points in any row (a line parallel to the x axis) directly. So `LatticeShape::iter_points`
+//! yields points in the same x-fastest order as `IntegerExtent::iter_points`, without testing any
+//! points that end up outside of the shape.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//!
+//! let ball = Ball { center: PointN([0, 0, 0]), radius: 1.0 };
+//! let points: Vec<_> = ball.iter_points().collect();
+//! assert_eq!(points.len(), 7);
+//! assert!(points.iter().all(|p| ball.contains(p)));
+//!
+//! let disk = Disk { center: PointN([5, 5]), radius: 2.5 };
+//! assert_eq!(
+//!     disk.bounding_extent(),
+//!     Extent2i::from_min_and_shape(PointN([3, 3]), PointN([5, 5]))
+//! );
+//! ```
+
+use crate::{ExtentN, IntegerExtent, IntegerPoint, Point2i, Point3i, PointN};
+
+/// The points of a shape in a single row, as at most two inclusive ranges of x coordinates, in
+/// increasing order.
+pub type RowSpans = [Option<(i32, i32)>; 2];
+
+/// A set of lattice points that can be enumerated one row at a time.
+pub trait LatticeShape<N> {
+    /// An extent that contains every point of the shape.
+    fn bounding_extent(&self) -> ExtentN<N>;
+
+    /// Returns `true` iff `p` is in the shape.
+    fn contains(&self, p: &PointN<N>) -> bool;
+
+    /// The points of the shape in the row through `row`. The x coordinate of `row` is ignored.
+    fn row_spans(&self, row: &PointN<N>) -> RowSpans;
+
+    /// Iterate over all points in the shape in x-fastest order.
+    fn iter_points(&self) -> ShapePointIter<N, Self>
+    where
+        Self: Clone,
+        N: AsMut<[i32]> + Copy,
+        ExtentN<N>: IntegerExtent<N>,
+    {
+        let bounds = self.bounding_extent();
+        let mut row_shape = bounds.shape;
+        let shape_x = &mut row_shape.0.as_mut()[0];
+        *shape_x = (*shape_x).min(1);
+
+        ShapePointIter {
+            shape: self.clone(),
+            rows: ExtentN::from_min_and_shape(bounds.minimum, row_shape).iter_points(),
+            row: bounds.minimum,
+            spans: [None, None],
+            next_x: 0,
+        }
+    }
+}
+
+/// An iterator over the points in a `LatticeShape`. See `LatticeShape::iter_points`.
+pub struct ShapePointIter<N, S>
+where
+    ExtentN<N>: IntegerExtent<N>,
+{
+    shape: S,
+    rows: <ExtentN<N> as IntegerExtent<N>>::PointIter,
+    row: PointN<N>,
+    // The spans of the current row that haven't been finished yet.
+    spans: RowSpans,
+    next_x: i32,
+}
+
+impl<N, S> Iterator for ShapePointIter<N, S>
+where
+    N: AsMut<[i32]> + Copy,
+    S: LatticeShape<N>,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Item = PointN<N>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((_, last_x)) = self.spans[0] {
+                if self.next_x <= last_x {
+                    let mut p = self.row;
+                    p.0.as_mut()[0] = self.next_x;
+                    self.next_x += 1;
+
+                    return Some(p);
+                }
+                self.spans = [self.spans[1], None];
+                if let Some((first_x, _)) = self.spans[0] {
+                    self.next_x = first_x;
+                }
+                continue;
+            }
+
+            self.row = self.rows.next()?;
+            self.spans = self.shape.row_spans(&self.row);
+            if self.spans[0].is_none() {
+                self.spans = [self.spans[1], None];
+            }
+            if let Some((first_x, _)) = self.spans[0] {
+                self.next_x = first_x;
+            }
+        }
+    }
+}
+
+/// The points within `radius` of `center` in 3D, including the boundary.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ball {
+    pub center: Point3i,
+    pub radius: f32,
+}
+
+/// The points whose distance `d` from `center` satisfies `inner_radius < d <= outer_radius`. Shells
+/// with consecutive radii partition a ball.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shell {
+    pub center: Point3i,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+}
+
+/// The points within `radius` of the segment from `start` to `end`, including the boundary.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Capsule {
+    pub start: Point3i,
+    pub end: Point3i,
+    pub radius: f32,
+}
+
+/// The points within `radius` of the axis from `start` to `end` whose projections onto the axis
+/// land on the segment between them. A cylinder with `start == end` is empty.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cylinder {
+    pub start: Point3i,
+    pub end: Point3i,
+    pub radius: f32,
+}
+
+/// The points within `radius` of `center` in 2D, including the boundary.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Disk {
+    pub center: Point2i,
+    pub radius: f32,
+}
+
+impl LatticeShape<[i32; 3]> for Ball {
+    fn bounding_extent(&self) -> ExtentN<[i32; 3]> {
+        ball_bounding_extent(self.center, self.radius)
+    }
+
+    fn contains(&self, p: &Point3i) -> bool {
+        distance_squared(p.0, self.center.0) <= square(self.radius)
+    }
+
+    fn row_spans(&self, row: &Point3i) -> RowSpans {
+        [ball_row_span(self.center, self.radius, row), None]
+    }
+}
+
+impl LatticeShape<[i32; 3]> for Shell {
+    fn bounding_extent(&self) -> ExtentN<[i32; 3]> {
+        ball_bounding_extent(self.center, self.outer_radius)
+    }
+
+    fn contains(&self, p: &Point3i) -> bool {
+        let d2 = distance_squared(p.0, self.center.0);
+
+        square(self.inner_radius) < d2 && d2 <= square(self.outer_radius)
+    }
+
+    fn row_spans(&self, row: &Point3i) -> RowSpans {
+        let outer = ball_row_span(self.center, self.outer_radius, row);
+        let inner = ball_row_span(self.center, self.inner_radius, row);
+        match (outer, inner) {
+            (Some((lo, hi)), Some((inner_lo, inner_hi))) => {
+                let left = if lo < inner_lo {
+                    Some((lo, inner_lo - 1))
+                } else {
+                    None
+                };
+                let right = if inner_hi < hi {
+                    Some((inner_hi + 1, hi))
+                } else {
+                    None
+                };
+
+                [left, right]
+            }
+            (outer, None) => [outer, None],
+            (None, Some(_)) => [None, None],
+        }
+    }
+}
+
+impl LatticeShape<[i32; 3]> for Capsule {
+    fn bounding_extent(&self) -> ExtentN<[i32; 3]> {
+        let start = ball_bounding_extent(self.start, self.radius);
+        let end = ball_bounding_extent(self.end, self.radius);
+        let minimum = start.minimum.meet(&end.minimum);
+        let lub = start.least_upper_bound().join(&end.least_upper_bound());
+
+        ExtentN::from_min_and_lub(minimum, lub)
+    }
+
+    fn contains(&self, p: &Point3i) -> bool {
+        let v = difference(self.end.0, self.start.0);
+        let w = difference(p.0, self.start.0);
+        let vv = dot(v, v);
+        let t = if vv == 0.0 {
+            0.0
+        } else {
+            (dot(w, v) / vv).clamp(0.0, 1.0)
+        };
+        let perp = [w[0] - t * v[0], w[1] - t * v[1], w[2] - t * v[2]];
+
+        dot(perp, perp) <= square(self.radius)
+    }
+
+    fn row_spans(&self, row: &Point3i) -> RowSpans {
+        // A capsule is convex, so its row is the union of the rows of its end caps and body, which
+        // is a single span.
+        let cylinder = Cylinder {
+            start: self.start,
+            end: self.end,
+            radius: self.radius,
+        };
+        let parts = [
+            ball_row_span(self.start, self.radius, row),
+            ball_row_span(self.end, self.radius, row),
+            cylinder.row_spans(row)[0],
+        ];
+        let span = parts
+            .iter()
+            .flatten()
+            .fold(None, |acc, &(lo, hi)| match acc {
+                None => Some((lo, hi)),
+                Some((acc_lo, acc_hi)) => Some((lo.min(acc_lo), hi.max(acc_hi))),
+            });
+
+        [span, None]
+    }
+}
+
+impl LatticeShape<[i32; 3]> for Cylinder {
+    fn bounding_extent(&self) -> ExtentN<[i32; 3]> {
+        let v = difference(self.end.0, self.start.0);
+        let vv = dot(v, v);
+        if vv == 0.0 {
+            return ExtentN::from_min_and_shape(self.start, PointN([0; 3]));
+        }
+
+        // The end caps are disks perpendicular to the axis.
+        let mut minimum = [0; 3];
+        let mut max = [0; 3];
+        for i in 0..3 {
+            let reach = self.radius as f64 * (1.0 - v[i] * v[i] / vv).max(0.0).sqrt();
+            let lo = self.start.0[i].min(self.end.0[i]) as f64;
+            let hi = self.start.0[i].max(self.end.0[i]) as f64;
+            minimum[i] = (lo - reach).floor() as i32;
+            max[i] = (hi + reach).ceil() as i32;
+        }
+
+        ExtentN::from_min_and_max(PointN(minimum), PointN(max))
+    }
+
+    fn contains(&self, p: &Point3i) -> bool {
+        let v = difference(self.end.0, self.start.0);
+        let w = difference(p.0, self.start.0);
+        let vv = dot(v, v);
+        let s = dot(w, v);
+
+        vv > 0.0 && 0.0 <= s && s <= vv && dot(w, w) * vv - s * s <= square(self.radius) * vv
+    }
+
+    fn row_spans(&self, row: &Point3i) -> RowSpans {
+        let v = difference(self.end.0, self.start.0);
+        let vv = dot(v, v);
+        if vv == 0.0 {
+            return [None, None];
+        }
+
+        // The points of the row are `start + w0 + x * (1, 0, 0)`.
+        let w0 = [
+            -self.start.x() as f64,
+            (row.y() - self.start.y()) as f64,
+            (row.z() - self.start.z()) as f64,
+        ];
+        let s0 = dot(w0, v);
+
+        // Solve for the x range where the projection onto the axis lands on the segment.
+        let (mut lo, mut hi) = if v[0] == 0.0 {
+            if s0 < 0.0 || s0 > vv {
+                return [None, None];
+            }
+            (f64::NEG_INFINITY, f64::INFINITY)
+        } else {
+            let a = -s0 / v[0];
+            let b = (vv - s0) / v[0];
+            (a.min(b), a.max(b))
+        };
+
+        // Solve the quadratic inequality for the x range within `radius` of the axis.
+        let qa = 1.0 - v[0] * v[0] / vv;
+        let qb = 2.0 * (w0[0] - s0 * v[0] / vv);
+        let qc = dot(w0, w0) - s0 * s0 / vv - square(self.radius);
+        if qa > 1e-12 {
+            let discriminant = qb * qb - 4.0 * qa * qc;
+            if discriminant < 0.0 {
+                return [None, None];
+            }
+            let root = discriminant.sqrt();
+            lo = lo.max((-qb - root) / (2.0 * qa));
+            hi = hi.min((-qb + root) / (2.0 * qa));
+        } else if qc > 0.0 {
+            // The axis is parallel to the row, so the distance doesn't depend on x.
+            return [None, None];
+        }
+
+        let bounds = self.bounding_extent();
+        lo = lo.max(bounds.minimum.x() as f64);
+        hi = hi.min(bounds.max().x() as f64);
+
+        [
+            convex_row_span(lo, hi, |x| self.contains(&PointN([x, row.y(), row.z()]))),
+            None,
+        ]
+    }
+}
+
+impl LatticeShape<[i32; 2]> for Disk {
+    fn bounding_extent(&self) -> ExtentN<[i32; 2]> {
+        let r = self.radius.max(-1.0).floor() as i32;
+
+        ExtentN::from_min_and_shape(self.center - PointN([r; 2]), PointN([2 * r + 1; 2]))
+    }
+
+    fn contains(&self, p: &Point2i) -> bool {
+        let dx = (p.x() - self.center.x()) as f64;
+        let dy = (p.y() - self.center.y()) as f64;
+
+        dx * dx + dy * dy <= square(self.radius)
+    }
+
+    fn row_spans(&self, row: &Point2i) -> RowSpans {
+        let dy = (row.y() - self.center.y()) as f64;
+        let remaining = square(self.radius) - dy * dy;
+        if remaining < 0.0 {
+            return [None, None];
+        }
+        let half_width = remaining.sqrt();
+        let cx = self.center.x() as f64;
+
+        [
+            convex_row_span(cx - half_width, cx + half_width, |x| {
+                self.contains(&PointN([x, row.y()]))
+            }),
+            None,
+        ]
+    }
+}
+
+fn ball_bounding_extent(center: Point3i, radius: f32) -> ExtentN<[i32; 3]> {
+    let r = radius.max(-1.0).floor() as i32;
+
+    ExtentN::from_min_and_shape(center - PointN([r; 3]), PointN([2 * r + 1; 3]))
+}
+
+fn ball_row_span(center: Point3i, radius: f32, row: &Point3i) -> Option<(i32, i32)> {
+    let dy = (row.y() - center.y()) as f64;
+    let dz = (row.z() - center.z()) as f64;
+    let remaining = square(radius) - dy * dy - dz * dz;
+    if remaining < 0.0 {
+        return None;
+    }
+    let half_width = remaining.sqrt();
+    let cx = center.x() as f64;
+    let ball = Ball { center, radius };
+
+    convex_row_span(cx - half_width, cx + half_width, |x| {
+        ball.contains(&PointN([x, row.y(), row.z()]))
+    })
+}
+
+/// Rounds the approximate x range `[lo, hi]` of a convex shape's row to the exact range of lattice
+/// points where `contains` is true.
+fn convex_row_span(lo: f64, hi: f64, contains: impl Fn(i32) -> bool) -> Option<(i32, i32)> {
+    const SLACK: f64 = 1e-6;
+
+    let mut first = (lo - SLACK).ceil() as i32;
+    let mut last = (hi + SLACK).floor() as i32;
+    while first <= last && !contains(first) {
+        first += 1;
+    }
+    while last >= first && !contains(last) {
+        last -= 1;
+    }
+
+    if first <= last {
+        Some((first, last))
+    } else {
+        None
+    }
+}
+
+fn square(x: f32) -> f64 {
+    let x = x as f64;
+
+    x * x
+}
+
+fn difference(a: [i32; 3], b: [i32; 3]) -> [f64; 3] {
+    [
+        (a[0] - b[0]) as f64,
+        (a[1] - b[1]) as f64,
+        (a[2] - b[2]) as f64,
+    ]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn distance_squared(a: [i32; 3], b: [i32; 3]) -> f64 {
+    let d = difference(a, b);
+
+    dot(d, d)
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_matches_filtered_bounds<N, S>(shape: &S, padding: i32)
+    where
+        N: AsMut<[i32]> + Copy,
+        S: LatticeShape<N> + Clone,
+        PointN<N>: IntegerPoint<Scalar = i32> + std::fmt::Debug + PartialEq,
+        ExtentN<N>: IntegerExtent<N>,
+    {
+        let points: Vec<_> = shape.iter_points().collect();
+        let expected: Vec<_> = shape
+            .bounding_extent()
+            .iter_points()
+            .filter(|p| shape.contains(p))
+            .collect();
+        assert_eq!(points, expected);
+        assert!(!points.is_empty());
+
+        // Nothing is left outside of the bounding extent.
+        let num_padded = shape
+            .bounding_extent()
+            .padded(padding)
+            .iter_points()
+            .filter(|p| shape.contains(p))
+            .count();
+        assert_eq!(num_padded, points.len());
+    }
+
+    #[test]
+    fn ball_and_disk() {
+        let ball = Ball {
+            center: PointN([1, -2, 3]),
+            radius: 4.5,
+        };
+        assert_matches_filtered_bounds(&ball, 2);
+        assert_eq!(
+            Ball {
+                center: PointN([0; 3]),
+                radius: 1.0
+            }
+            .iter_points()
+            .count(),
+            7
+        );
+
+        let disk = Disk {
+            center: PointN([-3, 2]),
+            radius: 3.0,
+        };
+        assert_matches_filtered_bounds(&disk, 2);
+    }
+
+    #[test]
+    fn shells_partition_a_ball() {
+        let center = PointN([2, 0, -1]);
+        let shell = Shell {
+            center,
+            inner_radius: 2.0,
+            outer_radius: 4.0,
+        };
+        assert_matches_filtered_bounds(&shell, 2);
+
+        let ball = Ball {
+            center,
+            radius: 4.0,
+        };
+        let num_in_shells: usize = (0..4)
+            .map(|r| {
+                Shell {
+                    center,
+                    inner_radius: r as f32,
+                    outer_radius: r as f32 + 1.0,
+                }
+                .iter_points()
+                .count()
+            })
+            .sum();
+        assert_eq!(num_in_shells + 1, ball.iter_points().count());
+    }
+
+    #[test]
+    fn capsule_and_cylinder() {
+        let capsule = Capsule {
+            start: PointN([-3, 1, 0]),
+            end: PointN([4, -2, 5]),
+            radius: 2.5,
+        };
+        assert_matches_filtered_bounds(&capsule, 3);
+
+        let cylinder = Cylinder {
+            start: PointN([-3, 1, 0]),
+            end: PointN([4, -2, 5]),
+            radius: 2.5,
+        };
+        assert_matches_filtered_bounds(&cylinder, 3);
+
+        // Parallel to the rows.
+        let cylinder = Cylinder {
+            start: PointN([0, 0, 0]),
+            end: PointN([5, 0, 0]),
+            radius: 1.0,
+        };
+        assert_matches_filtered_bounds(&cylinder, 2);
+        assert_eq!(cylinder.iter_points().count(), 6 * 5);
+    }
+
+    #[test]
+    fn empty_shapes() {
+        let cylinder = Cylinder {
+            start: PointN([1; 3]),
+            end: PointN([1; 3]),
+            radius: 2.0,
+        };
+        assert_eq!(cylinder.iter_points().count(), 0);
+
+        let ball = Ball {
+            center: PointN([0; 3]),
+            radius: -1.0,
+        };
+        assert_eq!(ball.iter_points().count(), 0);
+    }
+}
@@ -1,6 +1,6 @@
 use building_blocks_core::prelude::*;
 use building_blocks_partition::octree::Octree;
-use building_blocks_storage::{prelude::*, IsEmpty};
+use building_blocks_storage::{prelude::*, IsEmpty, MortonArray3};
 
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 
@@ -26,7 +26,7 @@ fn octree_from_array_sphere(c: &mut Criterion) {
                         let center = PointN([0; 3]);
                         let map_extent = *map.extent();
                         map.for_each_mut(&map_extent, |p: Point3i, value| {
-                            if p.l2_distance_squared(&center) <= sphere_radius * sphere_radius {
+                            if (p - center).dot(&(p - center)) <= sphere_radius * sphere_radius {
                                 *value = Voxel(true)
                             }
                         });
@@ -41,6 +41,43 @@ fn octree_from_array_sphere(c: &mut Criterion) {
     group.finish();
 }
 
+fn octree_from_morton_array_sphere(c: &mut Criterion) {
+    let mut group = c.benchmark_group("octree_from_morton_array_sphere");
+    for power in [4, 5, 6].iter() {
+        let edge_len = 1 << *power;
+        group.bench_with_input(
+            BenchmarkId::from_parameter(edge_len),
+            &(power, edge_len),
+            |b, &(&power, edge_len)| {
+                b.iter_with_setup(
+                    || {
+                        let sphere_radius = edge_len / 2;
+                        let mut map = Array3::fill(
+                            Extent3i::from_min_and_shape(
+                                PointN([-sphere_radius; 3]),
+                                PointN([2 * sphere_radius; 3]),
+                            ),
+                            Voxel(false),
+                        );
+
+                        let center = PointN([0; 3]);
+                        let map_extent = *map.extent();
+                        map.for_each_mut(&map_extent, |p: Point3i, value| {
+                            if (p - center).dot(&(p - center)) <= sphere_radius * sphere_radius {
+                                *value = Voxel(true)
+                            }
+                        });
+
+                        (MortonArray3::from_array(&map), power)
+                    },
+                    |(map, power)| Octree::from_morton_array(power, &map),
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
 fn full_octree(c: &mut Criterion) {
     let mut group = c.benchmark_group("full_octree");
     for power in [4, 5, 6].iter() {
@@ -66,7 +103,12 @@ fn full_octree(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, octree_from_array_sphere, full_octree);
+criterion_group!(
+    benches,
+    octree_from_array_sphere,
+    octree_from_morton_array_sphere,
+    full_octree
+);
 criterion_main!(benches);
 
 #[derive(Clone)]
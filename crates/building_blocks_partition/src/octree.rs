@@ -4,7 +4,7 @@
 //! into an `OctreeDBVT` in order to perform spatial queries like raycasting.
 
 use building_blocks_core::prelude::*;
-use building_blocks_storage::{prelude::*, IsEmpty};
+use building_blocks_storage::{prelude::*, IsEmpty, Local, MortonArray3};
 
 use fnv::FnvHashMap;
 
@@ -26,21 +26,68 @@ impl Octree {
     /// Constructs an `Octree` which contains all of the points which are not empty (as defined by
     /// the `IsEmpty` trait). `array` must be cube-shaped with edge length being a power of 2.
     /// `power` must be the exponent of the edge length, and `0 < power <= 6`.
-    pub fn from_array<T: IsEmpty>(power: u8, array: &Array3<T>) -> Self {
+    ///
+    /// Any array type with the `Array3` layout can be used, like a `BitArray3` occupancy mask. For
+    /// better cache locality, see `from_morton_array`.
+    pub fn from_array<A, T>(power: u8, array: &A) -> Self
+    where
+        A: Array<[i32; 3]> + GetRef<Stride, Data = T>,
+        T: IsEmpty,
+    {
+        Self::from_layout(
+            power,
+            array.extent(),
+            |corner_offsets, corner_strides| {
+                array.strides_from_points(corner_offsets, corner_strides)
+            },
+            |stride| array.get_ref(stride).is_empty(),
+        )
+    }
+
+    /// Like `from_array`, but every octant of a `MortonArray3` is a contiguous run of values, so
+    /// partitioning visits the array in order instead of jumping between rows and planes.
+    pub fn from_morton_array<T>(power: u8, array: &MortonArray3<T>) -> Self
+    where
+        T: IsEmpty,
+    {
+        Self::from_layout(
+            power,
+            array.extent(),
+            |corner_offsets, corner_strides| {
+                for (stride, offset) in corner_strides.iter_mut().zip(corner_offsets.iter()) {
+                    *stride = MortonArray3::<T>::stride_from_local_point(&Local(*offset));
+                }
+            },
+            |stride| array.get_ref(stride).is_empty(),
+        )
+    }
+
+    fn from_layout(
+        power: u8,
+        extent: &Extent3i,
+        strides_from_points: impl Fn(&[Point3i], &mut [Stride]),
+        is_empty: impl Fn(Stride) -> bool,
+    ) -> Self {
         // Constrained by 16-bit location code.
         assert!(power > 0 && power <= 6);
         let root_level = power - 1;
         let edge_len = 1 << power;
-        assert_eq!(PointN([edge_len; 3]), array.extent().shape);
-
-        // These are the corners of the root octant, in local coordinates.
-        let corner_offsets: Vec<_> = Point3i::corner_offsets()
-            .into_iter()
-            .map(|p| p * edge_len)
+        assert_eq!(PointN([edge_len; 3]), extent.shape);
+
+        // These are the corners of the children of an octant at each level, in local coordinates.
+        // They're converted into strides for indexing efficiency.
+        let level_corner_strides: Vec<_> = (0..power)
+            .map(|level| {
+                let corner_offsets: Vec<_> = Point3i::corner_offsets()
+                    .into_iter()
+                    .map(|p| p * (1 << level))
+                    .collect();
+                let mut corner_strides = [Stride(0); 8];
+                strides_from_points(&corner_offsets, &mut corner_strides);
+
+                corner_strides
+            })
             .collect();
-        // Convert into strides for indexing efficiency.
-        let mut corner_strides = [Stride(0); 8];
-        array.strides_from_points(&corner_offsets, &mut corner_strides);
 
         let mut nodes = FnvHashMap::default();
         let root_minimum = Stride(0);
@@ -48,52 +95,48 @@ impl Octree {
         let root_exists = Self::partition_array(
             root_location,
             root_minimum,
-            edge_len,
-            &corner_strides,
-            array,
+            power,
+            &level_corner_strides,
+            &is_empty,
             &mut nodes,
         );
 
         Octree {
             root_level,
             root_exists,
-            extent: *array.extent(),
+            extent: *extent,
             nodes,
         }
     }
 
-    fn partition_array<T: IsEmpty>(
+    fn partition_array(
         location: LocationCode,
         minimum: Stride,
-        edge_len: i32,
-        corner_strides: &[Stride],
-        array: &Array3<T>,
+        power: u8,
+        level_corner_strides: &[[Stride; 8]],
+        is_empty: &impl Fn(Stride) -> bool,
         nodes: &mut FnvHashMap<LocationCode, ChildBitMask>,
     ) -> bool {
         // Base case where the octant is a single voxel.
-        if edge_len == 1 {
-            return !array.get_ref(minimum).is_empty();
-        }
-
-        let mut octant_corner_strides = [Stride(0); 8];
-        for (child_corner, parent_corner) in
-            octant_corner_strides.iter_mut().zip(corner_strides.iter())
-        {
-            *child_corner = Stride(parent_corner.0 >> 1);
+        if power == 0 {
+            return !is_empty(minimum);
         }
 
-        let half_edge_len = edge_len >> 1;
+        let child_power = power - 1;
         let mut child_bitmask = 0;
         let extended_location = location.extend();
-        for (octant, offset) in octant_corner_strides.iter().enumerate() {
+        for (octant, offset) in level_corner_strides[child_power as usize]
+            .iter()
+            .enumerate()
+        {
             let octant_min = minimum + *offset;
             let octant_location = extended_location.with_lowest_octant(octant as u16);
             let child_exists = Self::partition_array(
                 octant_location,
                 octant_min,
-                half_edge_len,
-                &octant_corner_strides,
-                array,
+                child_power,
+                level_corner_strides,
+                is_empty,
                 nodes,
             );
             child_bitmask |= (child_exists as u8) << octant;
@@ -297,3 +340,59 @@ mod ncollide_support {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use building_blocks_storage::BitArray3;
+
+    #[test]
+    fn bit_array_and_dense_array_make_the_same_octree() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16; 3]));
+        let dense = Array3::fill_with(extent, |p| {
+            let d = *p - PointN([8; 3]);
+            d.x() * d.x() + d.y() * d.y() + d.z() * d.z() < 36
+        });
+        let bits = BitArray3::from_array(&dense);
+
+        let dense_octants = visited_octants(&Octree::from_array(4, &dense));
+        let bit_octants = visited_octants(&Octree::from_array(4, &bits));
+
+        assert!(!dense_octants.is_empty());
+        assert_eq!(dense_octants, bit_octants);
+    }
+
+    #[test]
+    fn morton_array_and_dense_array_make_the_same_octree() {
+        let extent = Extent3i::from_min_and_shape(PointN([-8; 3]), PointN([16; 3]));
+        let dense = Array3::fill_with(extent, |p| {
+            let d = *p - PointN([2, -1, 3]);
+            d.x() * d.x() + d.y() * d.y() + d.z() * d.z() < 30
+        });
+        let morton = MortonArray3::from_array(&dense);
+
+        let dense_octants = visited_octants(&Octree::from_array(4, &dense));
+        let morton_octants = visited_octants(&Octree::from_morton_array(4, &morton));
+
+        assert!(!dense_octants.is_empty());
+        assert_eq!(dense_octants, morton_octants);
+    }
+
+    fn visited_octants(octree: &Octree) -> Vec<(Point3i, i32, bool)> {
+        struct Collect(Vec<(Point3i, i32, bool)>);
+
+        impl OctreeVisitor for Collect {
+            fn visit_octant(&mut self, octant: Octant, is_leaf: bool) -> VisitStatus {
+                self.0.push((octant.minimum, octant.edge_length, is_leaf));
+
+                VisitStatus::Continue
+            }
+        }
+
+        let mut collect = Collect(Vec::new());
+        octree.visit(&mut collect);
+
+        collect.0
+    }
+}
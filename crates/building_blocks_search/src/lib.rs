@@ -227,6 +227,11 @@ impl<K: Ord> Ord for HeuristicCostHolder<K> {
 mod test {
     use super::*;
 
+    use building_blocks_storage::BitArray3;
+    use core::fmt::Debug;
+    use std::collections::HashSet;
+    use std::iter::FromIterator;
+
     #[derive(Clone)]
     struct Voxel(bool);
 
@@ -261,6 +266,26 @@ mod test {
         assert_elements_eq(&surface_points, &expected_surface_points);
     }
 
+    #[test]
+    fn find_surface_points_in_bit_array() {
+        let mut map = BitArray3::fill(
+            Extent3i::from_min_and_shape(PointN([0; 3]), PointN([5; 3])),
+            false,
+        );
+
+        let solid_extent = Extent3i::from_min_and_shape(PointN([1; 3]), PointN([3; 3]));
+        map.for_each_mut(&solid_extent, |_s: Stride, value| *value = true);
+
+        let (surface_points, _surface_strides) = find_surface_points(&map, &solid_extent);
+
+        let center = PointN([2; 3]);
+        let expected_surface_points = solid_extent
+            .iter_points()
+            .filter(|p| *p != center)
+            .collect();
+        assert_elements_eq(&surface_points, &expected_surface_points);
+    }
+
     fn assert_elements_eq<T: Clone + Debug + Eq + Hash>(v1: &Vec<T>, v2: &Vec<T>) {
         let set1: HashSet<T> = HashSet::from_iter(v1.iter().cloned());
         let set2: HashSet<T> = HashSet::from_iter(v2.iter().cloned());
@@ -66,6 +66,9 @@
 //! This means you keep the performance of simple array indexing, as opposed to indexing with a
 //! `Point3i`, which requires 2 multiplications to convert to a `Stride`. You'd be surprised how
 //! important this difference can be in tight loops.
+//!
+//! The `kernel` module wraps up this pattern, including the computation of the interior extent and
+//! handling of points near the array boundary.
 
 use crate::{
     access::{
@@ -73,6 +76,7 @@ use crate::{
         GetUncheckedRefRelease, GetUncheckedRelease,
     },
     chunk_map::ChunkCopySrc,
+    transform_map::{MapZipCopySrc, ZipCopySrc},
     ForEachMut, ForEachRef, Get, GetMut, GetRef, ReadExtent, TransformMap, WriteExtent,
 };
 
@@ -137,6 +141,11 @@ impl<N, T> ArrayN<N, T> {
         &self.values[..]
     }
 
+    /// Returns the entire mutable slice of values.
+    pub fn values_slice_mut(&mut self) -> &mut [T] {
+        &mut self.values[..]
+    }
+
     /// Moves the raw extent and values `Vec` out of `self`.
     pub fn into_parts(self) -> (ExtentN<N>, Vec<T>) {
         (self.extent, self.values)
@@ -145,6 +154,11 @@ impl<N, T> ArrayN<N, T> {
     pub fn extent(&self) -> &ExtentN<N> {
         &self.extent
     }
+
+    /// Translates the array so that its extent has `new_min` as the minimum. The values don't move.
+    pub fn set_minimum(&mut self, new_min: PointN<N>) {
+        self.extent.minimum = new_min;
+    }
 }
 
 impl<N, T> ArrayN<N, T>
@@ -639,6 +653,121 @@ where
     }
 }
 
+// The two halves of a zipped source don't need to share an extent, so each one is copied into its
+// own component of the destination values.
+impl<N, A, B, Ma, Mb, Sa, Sb>
+    WriteExtent<N, ZipCopySrc<ChunkCopySrc<Sa, N, A>, ChunkCopySrc<Sb, N, B>>> for ArrayN<N, (A, B)>
+where
+    A: Clone,
+    B: Clone,
+    Self: Array<N>,
+    ArrayCopySrc<Sa>: Deref<Target = Ma>,
+    ArrayCopySrc<Sb>: Deref<Target = Mb>,
+    Ma: ArrayExtent<N> + GetUncheckedRelease<Stride, A>,
+    Mb: ArrayExtent<N> + GetUncheckedRelease<Stride, B>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: Copy,
+{
+    fn write_extent(
+        &mut self,
+        extent: &ExtentN<N>,
+        src: ZipCopySrc<ChunkCopySrc<Sa, N, A>, ChunkCopySrc<Sb, N, B>>,
+    ) {
+        let in_bounds_extent = extent.intersection(self.extent());
+        write_component(self, &in_bounds_extent, src.a, |(a, _)| a);
+        write_component(self, &in_bounds_extent, src.b, |(_, b)| b);
+    }
+}
+
+fn write_component<N, T, C, M, Ms>(
+    dst: &mut ArrayN<N, T>,
+    extent: &ExtentN<N>,
+    src: ChunkCopySrc<Ms, N, C>,
+    component: impl Fn(&mut T) -> &mut C,
+) where
+    C: Clone,
+    ArrayN<N, T>: Array<N>,
+    ArrayCopySrc<Ms>: Deref<Target = M>,
+    M: ArrayExtent<N> + GetUncheckedRelease<Stride, C>,
+    ExtentN<N>: Copy,
+{
+    match src {
+        Either::Left(array) => {
+            let dst_extent = *dst.extent();
+            ArrayN::<N, T>::for_each_stride_parallel(
+                extent,
+                &dst_extent,
+                array.extent(),
+                |s_dst, s_src| {
+                    *component(dst.get_unchecked_mut_release(s_dst)) =
+                        array.get_unchecked_release(s_src);
+                },
+            );
+        }
+        Either::Right(ambient) => {
+            let src_value = ambient.get();
+            dst.for_each_mut(extent, |_s: Stride, value| {
+                *component(value) = src_value.clone();
+            });
+        }
+    }
+}
+
+impl<N, T, A, B, Ma, Mb, Sa, Sb, F>
+    WriteExtent<N, MapZipCopySrc<ZipCopySrc<ChunkCopySrc<Sa, N, A>, ChunkCopySrc<Sb, N, B>>, &F>>
+    for ArrayN<N, T>
+where
+    A: Clone,
+    B: Clone,
+    F: Fn((A, B)) -> T,
+    Self: ForEachMut<N, PointN<N>, Data = T>,
+    ArrayN<N, A>: Array<N>,
+    ArrayN<N, B>: Array<N>,
+    ArrayCopySrc<Sa>: Deref<Target = Ma>,
+    ArrayCopySrc<Sb>: Deref<Target = Mb>,
+    Ma: ArrayExtent<N> + GetUncheckedRelease<Stride, A>,
+    Mb: ArrayExtent<N> + GetUncheckedRelease<Stride, B>,
+    PointN<N>: IntegerPoint,
+{
+    fn write_extent(
+        &mut self,
+        extent: &ExtentN<N>,
+        src: MapZipCopySrc<ZipCopySrc<ChunkCopySrc<Sa, N, A>, ChunkCopySrc<Sb, N, B>>, &F>,
+    ) {
+        let in_bounds_extent = extent.intersection(self.extent());
+        let MapZipCopySrc {
+            zip: ZipCopySrc { a, b },
+            transform,
+        } = src;
+        self.for_each_mut(&in_bounds_extent, |p: PointN<N>, value| {
+            *value = transform((read_component(&a, &p), read_component(&b, &p)));
+        });
+    }
+}
+
+// The extent being copied is within the source, so the lookup is in bounds.
+fn read_component<N, C, M, Ms>(src: &ChunkCopySrc<Ms, N, C>, p: &PointN<N>) -> C
+where
+    C: Clone,
+    ArrayN<N, C>: Array<N>,
+    ArrayCopySrc<Ms>: Deref<Target = M>,
+    M: ArrayExtent<N> + GetUncheckedRelease<Stride, C>,
+    PointN<N>: Point,
+{
+    match src {
+        Either::Left(array) => {
+            let array_extent = array.extent();
+            let stride = ArrayN::<N, C>::stride_from_point(
+                &array_extent.shape,
+                &(*p - array_extent.minimum),
+            );
+
+            array.get_unchecked_release(stride)
+        }
+        Either::Right(ambient) => ambient.get(),
+    }
+}
+
 impl<'a, N, F, T: 'a + Clone> WriteExtent<N, F> for ArrayN<N, T>
 where
     F: Fn(&PointN<N>) -> T,
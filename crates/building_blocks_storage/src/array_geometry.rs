@@ -0,0 +1,319 @@
+//! Geometric transformations of `ArrayN`: axis-aligned orientations and resampling.
+//!
+//! All of these operations are defined in global coordinates, so the output array has whatever
+//! extent the transformed points land in. For example, orienting an array applies the orientation
+//! to every point of the array's extent, rotating or mirroring about the origin of the lattice.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::prelude::*;
+//!
+//! let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([2, 4, 8]));
+//! let prefab = Array3::fill_with(extent, |p| p.x() + 10 * p.y() + 100 * p.z());
+//!
+//! // Rotate the prefab a quarter turn about the Z axis. Every value moves to its rotated point.
+//! let rotation = Orientation3::quarter_turn_z();
+//! let mut rotated = prefab.orient(&rotation);
+//! assert_eq!(rotated.extent().shape, PointN([4, 2, 8]));
+//! for p in extent.iter_points() {
+//!     assert_eq!(rotated.get(&rotation.apply(&p)), prefab.get(&p));
+//! }
+//!
+//! // Now place it somewhere else in the world.
+//! rotated.set_minimum(PointN([100, 0, 0]));
+//!
+//! // Mirroring and transposing are also orientations.
+//! let mirrored = prefab.orient(&Orientation3::from_flips([true, false, false]));
+//! let transposed = prefab.orient(&AxisPermutation3::Zyx);
+//! assert_eq!(transposed.extent().shape, PointN([8, 4, 2]));
+//! ```
+//!
+//! Arrays can also be resampled by integer factors. When downsampling, each output point gets the
+//! value produced by a reducer from all of the points that are covered by it.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::prelude::*;
+//! let extent = Extent3i::from_min_and_shape(PointN([-4; 3]), PointN([8; 3]));
+//! let array = Array3::fill(extent, 1);
+//!
+//! let coarse = array.downsample(2, |values: &[i32]| values.iter().sum::<i32>());
+//! assert_eq!(coarse.extent(), &Extent3i::from_min_and_shape(PointN([-2; 3]), PointN([4; 3])));
+//! assert_eq!(coarse.get(&PointN([-2; 3])), 8);
+//!
+//! let fine = coarse.upsample(2);
+//! assert_eq!(fine.extent(), &extent);
+//! ```
+
+use crate::{Array, Array2, Array3, ArrayN, ForEachRef, Get, Stride};
+
+use building_blocks_core::prelude::*;
+
+impl<N, T> ArrayN<N, T>
+where
+    T: Clone,
+    Self: Array<N> + for<'r> Get<&'r PointN<N>, Data = T>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Returns a new array where the value at each point `p` of this array has been moved to
+    /// `orientation.apply(p)`.
+    pub fn orient(&self, orientation: &impl Orientation<N>) -> Self {
+        let inverse = orientation.inverse();
+        let oriented_extent = orientation.apply_to_extent(self.extent());
+        let values = oriented_extent
+            .iter_points()
+            .map(|p| self.get(&inverse.apply(&p)))
+            .collect();
+
+        ArrayN::new(oriented_extent, values)
+    }
+}
+
+impl<N, T> ArrayN<N, T>
+where
+    T: Clone,
+    Self: Array<N> + for<'r> Get<&'r PointN<N>, Data = T>,
+    PointN<N>: IntegerPoint<Scalar = i32>,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Scales up the array by `factor`, such that every point `p` becomes the cube of points with
+    /// minimum `p * factor` and shape `factor`, all taking the value at `p` (nearest neighbor).
+    pub fn upsample(&self, factor: i32) -> Self {
+        assert!(factor > 0);
+
+        let extent = self.extent();
+        let upsampled_extent =
+            ExtentN::from_min_and_shape(extent.minimum * factor, extent.shape * factor);
+        let values = upsampled_extent
+            .iter_points()
+            .map(|p| self.get(&(p / factor)))
+            .collect();
+
+        ArrayN::new(upsampled_extent, values)
+    }
+}
+
+impl<N, T> ArrayN<N, T>
+where
+    T: Clone,
+    Self: ForEachRef<N, Stride, Data = T>,
+    PointN<N>: IntegerPoint<Scalar = i32>,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Scales down the array by `factor`. The output point `q` covers the cube of points with
+    /// minimum `q * factor` and shape `factor`. Its value is given by calling `reducer` on the values
+    /// of the covered points that are also contained in this array.
+    ///
+    /// Division rounds towards negative infinity, so the output extent is the smallest extent that
+    /// covers this array, even for negative coordinates.
+    pub fn downsample<S>(&self, factor: i32, mut reducer: impl FnMut(&[T]) -> S) -> ArrayN<N, S> {
+        assert!(factor > 0);
+
+        let extent = *self.extent();
+        let downsampled_extent =
+            ExtentN::from_min_and_max(extent.minimum / factor, extent.max() / factor);
+        let block_shape = PointN::ONES * factor;
+
+        let mut block_values = Vec::new();
+        let values = downsampled_extent
+            .iter_points()
+            .map(|q| {
+                block_values.clear();
+                let block = ExtentN::from_min_and_shape(q * factor, block_shape);
+                self.for_each_ref(&block, |_s: Stride, value| block_values.push(value.clone()));
+
+                reducer(&block_values)
+            })
+            .collect();
+
+        ArrayN::new(downsampled_extent, values)
+    }
+}
+
+impl Array2<f32> {
+    /// Scales up the array by `factor` using bilinear interpolation between the centers of points.
+    /// The output extent is the same as for `upsample`.
+    pub fn upsample_bilinear(&self, factor: i32) -> Self {
+        assert!(factor > 0);
+
+        let extent = *self.extent();
+        let max = extent.max();
+        let upsampled_extent =
+            Extent2i::from_min_and_shape(extent.minimum * factor, extent.shape * factor);
+        let values = upsampled_extent
+            .iter_points()
+            .map(|p| {
+                let (x0, x1, tx) = interpolation_coords(p.x(), factor, extent.minimum.x(), max.x());
+                let (y0, y1, ty) = interpolation_coords(p.y(), factor, extent.minimum.y(), max.y());
+
+                let v0 = lerp(self.get(&PointN([x0, y0])), self.get(&PointN([x1, y0])), tx);
+                let v1 = lerp(self.get(&PointN([x0, y1])), self.get(&PointN([x1, y1])), tx);
+
+                lerp(v0, v1, ty)
+            })
+            .collect();
+
+        Array2::new(upsampled_extent, values)
+    }
+}
+
+impl Array3<f32> {
+    /// Scales up the array by `factor` using trilinear interpolation between the centers of points.
+    /// The output extent is the same as for `upsample`.
+    pub fn upsample_trilinear(&self, factor: i32) -> Self {
+        assert!(factor > 0);
+
+        let extent = *self.extent();
+        let max = extent.max();
+        let upsampled_extent =
+            Extent3i::from_min_and_shape(extent.minimum * factor, extent.shape * factor);
+        let values = upsampled_extent
+            .iter_points()
+            .map(|p| {
+                let (x0, x1, tx) = interpolation_coords(p.x(), factor, extent.minimum.x(), max.x());
+                let (y0, y1, ty) = interpolation_coords(p.y(), factor, extent.minimum.y(), max.y());
+                let (z0, z1, tz) = interpolation_coords(p.z(), factor, extent.minimum.z(), max.z());
+
+                let v00 = lerp(
+                    self.get(&PointN([x0, y0, z0])),
+                    self.get(&PointN([x1, y0, z0])),
+                    tx,
+                );
+                let v10 = lerp(
+                    self.get(&PointN([x0, y1, z0])),
+                    self.get(&PointN([x1, y1, z0])),
+                    tx,
+                );
+                let v01 = lerp(
+                    self.get(&PointN([x0, y0, z1])),
+                    self.get(&PointN([x1, y0, z1])),
+                    tx,
+                );
+                let v11 = lerp(
+                    self.get(&PointN([x0, y1, z1])),
+                    self.get(&PointN([x1, y1, z1])),
+                    tx,
+                );
+
+                lerp(lerp(v00, v10, ty), lerp(v01, v11, ty), tz)
+            })
+            .collect();
+
+        Array3::new(upsampled_extent, values)
+    }
+}
+
+// For a single axis of an upsampled point, returns the two source coordinates to interpolate
+// between (clamped to `[min, max]`) and the interpolation parameter.
+fn interpolation_coords(upsampled: i32, factor: i32, min: i32, max: i32) -> (i32, i32, f32) {
+    // Map the center of the upsampled point into source coordinates, where source point centers
+    // are at integer coordinates.
+    let u = (upsampled as f32 + 0.5) / factor as f32 - 0.5;
+    let c0 = u.floor();
+    let t = u - c0;
+    let c0 = c0 as i32;
+
+    (c0.max(min).min(max), (c0 + 1).max(min).min(max), t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + t * (b - a)
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orient_moves_every_value() {
+        let extent = Extent3i::from_min_and_shape(PointN([-3, 1, 2]), PointN([2, 3, 4]));
+        let array = Array3::fill_with(extent, |p| p.x() + 10 * p.y() + 100 * p.z());
+
+        for o in Orientation3::all() {
+            let oriented = array.orient(&o);
+            assert_eq!(oriented.extent(), &o.apply_to_extent(&extent));
+            for p in extent.iter_points() {
+                assert_eq!(oriented.get(&o.apply(&p)), array.get(&p));
+            }
+            assert_eq!(oriented.orient(&o.inverse()), array);
+        }
+    }
+
+    #[test]
+    fn quarter_turn_2d() {
+        let extent = Extent2i::from_min_and_shape(PointN([0, 0]), PointN([2, 1]));
+        let array = Array2::new(extent, vec![1, 2]);
+
+        let rotated = array.orient(&Orientation2::quarter_turn());
+        assert_eq!(
+            rotated,
+            Array2::new(
+                Extent2i::from_min_and_shape(PointN([0, 0]), PointN([1, 2])),
+                vec![1, 2]
+            )
+        );
+    }
+
+    #[test]
+    fn downsample_covers_negative_coordinates() {
+        let extent = Extent2i::from_min_and_shape(PointN([-3, -3]), PointN([5, 5]));
+        let array = Array2::fill(extent, 1);
+
+        let coarse = array.downsample(2, |values: &[i32]| values.len());
+        assert_eq!(
+            coarse.extent(),
+            &Extent2i::from_min_and_max(PointN([-2, -2]), PointN([0, 0]))
+        );
+        // Corner blocks are only partially covered by the array.
+        assert_eq!(coarse.get(&PointN([-2, -2])), 1);
+        assert_eq!(coarse.get(&PointN([-1, -1])), 4);
+        assert_eq!(coarse.get(&PointN([0, 0])), 4);
+        assert_eq!(coarse.get(&PointN([0, -2])), 2);
+    }
+
+    #[test]
+    fn upsample_then_downsample_is_identity() {
+        let extent = Extent3i::from_min_and_shape(PointN([-2, 0, 3]), PointN([3, 4, 5]));
+        let array = Array3::fill_with(extent, |p| p.x() * p.y() - p.z());
+
+        let fine = array.upsample(3);
+        assert_eq!(fine.extent().minimum, PointN([-6, 0, 9]));
+        let coarse = fine.downsample(3, |values: &[i32]| {
+            assert_eq!(values.len(), 27);
+            values[0]
+        });
+        assert_eq!(coarse, array);
+    }
+
+    #[test]
+    fn trilinear_reproduces_linear_ramp() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([4; 3]));
+        let array = Array3::fill_with(extent, |p| p.x() as f32);
+
+        let fine = array.upsample_trilinear(2);
+        // Away from the boundary, the interpolated value at a fine point center matches the ramp.
+        let interior = Extent3i::from_min_and_shape(PointN([1; 3]), PointN([6; 3]));
+        for p in interior.iter_points() {
+            let expected = (p.x() as f32 + 0.5) / 2.0 - 0.5;
+            assert!((fine.get(&p) - expected).abs() < 1e-6);
+        }
+        // Clamped at the boundary.
+        assert_eq!(fine.get(&PointN([0; 3])), 0.0);
+        assert_eq!(fine.get(&PointN([7; 3])), 3.0);
+
+        let flat = Array2::fill(
+            Extent2i::from_min_and_shape(PointN([-1; 2]), PointN([3; 2])),
+            2.0,
+        );
+        let fine = flat.upsample_bilinear(4);
+        fine.for_each_ref(fine.extent(), |_s: Stride, value| assert_eq!(*value, 2.0));
+    }
+}
@@ -0,0 +1,780 @@
+//! Borrowed views of a sub-extent of an `ArrayN`.
+//!
+//! A view only contains the points of its own extent, but it uses the same global coordinates and
+//! `Stride`s as the parent array. This means you can operate on a piece of an array without copying
+//! it, and any `Stride` offsets computed for the parent also work for the view.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::prelude::*;
+//!
+//! let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([32; 3]));
+//! let mut array = Array3::fill(extent, 0);
+//!
+//! // Write only to the points in the view, even though we iterate over the whole array extent.
+//! let view_extent = Extent3i::from_min_and_shape(PointN([8; 3]), PointN([16; 3]));
+//! let mut view = array.view_mut(&view_extent);
+//! view.for_each_mut(&extent, |_p: Point3i, value| *value = 1);
+//!
+//! // Mutable views can be split into disjoint views along the outermost axis, which is useful for
+//! // parallel processing.
+//! let (mut lower, mut upper) = view.split_at_z(16);
+//! lower.for_each_mut(&extent, |_s: Stride, value| *value += 1);
+//! upper.for_each_mut(&extent, |_s: Stride, value| *value += 2);
+//!
+//! let view = array.view(&view_extent);
+//! assert_eq!(view.get(&PointN([8; 3])), 2);
+//! assert_eq!(view.get(&PointN([23; 3])), 3);
+//!
+//! // Views can also be the source or destination of a copy.
+//! let mut dst = Array3::fill(view_extent, 0);
+//! copy_extent(&extent, &view, &mut dst);
+//! assert_eq!(dst.get(&PointN([23; 3])), 3);
+//! ```
+
+use crate::{
+    access::{
+        GetUnchecked, GetUncheckedMut, GetUncheckedMutRelease, GetUncheckedRef,
+        GetUncheckedRefRelease, GetUncheckedRelease,
+    },
+    array::ArrayCopySrc,
+    chunk_map::ChunkCopySrc,
+    Array, ArrayExtent, ArrayN, ForEachMut, ForEachRef, Get, GetMut, GetRef, ReadExtent, Stride,
+    WriteExtent,
+};
+
+use building_blocks_core::prelude::*;
+
+use core::iter::{once, Once};
+use core::ops::{Deref, Range};
+use either::Either;
+use num::Zero;
+
+/// An immutable view of the points in some sub-extent of a parent `ArrayN`.
+pub struct ArrayView<'a, N, T> {
+    array: &'a ArrayN<N, T>,
+    view_extent: ExtentN<N>,
+}
+
+pub type ArrayView2<'a, T> = ArrayView<'a, [i32; 2], T>;
+pub type ArrayView3<'a, T> = ArrayView<'a, [i32; 3], T>;
+
+impl<'a, N, T> Clone for ArrayView<'a, N, T>
+where
+    ExtentN<N>: Copy,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'a, N, T> Copy for ArrayView<'a, N, T> where ExtentN<N>: Copy {}
+
+/// A mutable view of the points in some sub-extent of a parent `ArrayN`.
+///
+/// The view only borrows the range of the parent's values between the first and last points of the
+/// view's extent, so disjoint views can be split off with `split_at_y` (2D) or `split_at_z` (3D).
+pub struct ArrayViewMut<'a, N, T> {
+    values: &'a mut [T],
+    // The stride of `values[0]` in the parent array.
+    offset: Stride,
+    array_extent: ExtentN<N>,
+    view_extent: ExtentN<N>,
+}
+
+pub type ArrayViewMut2<'a, T> = ArrayViewMut<'a, [i32; 2], T>;
+pub type ArrayViewMut3<'a, T> = ArrayViewMut<'a, [i32; 3], T>;
+
+impl<N, T> ArrayN<N, T>
+where
+    PointN<N>: IntegerPoint,
+{
+    /// Borrows the points of `extent` that are in-bounds of this array.
+    pub fn view(&self, extent: &ExtentN<N>) -> ArrayView<'_, N, T> {
+        ArrayView {
+            view_extent: extent.intersection(self.extent()),
+            array: self,
+        }
+    }
+}
+
+impl<N, T> ArrayN<N, T>
+where
+    Self: Array<N>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Mutably borrows the points of `extent` that are in-bounds of this array.
+    pub fn view_mut(&mut self, extent: &ExtentN<N>) -> ArrayViewMut<'_, N, T> {
+        let array_extent = *self.extent();
+        let extent = extent.intersection(&array_extent);
+        let strides = stride_range::<N, T>(&array_extent, &extent);
+
+        ArrayViewMut {
+            offset: Stride(strides.start),
+            values: &mut self.values_slice_mut()[strides],
+            array_extent,
+            view_extent: extent,
+        }
+    }
+}
+
+impl<'a, N, T> ArrayView<'a, N, T> {
+    /// The extent of the points in this view. Not to be confused with `ArrayExtent::extent`, which
+    /// is the extent of the parent array's storage.
+    pub fn view_extent(&self) -> &ExtentN<N> {
+        &self.view_extent
+    }
+
+    /// The array that this view borrows from.
+    pub fn array(&self) -> &'a ArrayN<N, T> {
+        self.array
+    }
+}
+
+impl<'a, N, T> ArrayView<'a, N, T>
+where
+    PointN<N>: Point,
+{
+    /// Returns `true` iff this view contains point `p`.
+    pub fn contains(&self, p: &PointN<N>) -> bool {
+        self.view_extent.contains(p)
+    }
+}
+
+impl<'a, N, T> ArrayViewMut<'a, N, T> {
+    /// The extent of the points in this view. Not to be confused with `ArrayExtent::extent`, which
+    /// is the extent of the parent array's storage.
+    pub fn view_extent(&self) -> &ExtentN<N> {
+        &self.view_extent
+    }
+}
+
+impl<'a, N, T> ArrayViewMut<'a, N, T>
+where
+    PointN<N>: Point,
+{
+    /// Returns `true` iff this view contains point `p`.
+    pub fn contains(&self, p: &PointN<N>) -> bool {
+        self.view_extent.contains(p)
+    }
+}
+
+impl<'a, N, T> ArrayViewMut<'a, N, T>
+where
+    ArrayN<N, T>: Array<N>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    fn stride_from_point(&self, p: &PointN<N>) -> Stride {
+        ArrayN::<N, T>::stride_from_point(
+            &self.array_extent.shape,
+            &(*p - self.array_extent.minimum),
+        )
+    }
+
+    // The caller must guarantee that every point of `lower` comes before every point of `upper` in
+    // the parent's layout.
+    fn split(self, lower: ExtentN<N>, upper: ExtentN<N>) -> (Self, Self) {
+        let split_index = if extent_is_empty(&upper) {
+            self.values.len()
+        } else {
+            (self.stride_from_point(&upper.minimum) - self.offset).0
+        };
+        let (lower_values, upper_values) = self.values.split_at_mut(split_index);
+
+        (
+            ArrayViewMut {
+                values: lower_values,
+                offset: self.offset,
+                array_extent: self.array_extent,
+                view_extent: lower,
+            },
+            ArrayViewMut {
+                values: upper_values,
+                offset: self.offset + Stride(split_index),
+                array_extent: self.array_extent,
+                view_extent: upper,
+            },
+        )
+    }
+}
+
+impl<'a, T> ArrayViewMut2<'a, T> {
+    /// Splits this view into the points with `p.y() < y` and the points with `p.y() >= y`.
+    pub fn split_at_y(self, y: i32) -> (Self, Self) {
+        let min = self.view_extent.minimum;
+        let lub = self.view_extent.least_upper_bound();
+        let y = y.max(min.y()).min(lub.y());
+        let lower = Extent2i::from_min_and_lub(min, PointN([lub.x(), y]));
+        let upper = Extent2i::from_min_and_lub(PointN([min.x(), y]), lub);
+
+        self.split(lower, upper)
+    }
+}
+
+impl<'a, T> ArrayViewMut3<'a, T> {
+    /// Splits this view into the points with `p.z() < z` and the points with `p.z() >= z`.
+    pub fn split_at_z(self, z: i32) -> (Self, Self) {
+        let min = self.view_extent.minimum;
+        let lub = self.view_extent.least_upper_bound();
+        let z = z.max(min.z()).min(lub.z());
+        let lower = Extent3i::from_min_and_lub(min, PointN([lub.x(), lub.y(), z]));
+        let upper = Extent3i::from_min_and_lub(PointN([min.x(), min.y(), z]), lub);
+
+        self.split(lower, upper)
+    }
+}
+
+// Using the partial order on points, an extent is nonempty iff every dimension is positive.
+#[allow(clippy::neg_cmp_op_on_partial_ord)]
+pub(crate) fn extent_is_empty<N>(extent: &ExtentN<N>) -> bool
+where
+    PointN<N>: Point,
+{
+    !(extent.shape > PointN::zero())
+}
+
+// The range of strides in the parent array spanned by `extent`.
+fn stride_range<N, T>(array_extent: &ExtentN<N>, extent: &ExtentN<N>) -> Range<usize>
+where
+    ArrayN<N, T>: Array<N>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    if extent_is_empty(extent) {
+        return 0..0;
+    }
+
+    let first = ArrayN::<N, T>::stride_from_point(
+        &array_extent.shape,
+        &(extent.minimum - array_extent.minimum),
+    );
+    let last = ArrayN::<N, T>::stride_from_point(
+        &array_extent.shape,
+        &(extent.max() - array_extent.minimum),
+    );
+
+    first.0..last.0 + 1
+}
+
+// Views use the same data layout as the parent array, so `ArrayExtent::extent` is the storage
+// extent of the parent, which determines how `Stride`s are computed. The points of the view itself
+// are given by `view_extent`.
+
+impl<'a, N, T> ArrayExtent<N> for ArrayView<'a, N, T> {
+    fn extent(&self) -> &ExtentN<N> {
+        self.array.extent()
+    }
+}
+
+impl<'a, N, T> ArrayExtent<N> for ArrayViewMut<'a, N, T> {
+    fn extent(&self) -> &ExtentN<N> {
+        &self.array_extent
+    }
+}
+
+macro_rules! impl_array_for_view {
+    ($view:ident) => {
+        impl<'a, N, T> Array<N> for $view<'a, N, T>
+        where
+            ArrayN<N, T>: Array<N>,
+        {
+            #[inline]
+            fn stride_from_point(shape: &PointN<N>, point: &PointN<N>) -> Stride {
+                ArrayN::<N, T>::stride_from_point(shape, point)
+            }
+
+            fn for_each_point_and_stride(
+                array_extent: &ExtentN<N>,
+                extent: &ExtentN<N>,
+                f: impl FnMut(PointN<N>, Stride),
+            ) {
+                ArrayN::<N, T>::for_each_point_and_stride(array_extent, extent, f)
+            }
+
+            fn for_each_stride_parallel(
+                iter_extent: &ExtentN<N>,
+                array1_extent: &ExtentN<N>,
+                array2_extent: &ExtentN<N>,
+                f: impl FnMut(Stride, Stride),
+            ) {
+                ArrayN::<N, T>::for_each_stride_parallel(
+                    iter_extent,
+                    array1_extent,
+                    array2_extent,
+                    f,
+                )
+            }
+        }
+    };
+}
+
+impl_array_for_view!(ArrayView);
+impl_array_for_view!(ArrayViewMut);
+
+//  ██████╗ ███████╗████████╗████████╗███████╗██████╗ ███████╗
+// ██╔════╝ ██╔════╝╚══██╔══╝╚══██╔══╝██╔════╝██╔══██╗██╔════╝
+// ██║  ███╗█████╗     ██║      ██║   █████╗  ██████╔╝███████╗
+// ██║   ██║██╔══╝     ██║      ██║   ██╔══╝  ██╔══██╗╚════██║
+// ╚██████╔╝███████╗   ██║      ██║   ███████╗██║  ██║███████║
+//  ╚═════╝ ╚══════╝   ╚═╝      ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
+
+impl<'a, N, T, Coord> Get<Coord> for ArrayView<'a, N, T>
+where
+    ArrayN<N, T>: Get<Coord, Data = T>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get(&self, c: Coord) -> T {
+        self.array.get(c)
+    }
+}
+
+impl<'a, N, T, Coord> GetRef<Coord> for ArrayView<'a, N, T>
+where
+    ArrayN<N, T>: GetRef<Coord, Data = T>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get_ref(&self, c: Coord) -> &T {
+        self.array.get_ref(c)
+    }
+}
+
+impl<'a, N, T, Coord> GetUnchecked<Coord> for ArrayView<'a, N, T>
+where
+    ArrayN<N, T>: GetUnchecked<Coord, Data = T>,
+{
+    type Data = T;
+
+    #[inline]
+    unsafe fn get_unchecked(&self, c: Coord) -> T {
+        self.array.get_unchecked(c)
+    }
+}
+
+impl<'a, N, T, Coord> GetUncheckedRef<Coord> for ArrayView<'a, N, T>
+where
+    ArrayN<N, T>: GetUncheckedRef<Coord, Data = T>,
+{
+    type Data = T;
+
+    #[inline]
+    unsafe fn get_unchecked_ref(&self, c: Coord) -> &T {
+        self.array.get_unchecked_ref(c)
+    }
+}
+
+impl<'a, N, T> Get<Stride> for ArrayViewMut<'a, N, T>
+where
+    T: Clone,
+{
+    type Data = T;
+
+    #[inline]
+    fn get(&self, stride: Stride) -> T {
+        self.get_ref(stride).clone()
+    }
+}
+
+impl<'a, N, T> GetRef<Stride> for ArrayViewMut<'a, N, T> {
+    type Data = T;
+
+    #[inline]
+    fn get_ref(&self, stride: Stride) -> &T {
+        &self.values[(stride - self.offset).0]
+    }
+}
+
+impl<'a, N, T> GetMut<Stride> for ArrayViewMut<'a, N, T> {
+    type Data = T;
+
+    #[inline]
+    fn get_mut(&mut self, stride: Stride) -> &mut T {
+        &mut self.values[(stride - self.offset).0]
+    }
+}
+
+impl<'a, N, T> GetUnchecked<Stride> for ArrayViewMut<'a, N, T>
+where
+    T: Clone,
+{
+    type Data = T;
+
+    #[inline]
+    unsafe fn get_unchecked(&self, stride: Stride) -> T {
+        self.get_unchecked_ref(stride).clone()
+    }
+}
+
+impl<'a, N, T> GetUncheckedRef<Stride> for ArrayViewMut<'a, N, T> {
+    type Data = T;
+
+    #[inline]
+    unsafe fn get_unchecked_ref(&self, stride: Stride) -> &T {
+        self.values.get_unchecked((stride - self.offset).0)
+    }
+}
+
+impl<'a, N, T> GetUncheckedMut<Stride> for ArrayViewMut<'a, N, T> {
+    type Data = T;
+
+    #[inline]
+    unsafe fn get_unchecked_mut(&mut self, stride: Stride) -> &mut T {
+        self.values.get_unchecked_mut((stride - self.offset).0)
+    }
+}
+
+impl<'a, N, T> Get<&PointN<N>> for ArrayViewMut<'a, N, T>
+where
+    T: Clone,
+    ArrayN<N, T>: Array<N>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get(&self, p: &PointN<N>) -> T {
+        self.get(self.stride_from_point(p))
+    }
+}
+
+impl<'a, N, T> GetRef<&PointN<N>> for ArrayViewMut<'a, N, T>
+where
+    ArrayN<N, T>: Array<N>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get_ref(&self, p: &PointN<N>) -> &T {
+        self.get_ref(self.stride_from_point(p))
+    }
+}
+
+impl<'a, N, T> GetMut<&PointN<N>> for ArrayViewMut<'a, N, T>
+where
+    ArrayN<N, T>: Array<N>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get_mut(&mut self, p: &PointN<N>) -> &mut T {
+        let stride = self.stride_from_point(p);
+
+        self.get_mut(stride)
+    }
+}
+
+// ███████╗ ██████╗ ██████╗     ███████╗ █████╗  ██████╗██╗  ██╗
+// ██╔════╝██╔═══██╗██╔══██╗    ██╔════╝██╔══██╗██╔════╝██║  ██║
+// █████╗  ██║   ██║██████╔╝    █████╗  ███████║██║     ███████║
+// ██╔══╝  ██║   ██║██╔══██╗    ██╔══╝  ██╔══██║██║     ██╔══██║
+// ██║     ╚██████╔╝██║  ██║    ███████╗██║  ██║╚██████╗██║  ██║
+// ╚═╝      ╚═════╝ ╚═╝  ╚═╝    ╚══════╝╚═╝  ╚═╝ ╚═════╝╚═╝  ╚═╝
+
+impl<'a, N, T, Coord> ForEachRef<N, Coord> for ArrayView<'a, N, T>
+where
+    ArrayN<N, T>: ForEachRef<N, Coord, Data = T>,
+    PointN<N>: IntegerPoint,
+{
+    type Data = T;
+
+    fn for_each_ref(&self, extent: &ExtentN<N>, f: impl FnMut(Coord, &T)) {
+        self.array
+            .for_each_ref(&extent.intersection(&self.view_extent), f)
+    }
+}
+
+macro_rules! impl_array_view_mut_for_each {
+    (coords: $coords:ty; forwarder = |$p:ident, $stride:ident| $forward_coords:expr;) => {
+        impl<'a, N, T> ForEachRef<N, $coords> for ArrayViewMut<'a, N, T>
+        where
+            ArrayN<N, T>: Array<N>,
+            PointN<N>: IntegerPoint,
+        {
+            type Data = T;
+
+            fn for_each_ref(&self, extent: &ExtentN<N>, mut f: impl FnMut($coords, &T)) {
+                let iter_extent = extent.intersection(&self.view_extent);
+                ArrayN::<N, T>::for_each_point_and_stride(
+                    &self.array_extent,
+                    &iter_extent,
+                    |$p, $stride| f($forward_coords, self.get_unchecked_ref_release($stride)),
+                )
+            }
+        }
+
+        impl<'a, N, T> ForEachMut<N, $coords> for ArrayViewMut<'a, N, T>
+        where
+            ArrayN<N, T>: Array<N>,
+            PointN<N>: IntegerPoint,
+            ExtentN<N>: Copy,
+        {
+            type Data = T;
+
+            fn for_each_mut(&mut self, extent: &ExtentN<N>, mut f: impl FnMut($coords, &mut T)) {
+                let array_extent = self.array_extent;
+                let iter_extent = extent.intersection(&self.view_extent);
+                ArrayN::<N, T>::for_each_point_and_stride(
+                    &array_extent,
+                    &iter_extent,
+                    |$p, $stride| f($forward_coords, self.get_unchecked_mut_release($stride)),
+                )
+            }
+        }
+    };
+}
+
+impl_array_view_mut_for_each!(
+    coords: (PointN<N>, Stride);
+    forwarder = |p, stride| (p, stride);
+);
+impl_array_view_mut_for_each!(
+    coords: Stride;
+    forwarder = |_p, stride| stride;
+);
+impl_array_view_mut_for_each!(
+    coords: PointN<N>;
+    forwarder = |p, stride| p;
+);
+
+//  ██████╗ ██████╗ ██████╗ ██╗   ██╗
+// ██╔════╝██╔═══██╗██╔══██╗╚██╗ ██╔╝
+// ██║     ██║   ██║██████╔╝ ╚████╔╝
+// ██║     ██║   ██║██╔═══╝   ╚██╔╝
+// ╚██████╗╚██████╔╝██║        ██║
+//  ╚═════╝ ╚═════╝ ╚═╝        ╚═╝
+
+impl<'b, N, T> Deref for ArrayCopySrc<&ArrayViewMut<'b, N, T>> {
+    type Target = ArrayViewMut<'b, N, T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<'a, N: 'a, T: 'a> ReadExtent<'a, N> for ArrayView<'a, N, T>
+where
+    PointN<N>: IntegerPoint,
+{
+    type Src = ArrayCopySrc<&'a ArrayN<N, T>>;
+    type SrcIter = Once<(ExtentN<N>, Self::Src)>;
+
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        let in_bounds_extent = extent.intersection(&self.view_extent);
+
+        once((in_bounds_extent, ArrayCopySrc(self.array)))
+    }
+}
+
+impl<'a, 'b: 'a, N: 'a, T: 'a> ReadExtent<'a, N> for ArrayViewMut<'b, N, T>
+where
+    PointN<N>: IntegerPoint,
+{
+    type Src = ArrayCopySrc<&'a ArrayViewMut<'b, N, T>>;
+    type SrcIter = Once<(ExtentN<N>, Self::Src)>;
+
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        let in_bounds_extent = extent.intersection(&self.view_extent);
+
+        once((in_bounds_extent, ArrayCopySrc(self)))
+    }
+}
+
+impl<'a, N, T, M, Ms> WriteExtent<N, ArrayCopySrc<Ms>> for ArrayViewMut<'a, N, T>
+where
+    ArrayN<N, T>: Array<N>,
+    ArrayCopySrc<Ms>: Deref<Target = M>,
+    M: ArrayExtent<N> + GetUncheckedRelease<Stride, T>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: Copy,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src_array: ArrayCopySrc<Ms>) {
+        // It is assumed by the interface that extent is a subset of the src array, so we only need
+        // to intersect with the destination.
+        let array_extent = self.array_extent;
+        let in_bounds_extent = extent.intersection(&self.view_extent);
+
+        ArrayN::<N, T>::for_each_stride_parallel(
+            &in_bounds_extent,
+            &array_extent,
+            src_array.extent(),
+            |s_dst, s_src| {
+                *self.get_unchecked_mut_release(s_dst) = src_array.get_unchecked_release(s_src);
+            },
+        );
+    }
+}
+
+impl<'a, M, N, T> WriteExtent<N, ChunkCopySrc<M, N, T>> for ArrayViewMut<'a, N, T>
+where
+    T: Clone,
+    Self: WriteExtent<N, ArrayCopySrc<M>> + ForEachMut<N, Stride, Data = T>,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: ChunkCopySrc<M, N, T>) {
+        match src {
+            Either::Left(array) => self.write_extent(extent, array),
+            Either::Right(ambient) => {
+                let src_value = ambient.get();
+                self.for_each_mut(extent, |_s: Stride, value| {
+                    *value = src_value.clone();
+                });
+            }
+        }
+    }
+}
+
+impl<'a, N, F, T> WriteExtent<N, F> for ArrayViewMut<'a, N, T>
+where
+    F: Fn(&PointN<N>) -> T,
+    ArrayN<N, T>: Array<N>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: Copy,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: F) {
+        self.for_each_mut(extent, |p: PointN<N>, value| *value = (src)(&p));
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        copy_extent, Array2, Array3, ChunkMap3, ChunkMapReader3, FastLz4, LocalChunkCache,
+    };
+
+    #[test]
+    fn view_only_visits_points_in_view_extent() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([10; 3]));
+        let array = Array3::fill_with(extent, |p| p.x() + p.y() + p.z());
+
+        let view_extent = Extent3i::from_min_and_shape(PointN([2; 3]), PointN([3; 3]));
+        let view = array.view(&view_extent);
+
+        let mut num_visited = 0;
+        view.for_each_ref(&extent, |p: Point3i, value| {
+            assert!(view_extent.contains(&p));
+            assert_eq!(*value, p.x() + p.y() + p.z());
+            num_visited += 1;
+        });
+        assert_eq!(num_visited, view_extent.num_points());
+
+        // Strides are the same as the parent's.
+        view.for_each_ref(&extent, |(p, s): (Point3i, Stride), value| {
+            assert_eq!(array.get_ref(&p), value);
+            assert_eq!(array.get_ref(s), value);
+        });
+    }
+
+    #[test]
+    fn view_extent_is_clipped_to_array() {
+        let extent = Extent2i::from_min_and_shape(PointN([0; 2]), PointN([10; 2]));
+        let mut array = Array2::fill(extent, 0);
+
+        let view_extent = Extent2i::from_min_and_shape(PointN([-5; 2]), PointN([10; 2]));
+        let mut view = array.view_mut(&view_extent);
+        assert_eq!(
+            view.view_extent(),
+            &Extent2i::from_min_and_shape(PointN([0; 2]), PointN([5; 2]))
+        );
+
+        view.for_each_mut(&view_extent, |_p: Point2i, value| *value = 1);
+        array.for_each_ref(&extent, |p: Point2i, value| {
+            if p < PointN([5; 2]) {
+                assert_eq!(*value, 1);
+            } else {
+                assert_eq!(*value, 0);
+            }
+        });
+    }
+
+    #[test]
+    fn split_views_are_disjoint() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([8; 3]));
+        let mut array = Array3::fill(extent, 0);
+
+        let view_extent = Extent3i::from_min_and_shape(PointN([1; 3]), PointN([6; 3]));
+        let view = array.view_mut(&view_extent);
+        let (mut lower, upper) = view.split_at_z(3);
+        let (mut middle, mut top) = upper.split_at_z(5);
+
+        assert_eq!(lower.view_extent().shape, PointN([6, 6, 2]));
+        assert_eq!(middle.view_extent().shape, PointN([6, 6, 2]));
+        assert_eq!(top.view_extent().shape, PointN([6, 6, 2]));
+
+        lower.for_each_mut(&extent, |_s: Stride, value| *value += 1);
+        middle.for_each_mut(&extent, |_s: Stride, value| *value += 2);
+        top.for_each_mut(&extent, |_s: Stride, value| *value += 3);
+
+        array.for_each_ref(&extent, |p: Point3i, value| {
+            let expected = if !view_extent.contains(&p) {
+                0
+            } else if p.z() < 3 {
+                1
+            } else if p.z() < 5 {
+                2
+            } else {
+                3
+            };
+            assert_eq!(*value, expected);
+        });
+    }
+
+    #[test]
+    fn split_at_boundary_gives_empty_view() {
+        let extent = Extent2i::from_min_and_shape(PointN([0; 2]), PointN([4; 2]));
+        let mut array = Array2::fill(extent, 0);
+
+        let (mut lower, mut upper) = array.view_mut(&extent).split_at_y(100);
+        upper.for_each_mut(&extent, |_s: Stride, _value| panic!("view should be empty"));
+        lower.for_each_mut(&extent, |_s: Stride, value| *value = 1);
+
+        array.for_each_ref(&extent, |_s: Stride, value| assert_eq!(*value, 1));
+    }
+
+    #[test]
+    fn copy_to_and_from_views() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16; 3]));
+        let src = Array3::fill_with(extent, |p| p.x());
+
+        let view_extent = Extent3i::from_min_and_shape(PointN([4; 3]), PointN([8; 3]));
+        let mut dst = Array3::fill(extent, -1);
+        copy_extent(&extent, &src.view(&view_extent), &mut dst.view_mut(&extent));
+        dst.for_each_ref(&extent, |p: Point3i, value| {
+            if view_extent.contains(&p) {
+                assert_eq!(*value, p.x());
+            } else {
+                assert_eq!(*value, -1);
+            }
+        });
+
+        let mut map = ChunkMap3::new(PointN([4; 3]), 0, (), FastLz4 { level: 10 });
+        copy_extent(&extent, &dst.view_mut(&view_extent), &mut map);
+
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&map, &local_cache);
+        let mut other = Array3::fill(extent, 0);
+        copy_extent(&extent, &reader, &mut other.view_mut(&view_extent));
+        other.for_each_ref(&extent, |p: Point3i, value| {
+            if view_extent.contains(&p) {
+                assert_eq!(*value, p.x());
+            } else {
+                assert_eq!(*value, 0);
+            }
+        });
+    }
+}
@@ -0,0 +1,560 @@
+//! A bit-packed N-dimensional array of `bool`s, where N is 2 or 3.
+//!
+//! `BitArrayN` uses the same extent and `Stride` layout as an `ArrayN<N, bool>`, but it only takes
+//! 1 bit per point. This makes it a good fit for occupancy masks and "visited" sets. Since the bits
+//! are packed into 64-bit words, whole masks can be combined with `&=`, `|=` and `^=` and counted
+//! with `count_ones` at the word level.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, BitArray3};
+//!
+//! let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16; 3]));
+//! let mut occupied = BitArray3::fill(extent, false);
+//!
+//! let solid = Extent3i::from_min_and_shape(PointN([4; 3]), PointN([8; 3]));
+//! occupied.for_each_mut(&solid, |_s: Stride, bit| *bit = true);
+//! assert_eq!(occupied.count_ones(), 8 * 8 * 8);
+//! assert!(occupied.get(&PointN([4; 3])));
+//!
+//! // Combine masks without visiting every point.
+//! let mut visited = BitArray3::fill_with(extent, |p| p.x() < 8);
+//! visited &= &occupied;
+//! assert_eq!(visited.count_ones(), 4 * 8 * 8);
+//!
+//! // Bit arrays can be copied to and from other lattice maps of `bool`.
+//! let mut dense = Array3::fill(extent, false);
+//! copy_extent(&extent, &visited, &mut dense);
+//! assert_eq!(dense.get(&PointN([4; 3])), true);
+//! ```
+
+use crate::{
+    access::{GetUnchecked, GetUncheckedRef, GetUncheckedRelease},
+    array::ArrayCopySrc,
+    chunk_map::ChunkCopySrc,
+    Array, ArrayExtent, ArrayN, ForEachMut, ForEachRef, Get, GetRef, IsEmpty, Local, ReadExtent,
+    Stride, WriteExtent,
+};
+
+use building_blocks_core::prelude::*;
+
+use core::iter::{once, Once};
+use core::ops::{BitAndAssign, BitOrAssign, BitXorAssign, Deref};
+use either::Either;
+use serde::{Deserialize, Serialize};
+
+const WORD_BITS: usize = 64;
+
+/// A map from lattice location `PointN<N>` to `bool`, stored as packed bits on the heap.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BitArrayN<N> {
+    words: Vec<u64>,
+    extent: ExtentN<N>,
+}
+
+pub type BitArray2 = BitArrayN<[i32; 2]>;
+pub type BitArray3 = BitArrayN<[i32; 3]>;
+
+impl<N> BitArrayN<N> {
+    pub fn extent(&self) -> &ExtentN<N> {
+        &self.extent
+    }
+
+    /// Returns the packed words. Bit `i % 64` of word `i / 64` holds the value at `Stride(i)`. Any
+    /// bits past the last point are always 0.
+    pub fn words(&self) -> &[u64] {
+        &self.words[..]
+    }
+
+    /// The number of points set to `true`.
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Returns `true` iff any point is set to `true`.
+    pub fn any(&self) -> bool {
+        self.words.iter().any(|w| *w != 0)
+    }
+
+    /// Sets the value at `stride`.
+    #[inline]
+    pub fn set(&mut self, stride: Stride, value: bool) {
+        let (word, mask) = word_and_mask(stride);
+        if value {
+            self.words[word] |= mask;
+        } else {
+            self.words[word] &= !mask;
+        }
+    }
+}
+
+impl<N> BitArrayN<N>
+where
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Creates a map that fills the entire `extent` with the same `value`.
+    pub fn fill(extent: ExtentN<N>, value: bool) -> Self {
+        let word = if value { !0 } else { 0 };
+        let mut array = Self {
+            words: vec![word; num_words(extent.num_points())],
+            extent,
+        };
+        array.clear_trailing_bits();
+
+        array
+    }
+
+    pub fn fill_with(extent: ExtentN<N>, filler: impl Fn(&PointN<N>) -> bool) -> Self {
+        let mut array = Self::fill(extent, false);
+        // Extent iteration is in the same order as the array layout.
+        for (i, p) in extent.iter_points().enumerate() {
+            if filler(&p) {
+                array.set(Stride(i), true);
+            }
+        }
+
+        array
+    }
+
+    /// Creates a mask of the points in `array` which are not empty (as defined by the `IsEmpty`
+    /// trait).
+    pub fn from_array<T: IsEmpty>(array: &ArrayN<N, T>) -> Self {
+        let mut bits = Self::fill(*array.extent(), false);
+        for (i, value) in array.values_slice().iter().enumerate() {
+            if !value.is_empty() {
+                bits.set(Stride(i), true);
+            }
+        }
+
+        bits
+    }
+
+    /// Flips every bit.
+    pub fn invert(&mut self) {
+        for word in self.words.iter_mut() {
+            *word = !*word;
+        }
+        self.clear_trailing_bits();
+    }
+
+    fn clear_trailing_bits(&mut self) {
+        let used_bits = self.extent.num_points() % WORD_BITS;
+        if used_bits != 0 {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1 << used_bits) - 1;
+            }
+        }
+    }
+}
+
+impl<N> BitArrayN<N>
+where
+    Self: Array<N>,
+    PointN<N>: Point,
+{
+    /// Sets the value at `p`, in global coordinates.
+    #[inline]
+    pub fn set_point(&mut self, p: &PointN<N>, value: bool) {
+        let stride = Self::stride_from_point(&self.extent.shape, &(*p - self.extent.minimum));
+        self.set(stride, value);
+    }
+}
+
+#[inline]
+fn num_words(num_bits: usize) -> usize {
+    num_bits.div_ceil(WORD_BITS)
+}
+
+#[inline]
+fn word_and_mask(stride: Stride) -> (usize, u64) {
+    (stride.0 / WORD_BITS, 1 << (stride.0 % WORD_BITS))
+}
+
+// Bit arrays use the same layout as `ArrayN`.
+
+impl<N> ArrayExtent<N> for BitArrayN<N> {
+    fn extent(&self) -> &ExtentN<N> {
+        self.extent()
+    }
+}
+
+impl<N> Array<N> for BitArrayN<N>
+where
+    ArrayN<N, bool>: Array<N>,
+{
+    #[inline]
+    fn stride_from_point(shape: &PointN<N>, point: &PointN<N>) -> Stride {
+        ArrayN::<N, bool>::stride_from_point(shape, point)
+    }
+
+    fn for_each_point_and_stride(
+        array_extent: &ExtentN<N>,
+        extent: &ExtentN<N>,
+        f: impl FnMut(PointN<N>, Stride),
+    ) {
+        ArrayN::<N, bool>::for_each_point_and_stride(array_extent, extent, f)
+    }
+
+    fn for_each_stride_parallel(
+        iter_extent: &ExtentN<N>,
+        array1_extent: &ExtentN<N>,
+        array2_extent: &ExtentN<N>,
+        f: impl FnMut(Stride, Stride),
+    ) {
+        ArrayN::<N, bool>::for_each_stride_parallel(iter_extent, array1_extent, array2_extent, f)
+    }
+}
+
+//  ██████╗ ███████╗████████╗████████╗███████╗██████╗ ███████╗
+// ██╔════╝ ██╔════╝╚══██╔══╝╚══██╔══╝██╔════╝██╔══██╗██╔════╝
+// ██║  ███╗█████╗     ██║      ██║   █████╗  ██████╔╝███████╗
+// ██║   ██║██╔══╝     ██║      ██║   ██╔══╝  ██╔══██╗╚════██║
+// ╚██████╔╝███████╗   ██║      ██║   ███████╗██║  ██║███████║
+//  ╚═════╝ ╚══════╝   ╚═╝      ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
+
+// There is no `GetMut` for bit arrays, since a single bit can't be borrowed. References are handed
+// out to static `bool`s instead, so generic algorithms written against `GetRef` still work.
+
+#[inline]
+fn bool_ref(value: bool) -> &'static bool {
+    if value {
+        &true
+    } else {
+        &false
+    }
+}
+
+impl<N> Get<Stride> for BitArrayN<N> {
+    type Data = bool;
+
+    #[inline]
+    fn get(&self, stride: Stride) -> Self::Data {
+        let (word, mask) = word_and_mask(stride);
+
+        self.words[word] & mask != 0
+    }
+}
+
+impl<N> GetUnchecked<Stride> for BitArrayN<N> {
+    type Data = bool;
+
+    #[inline]
+    unsafe fn get_unchecked(&self, stride: Stride) -> Self::Data {
+        let (word, mask) = word_and_mask(stride);
+
+        self.words.get_unchecked(word) & mask != 0
+    }
+}
+
+impl<N> GetRef<Stride> for BitArrayN<N> {
+    type Data = bool;
+
+    #[inline]
+    fn get_ref(&self, stride: Stride) -> &Self::Data {
+        bool_ref(self.get(stride))
+    }
+}
+
+impl<N> GetUncheckedRef<Stride> for BitArrayN<N> {
+    type Data = bool;
+
+    #[inline]
+    unsafe fn get_unchecked_ref(&self, stride: Stride) -> &Self::Data {
+        bool_ref(self.get_unchecked(stride))
+    }
+}
+
+impl<N> Get<&Local<N>> for BitArrayN<N>
+where
+    Self: Array<N>,
+{
+    type Data = bool;
+
+    #[inline]
+    fn get(&self, p: &Local<N>) -> Self::Data {
+        self.get(Self::stride_from_point(&self.extent.shape, &p.0))
+    }
+}
+
+impl<N> GetRef<&Local<N>> for BitArrayN<N>
+where
+    Self: Array<N>,
+{
+    type Data = bool;
+
+    #[inline]
+    fn get_ref(&self, p: &Local<N>) -> &Self::Data {
+        bool_ref(self.get(p))
+    }
+}
+
+impl<N> Get<&PointN<N>> for BitArrayN<N>
+where
+    Self: Array<N>,
+    PointN<N>: Point,
+{
+    type Data = bool;
+
+    #[inline]
+    fn get(&self, p: &PointN<N>) -> Self::Data {
+        self.get(&Local(*p - self.extent.minimum))
+    }
+}
+
+impl<N> GetRef<&PointN<N>> for BitArrayN<N>
+where
+    Self: Array<N>,
+    PointN<N>: Point,
+{
+    type Data = bool;
+
+    #[inline]
+    fn get_ref(&self, p: &PointN<N>) -> &Self::Data {
+        bool_ref(self.get(p))
+    }
+}
+
+// ███████╗ ██████╗ ██████╗     ███████╗ █████╗  ██████╗██╗  ██╗
+// ██╔════╝██╔═══██╗██╔══██╗    ██╔════╝██╔══██╗██╔════╝██║  ██║
+// █████╗  ██║   ██║██████╔╝    █████╗  ███████║██║     ███████║
+// ██╔══╝  ██║   ██║██╔══██╗    ██╔══╝  ██╔══██║██║     ██╔══██║
+// ██║     ╚██████╔╝██║  ██║    ███████╗██║  ██║╚██████╗██║  ██║
+// ╚═╝      ╚═════╝ ╚═╝  ╚═╝    ╚══════╝╚═╝  ╚═╝ ╚═════╝╚═╝  ╚═╝
+
+macro_rules! impl_bit_array_for_each {
+    (coords: $coords:ty; forwarder = |$p:ident, $stride:ident| $forward_coords:expr;) => {
+        impl<N> ForEachRef<N, $coords> for BitArrayN<N>
+        where
+            Self: Array<N>,
+        {
+            type Data = bool;
+
+            fn for_each_ref(&self, extent: &ExtentN<N>, mut f: impl FnMut($coords, &bool)) {
+                Self::for_each_point_and_stride(self.extent(), &extent, |$p, $stride| {
+                    f(
+                        $forward_coords,
+                        bool_ref(self.get_unchecked_release($stride)),
+                    )
+                })
+            }
+        }
+
+        // Each bit is copied out, mutated, and written back.
+        impl<N> ForEachMut<N, $coords> for BitArrayN<N>
+        where
+            Self: Array<N>,
+            ExtentN<N>: Copy,
+        {
+            type Data = bool;
+
+            fn for_each_mut(&mut self, extent: &ExtentN<N>, mut f: impl FnMut($coords, &mut bool)) {
+                let array_extent = *self.extent();
+                Self::for_each_point_and_stride(&array_extent, &extent, |$p, $stride| {
+                    let mut value = self.get_unchecked_release($stride);
+                    f($forward_coords, &mut value);
+                    self.set($stride, value);
+                })
+            }
+        }
+    };
+}
+
+impl_bit_array_for_each!(
+    coords: (PointN<N>, Stride);
+    forwarder = |p, stride| (p, stride);
+);
+impl_bit_array_for_each!(
+    coords: Stride;
+    forwarder = |_p, stride| stride;
+);
+impl_bit_array_for_each!(
+    coords: PointN<N>;
+    forwarder = |p, stride| p;
+);
+
+// ██████╗ ██╗████████╗    ██████╗ ██████╗ ███████╗
+// ██╔══██╗██║╚══██╔══╝   ██╔═══██╗██╔══██╗██╔════╝
+// ██████╔╝██║   ██║      ██║   ██║██████╔╝███████╗
+// ██╔══██╗██║   ██║      ██║   ██║██╔═══╝ ╚════██║
+// ██████╔╝██║   ██║      ╚██████╔╝██║     ███████║
+// ╚═════╝ ╚═╝   ╚═╝       ╚═════╝ ╚═╝     ╚══════╝
+
+// Bulk operations require both arrays to have the same extent, so that the words line up.
+
+macro_rules! impl_bit_array_assign_op {
+    ($op_trait:ident, $op_fn:ident, $op:tt) => {
+        impl<N> $op_trait<&BitArrayN<N>> for BitArrayN<N>
+        where
+            ExtentN<N>: core::fmt::Debug + PartialEq,
+        {
+            fn $op_fn(&mut self, rhs: &BitArrayN<N>) {
+                assert_eq!(self.extent, rhs.extent);
+                for (lhs, rhs) in self.words.iter_mut().zip(rhs.words.iter()) {
+                    *lhs $op *rhs;
+                }
+            }
+        }
+    };
+}
+
+impl_bit_array_assign_op!(BitAndAssign, bitand_assign, &=);
+impl_bit_array_assign_op!(BitOrAssign, bitor_assign, |=);
+impl_bit_array_assign_op!(BitXorAssign, bitxor_assign, ^=);
+
+//  ██████╗ ██████╗ ██████╗ ██╗   ██╗
+// ██╔════╝██╔═══██╗██╔══██╗╚██╗ ██╔╝
+// ██║     ██║   ██║██████╔╝ ╚████╔╝
+// ██║     ██║   ██║██╔═══╝   ╚██╔╝
+// ╚██████╗╚██████╔╝██║        ██║
+//  ╚═════╝ ╚═════╝ ╚═╝        ╚═╝
+
+impl<N> Deref for ArrayCopySrc<&BitArrayN<N>> {
+    type Target = BitArrayN<N>;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<'a, N: 'a> ReadExtent<'a, N> for BitArrayN<N>
+where
+    PointN<N>: IntegerPoint,
+{
+    type Src = ArrayCopySrc<&'a BitArrayN<N>>;
+    type SrcIter = Once<(ExtentN<N>, Self::Src)>;
+
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        let in_bounds_extent = extent.intersection(self.extent());
+
+        once((in_bounds_extent, ArrayCopySrc(self)))
+    }
+}
+
+impl<N, M, Ms> WriteExtent<N, ArrayCopySrc<Ms>> for BitArrayN<N>
+where
+    Self: Array<N>,
+    ArrayCopySrc<Ms>: Deref<Target = M>,
+    M: ArrayExtent<N> + GetUncheckedRelease<Stride, bool>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: Copy,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src_array: ArrayCopySrc<Ms>) {
+        let dst_extent = *self.extent();
+        let in_bounds_extent = extent.intersection(&dst_extent);
+
+        Self::for_each_stride_parallel(
+            &in_bounds_extent,
+            &dst_extent,
+            src_array.extent(),
+            |s_dst, s_src| {
+                self.set(s_dst, src_array.get_unchecked_release(s_src));
+            },
+        );
+    }
+}
+
+impl<M, N> WriteExtent<N, ChunkCopySrc<M, N, bool>> for BitArrayN<N>
+where
+    Self: Array<N> + WriteExtent<N, ArrayCopySrc<M>>,
+    ExtentN<N>: Copy,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: ChunkCopySrc<M, N, bool>) {
+        match src {
+            Either::Left(array) => self.write_extent(extent, array),
+            Either::Right(ambient) => {
+                let src_value = ambient.get();
+                self.for_each_mut(extent, |_s: Stride, value| *value = src_value);
+            }
+        }
+    }
+}
+
+impl<N, F> WriteExtent<N, F> for BitArrayN<N>
+where
+    F: Fn(&PointN<N>) -> bool,
+    Self: Array<N>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: Copy,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: F) {
+        self.for_each_mut(extent, |p: PointN<N>, value| *value = (src)(&p));
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{copy_extent, Array3};
+
+    #[test]
+    fn set_and_get_matches_dense_array() {
+        let extent = Extent3i::from_min_and_shape(PointN([-3, 1, 2]), PointN([5, 7, 3]));
+        let dense = Array3::fill_with(extent, |p| (p.x() + p.y() * p.z()) % 3 == 0);
+
+        let mut bits = BitArray3::fill(extent, false);
+        for p in extent.iter_points() {
+            bits.set_point(&p, dense.get(&p));
+        }
+
+        assert_eq!(bits, BitArray3::from_array(&dense));
+        for (i, p) in extent.iter_points().enumerate() {
+            assert_eq!(bits.get(Stride(i)), dense.get(Stride(i)));
+            assert_eq!(bits.get_ref(&p), dense.get_ref(&p));
+            assert_eq!(
+                unsafe { bits.get_unchecked(Stride(i)) },
+                dense.get(Stride(i))
+            );
+        }
+        assert_eq!(
+            bits.count_ones(),
+            dense.values_slice().iter().filter(|b| **b).count()
+        );
+    }
+
+    #[test]
+    fn bulk_ops_ignore_trailing_bits() {
+        // 105 points don't fill the last word.
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([7, 5, 3]));
+        let mut bits = BitArray3::fill(extent, true);
+        assert_eq!(bits.count_ones(), 105);
+
+        bits.invert();
+        assert_eq!(bits.count_ones(), 0);
+        assert!(!bits.any());
+
+        let evens = BitArray3::fill_with(extent, |p| p.x() % 2 == 0);
+        let low = BitArray3::fill_with(extent, |p| p.z() == 0);
+        bits |= &evens;
+        assert_eq!(bits.count_ones(), 4 * 5 * 3);
+        bits &= &low;
+        assert_eq!(bits.count_ones(), 4 * 5);
+        bits ^= &evens;
+        assert_eq!(bits.count_ones(), 4 * 5 * 2);
+        bits.invert();
+        assert_eq!(bits.count_ones(), 105 - 4 * 5 * 2);
+    }
+
+    #[test]
+    fn copy_to_and_from_dense_array() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([10; 3]));
+        let subextent = Extent3i::from_min_and_shape(PointN([2; 3]), PointN([5; 3]));
+        let dense = Array3::fill_with(extent, |p| p.y() > 4);
+
+        let mut bits = BitArray3::fill(extent, false);
+        copy_extent(&subextent, &dense, &mut bits);
+        bits.for_each_ref(&extent, |p: Point3i, value| {
+            assert_eq!(*value, subextent.contains(&p) && p.y() > 4);
+        });
+
+        let mut other_dense = Array3::fill(extent, false);
+        copy_extent(&extent, &bits, &mut other_dense);
+        assert_eq!(BitArray3::from_array(&other_dense), bits);
+    }
+}
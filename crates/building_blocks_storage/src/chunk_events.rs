@@ -0,0 +1,231 @@
+//! Change notifications for `ChunkMap`.
+//!
+//! Systems like meshing, physics and networking need to know which chunks changed since they last
+//! looked. Instead of diffing the set of chunk keys every frame, they can ask the `ChunkMap` to
+//! record a queue of `ChunkEvent`s, then drain it once per frame.
+//!
+//! Recording is off by default. While it's on, every API that mutates, inserts, removes or compresses
+//! a chunk pushes an event. Repeated modifications of the same chunk are coalesced into a single
+//! `Modified` event until the queue is drained, so writing one point at a time doesn't flood the
+//! queue.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, chunk_events::{ChunkEvent, ChunkEventKind}};
+//!
+//! let mut map = ChunkMap3::new(PointN([16; 3]), 0, (), FastLz4 { level: 10 });
+//! map.record_events(true);
+//!
+//! *map.get_mut(&PointN([1; 3])) = 1;
+//! *map.get_mut(&PointN([2; 3])) = 1;
+//! map.remove_chunk(PointN([0; 3]));
+//!
+//! let key = PointN([0; 3]);
+//! assert_eq!(
+//!     map.drain_events(),
+//!     vec![
+//!         ChunkEvent { key, kind: ChunkEventKind::Inserted },
+//!         ChunkEvent { key, kind: ChunkEventKind::Removed },
+//!     ]
+//! );
+//! ```
+
+use building_blocks_core::prelude::*;
+
+use core::hash::Hash;
+use fnv::{FnvHashMap, FnvHashSet};
+use std::collections::BTreeMap;
+
+/// What happened to a chunk.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChunkEventKind {
+    /// The chunk was created.
+    Inserted,
+    /// The chunk was mutably accessed, so its data or metadata may have changed.
+    Modified,
+    /// The chunk was compressed, so it will need to be decompressed on the next access.
+    Compressed,
+    /// The chunk was removed.
+    Removed,
+}
+
+/// Something that happened to the chunk at `key`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ChunkEvent<N> {
+    pub key: PointN<N>,
+    pub kind: ChunkEventKind,
+}
+
+/// The events recorded by a `ChunkMap`, with modifications coalesced.
+pub(crate) struct ChunkEventQueue<N> {
+    events: Vec<ChunkEvent<N>>,
+    // Chunks that already have an `Inserted` or `Modified` event in the queue since they were last
+    // removed.
+    pending_modified: FnvHashSet<PointN<N>>,
+    // The cached chunks in the order they were last used, mirroring the LRU order of the
+    // `CompressibleMap`, so we know which chunk `compress_lru` compresses.
+    lru_stamps: FnvHashMap<PointN<N>, u64>,
+    lru_order: BTreeMap<u64, PointN<N>>,
+    next_stamp: u64,
+}
+
+impl<N> Default for ChunkEventQueue<N> {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+            pending_modified: FnvHashSet::default(),
+            lru_stamps: FnvHashMap::default(),
+            lru_order: BTreeMap::new(),
+            next_stamp: 0,
+        }
+    }
+}
+
+impl<N> ChunkEventQueue<N>
+where
+    PointN<N>: Copy + Eq + Hash,
+{
+    pub fn inserted(&mut self, key: PointN<N>) {
+        self.touched(key);
+        self.pending_modified.insert(key);
+        self.push(key, ChunkEventKind::Inserted);
+    }
+
+    pub fn modified(&mut self, key: PointN<N>) {
+        self.touched(key);
+        if self.pending_modified.insert(key) {
+            self.push(key, ChunkEventKind::Modified);
+        }
+    }
+
+    pub fn accessed(&mut self, key: PointN<N>, inserted: bool) {
+        if inserted {
+            self.inserted(key);
+        } else {
+            self.modified(key);
+        }
+    }
+
+    /// The chunk at `key` was used without being modified, so it's now the most recently used.
+    pub fn touched(&mut self, key: PointN<N>) {
+        if let Some(stamp) = self.lru_stamps.insert(key, self.next_stamp) {
+            self.lru_order.remove(&stamp);
+        }
+        self.lru_order.insert(self.next_stamp, key);
+        self.next_stamp += 1;
+    }
+
+    /// The least recently used chunk was compressed. Returns its key.
+    pub fn compressed_lru(&mut self) -> Option<PointN<N>> {
+        let (_stamp, key) = self.lru_order.pop_first()?;
+        self.lru_stamps.remove(&key);
+        self.push(key, ChunkEventKind::Compressed);
+
+        Some(key)
+    }
+
+    pub fn removed(&mut self, key: PointN<N>) {
+        if let Some(stamp) = self.lru_stamps.remove(&key) {
+            self.lru_order.remove(&stamp);
+        }
+        self.pending_modified.remove(&key);
+        self.push(key, ChunkEventKind::Removed);
+    }
+
+    pub fn drain(&mut self) -> Vec<ChunkEvent<N>> {
+        self.pending_modified.clear();
+
+        std::mem::take(&mut self.events)
+    }
+
+    fn push(&mut self, key: PointN<N>, kind: ChunkEventKind) {
+        self.events.push(ChunkEvent { key, kind });
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{ChunkMap3, ChunkMapReader3, FastLz4, Get, GetMut, LocalChunkCache, WriteExtent};
+
+    use ChunkEventKind::*;
+
+    fn kinds(events: Vec<ChunkEvent<[i32; 3]>>) -> Vec<([i32; 3], ChunkEventKind)> {
+        events.into_iter().map(|e| (e.key.0, e.kind)).collect()
+    }
+
+    #[test]
+    fn events_from_mutations_removals_and_compression() {
+        let mut map = ChunkMap3::new(PointN([4; 3]), 0, (), FastLz4 { level: 10 });
+
+        // Nothing is recorded until requested.
+        *map.get_mut(&PointN([0; 3])) = 1;
+        assert!(map.drain_events().is_empty());
+        map.record_events(true);
+
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([8, 4, 4]));
+        map.write_extent(&extent, |_p: &_| 2);
+        *map.get_mut(&PointN([5, 0, 0])) = 3;
+        assert_eq!(
+            kinds(map.drain_events()),
+            vec![([0, 0, 0], Modified), ([4, 0, 0], Inserted)]
+        );
+
+        // Modifications are only coalesced until the queue is drained.
+        *map.get_mut(&PointN([5, 0, 0])) = 4;
+        map.compress_lru_chunk();
+        map.retain(|key, _chunk| key.x() == 0);
+        assert_eq!(
+            kinds(map.drain_events()),
+            vec![
+                ([4, 0, 0], Modified),
+                ([0, 0, 0], Compressed),
+                ([4, 0, 0], Removed)
+            ]
+        );
+
+        map.record_events(false);
+        *map.get_mut(&PointN([5, 0, 0])) = 4;
+        assert!(map.drain_events().is_empty());
+    }
+
+    #[test]
+    fn compressed_events_follow_lru_order() {
+        let mut map = ChunkMap3::new(PointN([4; 3]), 0, (), FastLz4 { level: 10 });
+        // Chunks that were cached before recording started are used again in some known order.
+        *map.get_mut(&PointN([0; 3])) = 1;
+        *map.get_mut(&PointN([4, 0, 0])) = 1;
+        map.record_events(true);
+        *map.get_mut(&PointN([8, 0, 0])) = 1;
+        *map.get_mut(&PointN([0; 3])) = 2;
+        map.drain_events();
+
+        map.compress_lru_chunk();
+        map.compress_lru_chunk();
+        assert_eq!(
+            kinds(map.drain_events()),
+            vec![([4, 0, 0], Compressed), ([8, 0, 0], Compressed)]
+        );
+
+        // Chunks flushed from a local cache become the most recently used.
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&map, &local_cache);
+        assert_eq!(reader.get(&PointN([4, 0, 0])), 1);
+        map.flush_chunk_cache(local_cache);
+        map.compress_lru_chunk();
+        map.compress_lru_chunk();
+        map.compress_lru_chunk();
+        assert_eq!(
+            kinds(map.drain_events()),
+            vec![([0, 0, 0], Compressed), ([4, 0, 0], Compressed)]
+        );
+    }
+}
@@ -6,7 +6,7 @@
 //! use building_blocks_core::prelude::*;
 //! use building_blocks_storage::prelude::*;
 //!
-//! let chunk_shape = PointN([16; 3]); // components must be powers of 2
+//! let chunk_shape = PointN([16; 3]); // any positive shape; powers of 2 use a faster bit-mask path
 //! let ambient_value = 0;
 //! let default_chunk_meta = (); // chunk metadata is optional
 //! let mut map = ChunkMap3::new(
@@ -76,21 +76,27 @@ use crate::{
         WriteExtent,
     },
     array::{Array, ArrayCopySrc, ArrayN, FastLz4CompressedArrayN},
-    FastLz4, Get, GetMut, GetRef,
+    chunk_events::{ChunkEvent, ChunkEventQueue},
+    chunk_summary::ChunkSummary,
+    FastLz4, Get, GetMut, GetRef, Stride,
 };
 
 use building_blocks_core::{
-    bounding_extent, ExtentN, IntegerExtent, IntegerPoint, Ones, Point, Point2i, Point3i, PointN,
+    bounding_extent, ExtentN, HilbertEncode, IntegerExtent, IntegerPoint, Ones, Point, Point2i,
+    Point3i, PointN,
 };
 
 use compressible_map::{
     BincodeLz4, BincodeLz4Compressed, Compressible, CompressibleMap, Decompressible, LocalCache,
     MaybeCompressed,
 };
-use core::hash::Hash;
+use core::hash::{Hash, Hasher};
 use either::Either;
-use fnv::FnvHashMap;
+use fnv::{FnvBuildHasher, FnvHashMap, FnvHashSet, FnvHasher};
+use indexmap::IndexMap;
+use num::Zero;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io;
 
 /// Stores a partial (sparse) function on the N-dimensional integers (where N=2 or N=3) in
 /// same-shaped chunks using a `CompressibleMap`. The data can either be addressed by chunk with the
@@ -101,6 +107,9 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 /// `LocalChunkCache`. To read individual points, you can use the `ChunkMapReader`, which
 /// also uses a `LocalChunkCache`. A `LocalChunkCache` can be written back to the
 /// `ChunkMap` using the `flush_chunk_cache` method.
+///
+/// Every key owns its own chunk in memory, even when another chunk has identical contents. Identical
+/// chunks are only deduplicated when the map is serialized (see `to_serializable`).
 pub struct ChunkMap<N, T, M = ()>
 where
     T: Copy,
@@ -108,13 +117,23 @@ where
     ExtentN<N>: IntegerExtent<N>,
 {
     chunk_shape: PointN<N>,
-    chunk_shape_mask: PointN<N>,
+    // Only used when the chunk shape has power-of-2 dimensions.
+    chunk_shape_mask: Option<PointN<N>>,
 
     // The value to use when none is specified, i.e. when filling new chunks or erasing points.
     ambient_value: T,
 
     default_chunk_metadata: M,
 
+    // Chunks that were mutated since their summary metadata was last updated.
+    dirty_chunks: FnvHashSet<PointN<N>>,
+
+    // Chunks that were mutated or removed since they were last saved to a `ChunkStore`.
+    unsaved_chunks: FnvHashSet<PointN<N>>,
+
+    // Only recorded when requested with `record_events`.
+    events: Option<ChunkEventQueue<N>>,
+
     /// The chunks themselves, stored in a `CompressibleMap`.
     pub chunks: CompressibleFnvMap<PointN<N>, Chunk<N, T, M>, FastLz4>,
 }
@@ -186,9 +205,19 @@ where
 pub trait ChunkShape<N> {
     fn dimensions_are_powers_of_2(&self) -> bool;
 
-    /// Makes the mask required to convert points to chunk keys.
+    /// Makes the mask required to convert points to chunk keys. Only possible when the dimensions
+    /// are powers of 2.
     fn mask(&self) -> PointN<N>;
 
+    /// The mask for this chunk shape, if it has one.
+    fn mask_if_powers_of_2(&self) -> Option<PointN<N>> {
+        if self.dimensions_are_powers_of_2() {
+            Some(self.mask())
+        } else {
+            None
+        }
+    }
+
     /// A chunk key is just the leading m bits of each component of a point, where m depends on the
     /// size of the chunk. It can also be interpreted as the minimum point of a chunk extent.
     fn chunk_key_containing_point(mask: &PointN<N>, p: &PointN<N>) -> PointN<N>;
@@ -203,6 +232,8 @@ impl ChunkShape<[i32; 2]> for Point2i {
     }
 
     fn mask(&self) -> Point2i {
+        assert!(self.dimensions_are_powers_of_2());
+
         PointN([!(self.x() - 1), !(self.y() - 1)])
     }
 
@@ -239,18 +270,24 @@ where
     PointN<N>: IntegerPoint + ChunkShape<N> + Eq + Hash,
     ExtentN<N>: IntegerExtent<N>,
 {
-    /// Creates an empty map.
+    /// Creates an empty map. Every dimension of `chunk_shape` must be positive. Chunk keys are
+    /// computed fastest when the dimensions are powers of 2.
     pub fn new(
         chunk_shape: PointN<N>,
         ambient_value: T,
         default_chunk_metadata: M,
         compression_params: FastLz4,
     ) -> Self {
+        assert!(chunk_shape > PointN::zero());
+
         Self {
             chunk_shape,
-            chunk_shape_mask: chunk_shape.mask(),
+            chunk_shape_mask: chunk_shape.mask_if_powers_of_2(),
             ambient_value,
             default_chunk_metadata,
+            dirty_chunks: FnvHashSet::default(),
+            unsaved_chunks: FnvHashSet::default(),
+            events: None,
             chunks: CompressibleFnvMap::new(compression_params),
         }
     }
@@ -260,14 +297,28 @@ where
         &self.chunk_shape
     }
 
-    /// The mask used for calculating the chunk key of a chunk that contains a given point.
-    pub fn chunk_shape_mask(&self) -> &PointN<N> {
-        &self.chunk_shape_mask
+    /// The value of all points that are not in any chunk.
+    pub fn ambient_value(&self) -> &T {
+        &self.ambient_value
+    }
+
+    /// The metadata given to new chunks.
+    pub fn default_chunk_metadata(&self) -> &M {
+        &self.default_chunk_metadata
+    }
+
+    /// The mask used for calculating the chunk key of a chunk that contains a given point. Only
+    /// exists if the chunk shape has power-of-2 dimensions.
+    pub fn chunk_shape_mask(&self) -> Option<&PointN<N>> {
+        self.chunk_shape_mask.as_ref()
     }
 
     /// Returns the key of the chunk that contains `point`.
     pub fn chunk_key(&self, point: &PointN<N>) -> PointN<N> {
-        PointN::chunk_key_containing_point(self.chunk_shape_mask(), point)
+        match self.chunk_shape_mask() {
+            Some(mask) => PointN::chunk_key_containing_point(mask, point),
+            None => (*point / self.chunk_shape) * self.chunk_shape,
+        }
     }
 
     /// Same as `chunk_key_iter`, but for this map's chunk shape.
@@ -291,7 +342,16 @@ where
 
     /// Returns the mutable chunk at `key` if it exists.
     pub fn get_mut_chunk(&mut self, key: PointN<N>) -> Option<&mut Chunk<N, T, M>> {
-        self.chunks.get_mut(key)
+        let chunk = self.chunks.get_mut(key);
+        if chunk.is_some() {
+            self.dirty_chunks.insert(key);
+            self.unsaved_chunks.insert(key);
+            if let Some(events) = &mut self.events {
+                events.modified(key);
+            }
+        }
+
+        chunk
     }
 
     /// Get mutable chunk for `key`. If `key` does not exist, calls `fill_empty_chunk` to fill that
@@ -303,13 +363,25 @@ where
     ) -> &mut Chunk<N, T, M> {
         let ChunkMap {
             chunk_shape,
+            dirty_chunks,
+            unsaved_chunks,
+            events,
             chunks,
             ..
         } = self;
 
-        chunks.get_or_insert_with(key, || {
+        dirty_chunks.insert(key);
+        unsaved_chunks.insert(key);
+        let mut inserted = false;
+        let chunk = chunks.get_or_insert_with(key, || {
+            inserted = true;
             create_chunk(&key, &extent_for_chunk_at_key(chunk_shape, &key))
-        })
+        });
+        if let Some(events) = events {
+            events.accessed(key, inserted);
+        }
+
+        chunk
     }
 
     /// Returns the chunk containing `point` if it exists.
@@ -350,6 +422,38 @@ where
         }))
     }
 
+    /// An iterator over every chunk overlapping `extent`, yielding the chunk key, the
+    /// intersection of `extent` with the chunk's extent, and the chunk if it exists. Missing
+    /// chunks take the ambient value everywhere.
+    #[allow(clippy::type_complexity)]
+    pub fn chunks_in_extent<'a>(
+        &'a self,
+        extent: &ExtentN<N>,
+        local_cache: &'a LocalChunkCache<N, T, M>,
+    ) -> impl Iterator<Item = (PointN<N>, ExtentN<N>, Option<&'a Chunk<N, T, M>>)> {
+        let extent = *extent;
+
+        self.key_iter(&extent).map(move |key| {
+            let clipped_extent = extent.intersection(&self.extent_for_chunk_at_key(&key));
+
+            (key, clipped_extent, self.get_chunk(key, local_cache))
+        })
+    }
+
+    /// The mutable counterpart of `chunks_in_extent`. Calls `f` with the key, the clipped extent
+    /// and the chunk (if it exists) for every chunk overlapping `extent`. Missing chunks are not
+    /// inserted, but existing chunks are marked dirty.
+    pub fn for_each_chunk_in_extent_mut(
+        &mut self,
+        extent: &ExtentN<N>,
+        mut f: impl FnMut(PointN<N>, ExtentN<N>, Option<&mut Chunk<N, T, M>>),
+    ) {
+        for key in self.key_iter(extent) {
+            let clipped_extent = extent.intersection(&self.extent_for_chunk_at_key(&key));
+            f(key, clipped_extent, self.get_mut_chunk(key));
+        }
+    }
+
     /// Get mutable data for point `p`. If `p` does not exist, calls `fill_empty_chunk` to fill
     /// that entry first.
     pub fn get_mut_or_insert_chunk_with(
@@ -377,57 +481,440 @@ where
             chunk_shape,
             ambient_value,
             default_chunk_metadata,
+            dirty_chunks,
+            unsaved_chunks,
+            events,
             chunks,
             ..
         } = self;
+        dirty_chunks.insert(key);
+        unsaved_chunks.insert(key);
+        let mut inserted = false;
         let array = &mut chunks
-            .get_or_insert_with(key, || Chunk {
-                metadata: default_chunk_metadata.clone(),
-                map: ArrayN::fill(extent_for_chunk_at_key(chunk_shape, &key), *ambient_value),
+            .get_or_insert_with(key, || {
+                inserted = true;
+                Chunk {
+                    metadata: default_chunk_metadata.clone(),
+                    map: ArrayN::fill(extent_for_chunk_at_key(chunk_shape, &key), *ambient_value),
+                }
             })
             .map;
+        if let Some(events) = events {
+            events.accessed(key, inserted);
+        }
 
         (key, array.get_unchecked_mut_release(p))
     }
 
+    /// Inserts `chunk` at `key`, returning the old chunk if there was one. Compressed chunks are
+    /// decompressed.
+    pub fn insert_chunk(
+        &mut self,
+        key: PointN<N>,
+        chunk: Chunk<N, T, M>,
+    ) -> Option<Chunk<N, T, M>> {
+        self.dirty_chunks.insert(key);
+        self.unsaved_chunks.insert(key);
+        let old_chunk = self.chunks.insert(key, chunk).map(|chunk| match chunk {
+            MaybeCompressed::Decompressed(chunk) => chunk,
+            MaybeCompressed::Compressed(compressed_chunk) => compressed_chunk.decompress(),
+        });
+        if let Some(events) = &mut self.events {
+            events.accessed(key, old_chunk.is_none());
+        }
+
+        old_chunk
+    }
+
+    /// Removes the chunk at `key`, returning it if it existed. Compressed chunks are decompressed.
+    ///
+    /// The chunk is erased, so `save_changes` will also delete it from the `ChunkStore`. To only
+    /// evict the chunk from memory, use `unload_chunk`.
+    pub fn remove_chunk(&mut self, key: PointN<N>) -> Option<Chunk<N, T, M>> {
+        let chunk = self.take_chunk(key);
+        if chunk.is_some() {
+            self.unsaved_chunks.insert(key);
+        }
+
+        chunk
+    }
+
+    /// Evicts the chunk at `key` from memory, returning it if it existed. Unlike `remove_chunk`,
+    /// the chunk is not erased from the `ChunkStore`, so it can be loaded again later. Any changes
+    /// that weren't saved with `save_changes` are lost.
+    pub fn unload_chunk(&mut self, key: PointN<N>) -> Option<Chunk<N, T, M>> {
+        self.unsaved_chunks.remove(&key);
+
+        self.take_chunk(key)
+    }
+
+    fn take_chunk(&mut self, key: PointN<N>) -> Option<Chunk<N, T, M>> {
+        self.dirty_chunks.remove(&key);
+        let chunk = self.chunks.remove(&key).map(|chunk| match chunk {
+            MaybeCompressed::Decompressed(chunk) => chunk,
+            MaybeCompressed::Compressed(compressed_chunk) => compressed_chunk.decompress(),
+        });
+        if let (Some(events), true) = (&mut self.events, chunk.is_some()) {
+            events.removed(key);
+        }
+
+        chunk
+    }
+
+    /// Keeps only the chunks for which `f` returns `true`. Compressed chunks are decompressed
+    /// temporarily in order to call `f`, but they stay compressed if they are kept. The other chunks
+    /// are erased like with `remove_chunk`.
+    pub fn retain(&mut self, mut f: impl FnMut(&PointN<N>, &Chunk<N, T, M>) -> bool) {
+        let remove_keys: Vec<_> = self
+            .chunks
+            .iter_maybe_compressed()
+            .filter(|(chunk_key, chunk)| match chunk {
+                MaybeCompressed::Decompressed(chunk) => !f(chunk_key, chunk),
+                MaybeCompressed::Compressed(compressed_chunk) => {
+                    !f(chunk_key, &compressed_chunk.decompress())
+                }
+            })
+            .map(|(chunk_key, _)| *chunk_key)
+            .collect();
+
+        for chunk_key in remove_keys.into_iter() {
+            self.remove_chunk(chunk_key);
+        }
+    }
+
+    /// Removes every chunk where all points have the ambient value, along with its metadata.
+    pub fn prune_ambient_chunks(&mut self)
+    where
+        T: PartialEq,
+    {
+        let ambient_value = self.ambient_value;
+        self.retain(|_key, chunk| chunk.map.values_slice().iter().any(|v| *v != ambient_value));
+    }
+
+    /// Sets every point in `extent` to `value`. When `value` is the ambient value, chunks that are
+    /// entirely covered by `extent` are removed, and no new chunks are inserted.
+    pub fn fill_extent(&mut self, extent: &ExtentN<N>, value: T)
+    where
+        T: PartialEq,
+        ExtentN<N>: PartialEq,
+        ArrayN<N, T>: ForEachMut<N, Stride, Data = T>,
+    {
+        if value == self.ambient_value {
+            let covered_keys: Vec<_> = self
+                .key_iter(extent)
+                .filter(|key| self.extent_for_chunk_at_key(key).is_subset_of(extent))
+                .collect();
+            for key in covered_keys.into_iter() {
+                self.remove_chunk(key);
+            }
+            self.for_each_mut_existing(extent, |_s: Stride, v| *v = value);
+        } else {
+            self.for_each_chunk_mut(extent, true, |_key, chunk| {
+                chunk.map.for_each_mut(extent, |_s: Stride, v| *v = value)
+            });
+        }
+    }
+
+    /// Sets every point `p` in `extent` to `filler(&p)`, inserting any missing chunks. This is much
+    /// faster than writing one point at a time, since each chunk is only looked up once.
+    pub fn fill_with(&mut self, extent: &ExtentN<N>, filler: impl Fn(&PointN<N>) -> T)
+    where
+        ArrayN<N, T>: ForEachMut<N, (PointN<N>, Stride), Data = T>,
+    {
+        self.for_each_chunk_mut(extent, true, |_key, chunk| {
+            chunk
+                .map
+                .for_each_mut(extent, |(p, _s): (PointN<N>, Stride), v| *v = filler(&p))
+        });
+    }
+
+    /// Like the `ForEachMut` impl, but only visits chunks that already exist. Points of `extent`
+    /// that aren't covered by any chunk are skipped.
+    pub fn for_each_mut_existing<C>(&mut self, extent: &ExtentN<N>, mut f: impl FnMut(C, &mut T))
+    where
+        ArrayN<N, T>: ForEachMut<N, C, Data = T>,
+    {
+        self.for_each_chunk_mut(extent, false, |_key, chunk| {
+            chunk.map.for_each_mut(extent, |c, v| f(c, v))
+        });
+    }
+
+    /// Calls `f` on every chunk overlapping `extent`. Missing chunks are skipped unless
+    /// `insert_missing` is set, in which case they are filled with the ambient value and default
+    /// metadata first.
+    fn for_each_chunk_mut(
+        &mut self,
+        extent: &ExtentN<N>,
+        insert_missing: bool,
+        mut f: impl FnMut(PointN<N>, &mut Chunk<N, T, M>),
+    ) {
+        let ChunkMap {
+            chunk_shape,
+            ambient_value,
+            default_chunk_metadata,
+            dirty_chunks,
+            unsaved_chunks,
+            events,
+            chunks,
+            ..
+        } = self;
+
+        for chunk_key in chunk_key_iter(*chunk_shape, extent) {
+            if insert_missing {
+                let mut inserted = false;
+                let chunk = chunks.get_or_insert_with(chunk_key, || {
+                    inserted = true;
+                    Chunk {
+                        metadata: default_chunk_metadata.clone(),
+                        map: ArrayN::fill(
+                            extent_for_chunk_at_key(chunk_shape, &chunk_key),
+                            *ambient_value,
+                        ),
+                    }
+                });
+                dirty_chunks.insert(chunk_key);
+                unsaved_chunks.insert(chunk_key);
+                if let Some(events) = events {
+                    events.accessed(chunk_key, inserted);
+                }
+                f(chunk_key, chunk);
+            } else if let Some(chunk) = chunks.get_mut(chunk_key) {
+                dirty_chunks.insert(chunk_key);
+                unsaved_chunks.insert(chunk_key);
+                if let Some(events) = events {
+                    events.modified(chunk_key);
+                }
+                f(chunk_key, chunk);
+            }
+        }
+    }
+
+    /// An iterator over the keys of chunks that were mutated or erased since they were last saved
+    /// with `save_changes`.
+    pub fn unsaved_chunk_keys(&self) -> impl Iterator<Item = &PointN<N>> {
+        self.unsaved_chunks.iter()
+    }
+
+    pub(crate) fn clear_unsaved_chunks(&mut self) {
+        self.unsaved_chunks.clear();
+    }
+
+    /// An iterator over the keys of chunks that were mutated since their summary was last updated.
+    pub fn dirty_chunk_keys(&self) -> impl Iterator<Item = &PointN<N>> {
+        self.dirty_chunks.iter()
+    }
+
+    /// Returns true if the chunk at `key` was mutated since its summary was last updated.
+    pub fn chunk_is_dirty(&self, key: &PointN<N>) -> bool {
+        self.dirty_chunks.contains(key)
+    }
+
+    /// Recomputes the summary metadata of the chunk at `key` if it is dirty, then returns it.
+    pub fn update_chunk_summary(&mut self, key: PointN<N>) -> Option<&M>
+    where
+        M: ChunkSummary<N, T>,
+    {
+        let is_dirty = self.dirty_chunks.remove(&key);
+        let chunk = self.chunks.get_mut(key)?;
+        if let Some(events) = &mut self.events {
+            events.touched(key);
+        }
+        if is_dirty {
+            let Chunk { metadata, map } = chunk;
+            metadata.summarize(map);
+        }
+
+        Some(&chunk.metadata)
+    }
+
+    /// Recomputes the summary metadata of all dirty chunks.
+    pub fn update_chunk_summaries(&mut self)
+    where
+        M: ChunkSummary<N, T>,
+    {
+        let dirty_keys: Vec<_> = self.dirty_chunks.iter().cloned().collect();
+        for key in dirty_keys.into_iter() {
+            self.update_chunk_summary(key);
+        }
+    }
+
+    /// Returns the keys of the existing chunks overlapping `extent` whose (updated) summary
+    /// metadata satisfies `predicate`.
+    pub fn chunk_keys_with_summary(
+        &mut self,
+        extent: &ExtentN<N>,
+        predicate: impl Fn(&M) -> bool,
+    ) -> Vec<PointN<N>>
+    where
+        M: ChunkSummary<N, T>,
+    {
+        self.key_iter(extent)
+            .filter(|key| self.update_chunk_summary(*key).is_some_and(&predicate))
+            .collect()
+    }
+
     /// Compressed the least-recently-used chunk using LZ4 compression. On access, compressed chunks
     /// will be decompressed and cached.
     pub fn compress_lru_chunk(&mut self) {
         self.chunks.compress_lru();
+        if let Some(events) = &mut self.events {
+            events.compressed_lru();
+        }
+    }
+
+    /// Starts or stops recording `ChunkEvent`s. Stopping also discards any events that haven't
+    /// been drained.
+    ///
+    /// While recording, the map keeps track of the order in which chunks are used, so it can tell
+    /// which chunk `compress_lru_chunk` compresses. This only works if the chunks are accessed
+    /// through the methods of `ChunkMap`, not directly with `ChunkMap::chunks`.
+    pub fn record_events(&mut self, enabled: bool) {
+        match (enabled, &self.events) {
+            (true, None) => {
+                // We don't know the order in which the cached chunks were used, so we use them
+                // all again in a known order.
+                let mut events = ChunkEventQueue::default();
+                for key in self.cached_chunk_keys() {
+                    self.chunks.get_mut(key);
+                    events.touched(key);
+                }
+                self.events = Some(events);
+            }
+            (false, Some(_)) => self.events = None,
+            _ => (),
+        }
+    }
+
+    /// Removes and returns all of the events recorded since the last drain, in order.
+    pub fn drain_events(&mut self) -> Vec<ChunkEvent<N>> {
+        self.events
+            .as_mut()
+            .map(|events| events.drain())
+            .unwrap_or_default()
     }
 
     /// Consumes and flushes the chunk cache into the chunk map. This is not strictly necessary, but
     /// it will help with caching efficiency.
+    ///
+    /// When events are being recorded, this takes time linear in the number of chunks.
     pub fn flush_chunk_cache(&mut self, local_cache: LocalChunkCache<N, T, M>) {
+        if self.events.is_none() {
+            self.chunks.flush_local_cache(local_cache);
+            return;
+        }
+
+        // The flushed chunks become the most recently used, in no particular order, so we use them
+        // again in a known order.
+        let cached_before: FnvHashSet<_> = self.cached_chunk_keys().into_iter().collect();
         self.chunks.flush_local_cache(local_cache);
+        for key in self.cached_chunk_keys().into_iter() {
+            if !cached_before.contains(&key) {
+                self.chunks.get_mut(key);
+                if let Some(events) = &mut self.events {
+                    events.touched(key);
+                }
+            }
+        }
+    }
+
+    // For code that uses `self.chunks` directly, to keep the recorded LRU order in sync.
+    pub(crate) fn chunk_touched(&mut self, key: PointN<N>) {
+        if let Some(events) = &mut self.events {
+            events.touched(key);
+        }
+    }
+
+    fn cached_chunk_keys(&self) -> Vec<PointN<N>> {
+        self.chunks
+            .iter_maybe_compressed()
+            .filter_map(|(key, chunk)| match chunk {
+                MaybeCompressed::Decompressed(_) => Some(*key),
+                MaybeCompressed::Compressed(_) => None,
+            })
+            .collect()
+    }
+
+    /// All occupied chunk keys, sorted so that chunks which are close in space are usually close
+    /// in the sequence.
+    ///
+    /// In 3D, chunk coordinates (`key / chunk_shape`, not voxel coordinates) must lie in
+    /// `[-2^20, 2^20)`, the range supported by `HilbertEncode`. Debug builds panic on chunks
+    /// outside that range; release builds wrap their coordinates, which only affects the order.
+    pub fn chunk_keys_in_hilbert_order(&self) -> Vec<PointN<N>>
+    where
+        PointN<N>: HilbertEncode,
+    {
+        let mut keys: Vec<_> = self.chunk_keys().cloned().collect();
+        keys.sort_by_cached_key(|key| self.chunk_hilbert_index(key));
+
+        keys
+    }
+
+    /// The index on the Hilbert curve of the chunk at `key`, in units of chunks.
+    pub(crate) fn chunk_hilbert_index(&self, key: &PointN<N>) -> u64
+    where
+        PointN<N>: HilbertEncode,
+    {
+        (*key / self.chunk_shape).hilbert_encode()
     }
 
     /// Returns a serializable version of this map. This will compress every chunk in a portable
-    /// way.
+    /// way. Chunks with the same values and metadata as another chunk are only stored once.
+    ///
+    /// Chunks are emitted in Hilbert order (see `chunk_keys_in_hilbert_order`), so nearby chunks
+    /// end up close together in the serialized bytes. This has the same limit on 3D chunk
+    /// coordinates as `chunk_keys_in_hilbert_order`.
     pub fn to_serializable(&self, params: BincodeLz4) -> SerializableChunkMap<N, T, M>
     where
+        T: Serialize,
+        M: Serialize,
         Chunk<N, T, M>: DeserializeOwned + Serialize,
+        PointN<N>: HilbertEncode,
     {
-        let portable_chunks = self
-            .chunks
-            .iter_maybe_compressed()
-            .map(|(chunk_key, chunk)| {
-                let portable_chunk: BincodeLz4Compressed<Chunk<N, T, M>> = match chunk {
-                    MaybeCompressed::Compressed(compressed_chunk) => {
-                        compressed_chunk.decompress().compress(params)
+        let mut chunks: Vec<_> = self.chunks.iter_maybe_compressed().collect();
+        chunks.sort_by_cached_key(|(chunk_key, _chunk)| self.chunk_hilbert_index(chunk_key));
+
+        // Only hashes are kept for the chunks seen so far, along with the positions in `chunks` of
+        // the unique chunks with each hash. Contents are only compared when the hashes match.
+        let mut unique_chunks_by_hash: FnvHashMap<u64, Vec<usize>> = FnvHashMap::default();
+        let mut compressed_chunks = IndexMap::default();
+        let mut chunk_aliases = FnvHashMap::default();
+        for (i, (chunk_key, chunk)) in chunks.iter().enumerate() {
+            let decompressed;
+            let chunk = match chunk {
+                MaybeCompressed::Compressed(compressed_chunk) => {
+                    decompressed = compressed_chunk.decompress();
+                    &decompressed
+                }
+                MaybeCompressed::Decompressed(chunk) => *chunk,
+            };
+
+            let same_hash = unique_chunks_by_hash
+                .entry(hash_chunk_contents(chunk))
+                .or_default();
+            let original = same_hash.iter().find(|j| {
+                let (_, other) = &chunks[**j];
+                match other {
+                    MaybeCompressed::Compressed(compressed_other) => {
+                        chunk_contents_eq(chunk, &compressed_other.decompress())
                     }
-                    MaybeCompressed::Decompressed(chunk) => chunk.compress(params),
-                };
-
-                (*chunk_key, portable_chunk)
-            })
-            .collect();
+                    MaybeCompressed::Decompressed(other) => chunk_contents_eq(chunk, other),
+                }
+            });
+            if let Some(j) = original {
+                chunk_aliases.insert(**chunk_key, *chunks[*j].0);
+            } else {
+                same_hash.push(i);
+                compressed_chunks.insert(**chunk_key, chunk.compress(params));
+            }
+        }
 
         SerializableChunkMap {
             chunk_shape: self.chunk_shape,
             ambient_value: self.ambient_value,
             default_chunk_metadata: self.default_chunk_metadata.clone(),
-            compressed_chunks: portable_chunks,
+            compressed_chunks,
+            chunk_aliases,
         }
     }
 
@@ -443,12 +930,31 @@ where
             compressible_map.insert(*chunk_key, compressed_chunk.decompress());
             compressible_map.compress_lru();
         }
+        // Each original is only decompressed once, no matter how many aliases it has.
+        let mut originals = FnvHashMap::default();
+        for (chunk_key, original_key) in map.chunk_aliases.iter() {
+            let original: &Chunk<N, T, M> = originals
+                .entry(*original_key)
+                .or_insert_with(|| map.compressed_chunks[original_key].decompress());
+            let chunk = Chunk {
+                metadata: original.metadata.clone(),
+                map: ArrayN::new(
+                    extent_for_chunk_at_key(&map.chunk_shape, chunk_key),
+                    original.map.values_slice().to_vec(),
+                ),
+            };
+            compressible_map.insert(*chunk_key, chunk);
+            compressible_map.compress_lru();
+        }
 
         Self {
             chunk_shape: map.chunk_shape,
-            chunk_shape_mask: map.chunk_shape.mask(),
+            chunk_shape_mask: map.chunk_shape.mask_if_powers_of_2(),
             ambient_value: map.ambient_value,
             default_chunk_metadata: map.default_chunk_metadata.clone(),
+            dirty_chunks: FnvHashSet::default(),
+            unsaved_chunks: FnvHashSet::default(),
+            events: None,
             chunks: compressible_map,
         }
     }
@@ -463,8 +969,8 @@ where
     PointN<N>: Eq + Hash,
     ExtentN<N>: IntegerExtent<N>,
 {
-    map: &'a ChunkMap<N, T, M>,
-    local_cache: &'a LocalChunkCache<N, T, M>,
+    pub(crate) map: &'a ChunkMap<N, T, M>,
+    pub(crate) local_cache: &'a LocalChunkCache<N, T, M>,
 }
 
 pub type ChunkMapReader2<'a, T, M> = ChunkMapReader<'a, [i32; 2], T, M>;
@@ -484,7 +990,8 @@ where
 }
 
 /// Call `ChunkMap::to_serializable` to get this type, which is an LZ4-compressed,
-/// serde-serializable type.
+/// serde-serializable type. For long-lived saves, prefer the versioned format in the
+/// `chunk_map_file` module.
 #[allow(clippy::type_complexity)]
 #[derive(Deserialize, Serialize)]
 pub struct SerializableChunkMap<N, T, M = ()>
@@ -494,7 +1001,58 @@ where
     pub chunk_shape: PointN<N>,
     pub ambient_value: T,
     pub default_chunk_metadata: M,
-    pub compressed_chunks: FnvHashMap<PointN<N>, BincodeLz4Compressed<Chunk<N, T, M>>>,
+    /// Chunks in the order they were emitted by `ChunkMap::to_serializable`.
+    pub compressed_chunks:
+        IndexMap<PointN<N>, BincodeLz4Compressed<Chunk<N, T, M>>, FnvBuildHasher>,
+    /// Maps the key of each chunk that was deduplicated to the key of an identical chunk in
+    /// `compressed_chunks`.
+    ///
+    /// This field is only defaulted by self-describing formats. Bincode can't tell that a field is
+    /// missing, so use `SerializableChunkMap::from_bincode` to load bincode data written before
+    /// this field existed.
+    #[serde(default)]
+    pub chunk_aliases: FnvHashMap<PointN<N>, PointN<N>>,
+}
+
+impl<N, T, M> SerializableChunkMap<N, T, M>
+where
+    Self: DeserializeOwned,
+    N: DeserializeOwned,
+    T: DeserializeOwned,
+    M: DeserializeOwned,
+    PointN<N>: Eq + Hash,
+{
+    /// Deserializes a map from bincode, including maps that were serialized before
+    /// `chunk_aliases` existed.
+    pub fn from_bincode(bytes: &[u8]) -> bincode::Result<Self> {
+        // Bincode ignores trailing bytes, so the current layout must be tried first. The old layout
+        // always ends before `chunk_aliases` would start.
+        bincode::deserialize(bytes).or_else(|error| {
+            let old: SerializableChunkMapV0<N, T, M> =
+                bincode::deserialize(bytes).map_err(|_| error)?;
+
+            Ok(SerializableChunkMap {
+                chunk_shape: old.chunk_shape,
+                ambient_value: old.ambient_value,
+                default_chunk_metadata: old.default_chunk_metadata,
+                compressed_chunks: old.compressed_chunks,
+                chunk_aliases: FnvHashMap::default(),
+            })
+        })
+    }
+}
+
+/// The layout of `SerializableChunkMap` before chunks were deduplicated.
+#[allow(clippy::type_complexity)]
+#[derive(Deserialize, Serialize)]
+struct SerializableChunkMapV0<N, T, M>
+where
+    PointN<N>: Eq + Hash,
+{
+    chunk_shape: PointN<N>,
+    ambient_value: T,
+    default_chunk_metadata: M,
+    compressed_chunks: IndexMap<PointN<N>, BincodeLz4Compressed<Chunk<N, T, M>>, FnvBuildHasher>,
 }
 
 pub type SerializableChunkMap2<T, M> = SerializableChunkMap<[i32; 2], T, M>;
@@ -526,6 +1084,45 @@ impl<N, T> AmbientExtent<N, T> {
     }
 }
 
+// Chunks at different keys have different extents, so only the metadata and values are compared.
+
+fn hash_chunk_contents<N, T, M>(chunk: &Chunk<N, T, M>) -> u64
+where
+    T: Serialize,
+    M: Serialize,
+{
+    // Streaming the bytes into the hasher means they never need to be buffered.
+    let mut writer = HashWriter(FnvHasher::default());
+    bincode::serialize_into(&mut writer, &(&chunk.metadata, chunk.map.values_slice())).unwrap();
+
+    writer.0.finish()
+}
+
+fn chunk_contents_eq<N, T, M>(a: &Chunk<N, T, M>, b: &Chunk<N, T, M>) -> bool
+where
+    T: Serialize,
+    M: Serialize,
+{
+    let serialize =
+        |c: &Chunk<N, T, M>| bincode::serialize(&(&c.metadata, c.map.values_slice())).unwrap();
+
+    serialize(a) == serialize(b)
+}
+
+struct HashWriter(FnvHasher);
+
+impl io::Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 /// Returns the extent of the chunk at `key`.
 pub fn extent_for_chunk_at_key<N>(chunk_shape: &PointN<N>, key: &PointN<N>) -> ExtentN<N>
 where
@@ -627,13 +1224,14 @@ where
     type Data = T;
 
     fn for_each_ref(&self, extent: &ExtentN<N>, mut f: impl FnMut(PointN<N>, &Self::Data)) {
-        for chunk_key in self.map.key_iter(extent) {
-            if let Some(chunk) = self.map.get_chunk(chunk_key, &self.local_cache) {
-                chunk.map.for_each_ref(extent, |p, value| f(p, value));
+        for (_key, clipped_extent, chunk) in self.map.chunks_in_extent(extent, self.local_cache) {
+            if let Some(chunk) = chunk {
+                chunk
+                    .map
+                    .for_each_ref(&clipped_extent, |p, value| f(p, value));
             } else {
-                let chunk_extent = self.map.extent_for_chunk_at_key(&chunk_key);
                 AmbientExtent::new(self.map.ambient_value)
-                    .for_each_ref(&extent.intersection(&chunk_extent), |p, value| f(p, value))
+                    .for_each_ref(&clipped_extent, |p, value| f(p, value))
             }
         }
     }
@@ -656,31 +1254,16 @@ impl<'a, N, T, M> ForEachMut<N, PointN<N>> for ChunkMap<N, T, M>
 where
     T: Copy,
     M: Clone,
-    PointN<N>: Point + ChunkShape<N> + Eq + Hash,
+    PointN<N>: IntegerPoint + ChunkShape<N> + Eq + Hash,
     ExtentN<N>: IntegerExtent<N>,
     ArrayN<N, T>: ForEachMut<N, PointN<N>, Data = T>,
 {
     type Data = T;
 
     fn for_each_mut(&mut self, extent: &ExtentN<N>, mut f: impl FnMut(PointN<N>, &mut Self::Data)) {
-        let ChunkMap {
-            chunk_shape,
-            ambient_value,
-            default_chunk_metadata,
-            chunks,
-            ..
-        } = self;
-
-        for chunk_key in chunk_key_iter(*chunk_shape, extent) {
-            let chunk = chunks.get_or_insert_with(chunk_key, || Chunk {
-                metadata: default_chunk_metadata.clone(),
-                map: ArrayN::fill(
-                    extent_for_chunk_at_key(chunk_shape, &chunk_key),
-                    *ambient_value,
-                ),
-            });
-            chunk.map.for_each_mut(extent, |p, value| f(p, value));
-        }
+        self.for_each_chunk_mut(extent, true, |_key, chunk| {
+            chunk.map.for_each_mut(extent, |p, value| f(p, value))
+        });
     }
 }
 
@@ -705,15 +1288,11 @@ where
     fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
         let chunk_iters = self
             .map
-            .key_iter(extent)
-            .map(|key| {
-                let chunk_extent = self.map.extent_for_chunk_at_key(&key);
-                let intersection = extent.intersection(&chunk_extent);
-
+            .chunks_in_extent(extent, self.local_cache)
+            .map(|(_key, clipped_extent, chunk)| {
                 (
-                    intersection,
-                    self.map
-                        .get_chunk(key, &self.local_cache)
+                    clipped_extent,
+                    chunk
                         .map(|chunk| Either::Left(ArrayCopySrc(&chunk.map)))
                         .unwrap_or_else(|| {
                             Either::Right(AmbientExtent::new(self.map.ambient_value))
@@ -748,18 +1327,30 @@ where
             chunk_shape,
             ambient_value,
             default_chunk_metadata,
+            dirty_chunks,
+            unsaved_chunks,
+            events,
             chunks,
             ..
         } = self;
 
         for chunk_key in chunk_key_iter(*chunk_shape, extent) {
-            let chunk = chunks.get_or_insert_with(chunk_key, || Chunk {
-                metadata: default_chunk_metadata.clone(),
-                map: ArrayN::fill(
-                    extent_for_chunk_at_key(chunk_shape, &chunk_key),
-                    *ambient_value,
-                ),
+            let mut inserted = false;
+            let chunk = chunks.get_or_insert_with(chunk_key, || {
+                inserted = true;
+                Chunk {
+                    metadata: default_chunk_metadata.clone(),
+                    map: ArrayN::fill(
+                        extent_for_chunk_at_key(chunk_shape, &chunk_key),
+                        *ambient_value,
+                    ),
+                }
             });
+            dirty_chunks.insert(chunk_key);
+            unsaved_chunks.insert(chunk_key);
+            if let Some(events) = events {
+                events.accessed(chunk_key, inserted);
+            }
             chunk.map.write_extent(extent, src);
         }
     }
@@ -867,4 +1458,239 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn fill_extent_with_ambient_removes_covered_chunks() {
+        let chunk_shape = PointN([16; 3]);
+        let ambient_value = 0;
+        let mut map = ChunkMap3::new(chunk_shape, ambient_value, (), FastLz4 { level: 10 });
+
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([32; 3]));
+        map.fill_extent(&extent, 1);
+        assert_eq!(map.chunk_keys().count(), 8);
+
+        // Only covers 4 chunks entirely, and the other 4 partially.
+        let erase_extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([32, 32, 20]));
+        map.compress_lru_chunk();
+        map.fill_extent(&erase_extent, ambient_value);
+        assert_eq!(map.chunk_keys().count(), 4);
+
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&map, &local_cache);
+        reader.for_each_ref(&extent, |p, value| {
+            assert_eq!(*value, if erase_extent.contains(&p) { 0 } else { 1 });
+        });
+    }
+
+    #[test]
+    fn remove_retain_and_prune_chunks() {
+        let chunk_shape = PointN([16; 3]);
+        let mut map = ChunkMap3::new(chunk_shape, 0, 0u8, FastLz4 { level: 10 });
+
+        for p in [[0, 0, 0], [16, 0, 0], [32, 0, 0], [48, 0, 0]].iter() {
+            *map.get_mut(&PointN(*p)) = 1;
+        }
+        // Make one chunk entirely ambient again.
+        *map.get_mut(&PointN([16, 0, 0])) = 0;
+        map.get_mut_chunk(PointN([32, 0, 0])).unwrap().metadata = 7;
+        map.compress_lru_chunk();
+
+        let removed = map.remove_chunk(PointN([0; 3])).unwrap();
+        assert_eq!(removed.map.get(&PointN([0; 3])), 1);
+        assert!(map.remove_chunk(PointN([0; 3])).is_none());
+
+        map.prune_ambient_chunks();
+        let mut keys: Vec<_> = map.chunk_keys().cloned().collect();
+        keys.sort_by_key(|k| k.x());
+        assert_eq!(keys, vec![PointN([32, 0, 0]), PointN([48, 0, 0])]);
+
+        map.retain(|_key, chunk| chunk.metadata == 7);
+        let keys: Vec<_> = map.chunk_keys().cloned().collect();
+        assert_eq!(keys, vec![PointN([32, 0, 0])]);
+    }
+
+    #[test]
+    fn fill_with_matches_dense_array() {
+        let chunk_shape = PointN([16; 3]);
+        let mut map = ChunkMap3::new(chunk_shape, 0, (), FastLz4 { level: 10 });
+
+        let extent = Extent3i::from_min_and_shape(PointN([-10; 3]), PointN([30; 3]));
+        let filler = |p: &Point3i| p.x() + 2 * p.y() + 3 * p.z();
+        map.fill_with(&extent, filler);
+        assert_eq!(map.chunk_keys().count(), 27);
+
+        let expected = Array3::fill_with(extent, filler);
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&map, &local_cache);
+        expected.for_each_ref(&extent, |p: Point3i, value| {
+            assert_eq!(reader.get(&p), *value);
+        });
+    }
+
+    #[test]
+    fn for_each_mut_existing_does_not_insert_chunks() {
+        let chunk_shape = PointN([16; 3]);
+        let mut map = ChunkMap3::new(chunk_shape, 0, (), FastLz4 { level: 10 });
+        *map.get_mut(&PointN([0; 3])) = 1;
+
+        let extent = Extent3i::from_min_and_shape(PointN([-16; 3]), PointN([48; 3]));
+        let mut num_visited = 0;
+        map.for_each_mut_existing(&extent, |_p: Point3i, value| {
+            *value += 1;
+            num_visited += 1;
+        });
+        assert_eq!(num_visited, 16 * 16 * 16);
+        assert_eq!(map.chunk_keys().count(), 1);
+        assert_eq!(*map.get_mut(&PointN([0; 3])), 2);
+        assert_eq!(*map.get_mut(&PointN([1; 3])), 1);
+    }
+
+    #[test]
+    fn non_power_of_2_chunk_shape() {
+        let chunk_shape = PointN([24; 3]);
+        let mut map = ChunkMap3::new(chunk_shape, 0, (), FastLz4 { level: 10 });
+        assert_eq!(map.chunk_shape_mask(), None);
+        assert_eq!(map.chunk_key(&PointN([23, 24, -1])), PointN([0, 24, -24]));
+        assert_eq!(
+            map.chunk_key(&PointN([-24, -25, 48])),
+            PointN([-24, -48, 48])
+        );
+
+        let extent = Extent3i::from_min_and_shape(PointN([-10; 3]), PointN([40; 3]));
+        let keys: Vec<_> = map.key_iter(&extent).collect();
+        assert_eq!(keys.len(), 27);
+        for key in keys.iter() {
+            assert!(
+                map.extent_for_chunk_at_key(key)
+                    .intersection(&extent)
+                    .num_points()
+                    > 0
+            );
+        }
+
+        let array = Array3::fill_with(extent, |p| p.x() + 2 * p.y() + 3 * p.z());
+        copy_extent(&extent, &array, &mut map);
+        assert_eq!(map.chunk_keys().count(), 27);
+
+        let map = ChunkMap3::from_serializable(
+            &map.to_serializable(BincodeLz4 { level: 10 }),
+            FastLz4 { level: 10 },
+        );
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&map, &local_cache);
+        array.for_each_ref(&extent, |p: Point3i, value| {
+            assert_eq!(reader.get(&p), *value);
+        });
+    }
+
+    #[test]
+    fn chunks_in_extent_gives_clipped_extents() {
+        let chunk_shape = PointN([4; 3]);
+        let mut map = ChunkMap3::new(chunk_shape, 0, (), FastLz4 { level: 10 });
+        *map.get_mut(&PointN([0; 3])) = 1;
+
+        let extent = Extent3i::from_min_and_shape(PointN([2, 0, 0]), PointN([4, 2, 2]));
+        let local_cache = LocalChunkCache::new();
+        let visited: Vec<_> = map
+            .chunks_in_extent(&extent, &local_cache)
+            .map(|(key, clipped, chunk)| (key, clipped, chunk.is_some()))
+            .collect();
+        assert_eq!(
+            visited,
+            vec![
+                (
+                    PointN([0; 3]),
+                    Extent3i::from_min_and_shape(PointN([2, 0, 0]), PointN([2; 3])),
+                    true
+                ),
+                (
+                    PointN([4, 0, 0]),
+                    Extent3i::from_min_and_shape(PointN([4, 0, 0]), PointN([2; 3])),
+                    false
+                ),
+            ]
+        );
+
+        let mut num_missing = 0;
+        map.for_each_chunk_in_extent_mut(&extent, |_key, clipped, chunk| match chunk {
+            Some(chunk) => chunk.map.for_each_mut(&clipped, |_p: Point3i, v| *v = 2),
+            None => num_missing += 1,
+        });
+        assert_eq!(num_missing, 1);
+        assert_eq!(map.chunk_keys().count(), 1);
+        assert_eq!(*map.get_mut(&PointN([0; 3])), 1);
+        assert_eq!(*map.get_mut(&PointN([2, 1, 1])), 2);
+        assert_eq!(*map.get_mut(&PointN([2, 2, 2])), 0);
+    }
+
+    #[test]
+    fn to_serializable_emits_chunks_in_hilbert_order() {
+        let chunk_shape = PointN([4; 3]);
+        let mut map = ChunkMap3::new(chunk_shape, 0, (), FastLz4 { level: 10 });
+        // The curve only stays inside of aligned, power-of-2 cubes of chunks.
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16; 3]));
+        map.fill_with(&extent, |p| p.x() + 100 * p.y() + 10_000 * p.z());
+
+        let keys = map.chunk_keys_in_hilbert_order();
+        assert_eq!(keys.len(), 64);
+        for pair in keys.windows(2) {
+            let d = (pair[1] - pair[0]) / chunk_shape;
+            assert_eq!(d.x().abs() + d.y().abs() + d.z().abs(), 1);
+        }
+
+        let serializable = map.to_serializable(BincodeLz4 { level: 10 });
+        let serialized_keys: Vec<_> = serializable.compressed_chunks.keys().cloned().collect();
+        assert_eq!(serialized_keys, keys);
+    }
+
+    #[test]
+    fn to_serializable_dedupes_identical_chunks() {
+        let chunk_shape = PointN([4; 3]);
+        let mut map = ChunkMap3::new(chunk_shape, 0, (), FastLz4 { level: 10 });
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16, 4, 4]));
+        map.fill_extent(&extent, 1);
+        *map.get_mut(&PointN([1; 3])) = 2;
+        // Compressed chunks are also deduplicated.
+        map.compress_lru_chunk();
+        map.compress_lru_chunk();
+
+        let serializable = map.to_serializable(BincodeLz4 { level: 10 });
+        assert_eq!(serializable.compressed_chunks.len(), 2);
+        assert_eq!(serializable.chunk_aliases.len(), 2);
+
+        let map = ChunkMap3::from_serializable(&serializable, FastLz4 { level: 10 });
+        assert_eq!(map.chunk_keys().count(), 4);
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&map, &local_cache);
+        for p in extent.iter_points() {
+            let expected = if p == PointN([1; 3]) { 2 } else { 1 };
+            assert_eq!(reader.get(&p), expected);
+        }
+    }
+
+    #[test]
+    fn load_bincode_from_before_chunk_aliases() {
+        let chunk_shape = PointN([4; 3]);
+        let mut map = ChunkMap3::new(chunk_shape, 0, (), FastLz4 { level: 10 });
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([8, 4, 4]));
+        map.fill_extent(&extent, 1);
+        let serializable = map.to_serializable(BincodeLz4 { level: 10 });
+
+        let old = SerializableChunkMapV0 {
+            chunk_shape: serializable.chunk_shape,
+            ambient_value: serializable.ambient_value,
+            default_chunk_metadata: serializable.default_chunk_metadata,
+            compressed_chunks: serializable.compressed_chunks.clone(),
+        };
+        let old_bytes = bincode::serialize(&old).unwrap();
+        assert!(bincode::deserialize::<SerializableChunkMap3<i32, ()>>(&old_bytes).is_err());
+        let loaded = SerializableChunkMap3::<i32, ()>::from_bincode(&old_bytes).unwrap();
+        assert_eq!(loaded.compressed_chunks.len(), 1);
+        assert!(loaded.chunk_aliases.is_empty());
+
+        let new_bytes = bincode::serialize(&serializable).unwrap();
+        let loaded = SerializableChunkMap3::<i32, ()>::from_bincode(&new_bytes).unwrap();
+        assert_eq!(loaded.compressed_chunks.len(), 1);
+        assert_eq!(loaded.chunk_aliases.len(), 1);
+    }
 }
@@ -0,0 +1,532 @@
+//! A versioned container format for saving a `ChunkMap` to bytes.
+//!
+//! Unlike `SerializableChunkMap`, which is just a serde struct, the container records enough about
+//! its contents to detect when a save no longer matches the types it's being loaded into, and to
+//! upgrade it if possible.
+//!
+//! # Layout
+//!
+//! | Bytes   | Contents                                                        |
+//! |---------|-----------------------------------------------------------------|
+//! | 0..4    | The magic number `b"BBCM"`                                      |
+//! | 4..8    | The format version, a little-endian `u32` (currently `1`)       |
+//! | 8..     | The bincode-encoded body, whose layout depends on the version   |
+//!
+//! In version 1, the body contains:
+//!   - a `ChunkMapFileHeader` with the `TypeTag`s of the voxel type `T` and metadata type `M`, and
+//!     the chunk shape
+//!   - the bincode-encoded ambient value and default chunk metadata
+//!   - a list of `ChunkRecord`s, each with the chunk key, a `ChunkCodec` tag, a 64-bit FNV-1a
+//!     checksum of the encoded bytes, and the encoded `Chunk` itself
+//!
+//! The header comes first so that it can be read with `read_chunk_map_header` without decoding any
+//! chunks.
+//!
+//! # Migrations
+//!
+//! If the `TypeTag`s in a save don't match those of `T` and `M`, then `ChunkMap::from_file_bytes`
+//! fails with `ChunkMapFileError::TypeMismatch`. To upgrade an old save, keep the old types around
+//! and load with `ChunkMap::from_file_bytes_with_migration`, which decodes the old types and
+//! converts each value.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{
+//!     prelude::*,
+//!     chunk_map_file::{read_chunk_map_header, TypeTagged},
+//! };
+//!
+//! let mut map = ChunkMap3::new(PointN([16; 3]), 0u8, (), FastLz4 { level: 10 });
+//! *map.get_mut(&PointN([1; 3])) = 200;
+//! let bytes = map.to_file_bytes(Some(BincodeLz4 { level: 10 }));
+//!
+//! let header = read_chunk_map_header::<[i32; 3]>(&bytes).unwrap();
+//! assert_eq!(header.voxel_type, u8::type_tag());
+//! assert_eq!(header.chunk_shape, PointN([16; 3]));
+//!
+//! // The voxel type changed from u8 to u16, so the save needs to be migrated.
+//! assert!(ChunkMap3::<u16, ()>::from_file_bytes(&bytes, FastLz4 { level: 10 }).is_err());
+//! let mut new_map = ChunkMap3::<u16, ()>::from_file_bytes_with_migration::<u8, ()>(
+//!     &bytes,
+//!     FastLz4 { level: 10 },
+//!     |v| 4 * v as u16,
+//!     |m| m,
+//! )
+//! .unwrap();
+//! assert_eq!(*new_map.get_mut(&PointN([1; 3])), 800);
+//! ```
+
+use crate::{chunk_map::ChunkShape, ArrayN, Chunk, ChunkMap, FastLz4};
+
+use building_blocks_core::prelude::*;
+
+use compressible_map::{BincodeLz4, Decompressible, MaybeCompressed};
+use core::hash::{Hash, Hasher};
+use fnv::FnvHasher;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::io::{Read, Write};
+
+/// The first 4 bytes of every chunk map file.
+pub const MAGIC: [u8; 4] = *b"BBCM";
+
+/// The version of the container layout written by this version of the crate.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Identifies the serialized representation of a type. The `version` should be bumped whenever the
+/// serialized representation changes.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TypeTag {
+    pub name: String,
+    pub version: u32,
+}
+
+/// A type whose serialized representation can be identified by a `TypeTag`. This must be
+/// implemented for the voxel and metadata types of any `ChunkMap` saved with `to_file_bytes`.
+pub trait TypeTagged {
+    fn type_tag() -> TypeTag;
+}
+
+macro_rules! impl_type_tagged {
+    ($($t:ty),+) => {
+        $(
+            impl TypeTagged for $t {
+                fn type_tag() -> TypeTag {
+                    TypeTag {
+                        name: stringify!($t).to_string(),
+                        version: 0,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_type_tagged!((), bool, u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+/// Everything about a chunk map file except for the data.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ChunkMapFileHeader<N> {
+    pub voxel_type: TypeTag,
+    pub metadata_type: TypeTag,
+    pub chunk_shape: PointN<N>,
+}
+
+/// How the bytes of a `ChunkRecord` are encoded.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ChunkCodec {
+    Bincode,
+    BincodeLz4,
+}
+
+/// One chunk in a chunk map file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChunkRecord<N> {
+    pub key: PointN<N>,
+    pub codec: ChunkCodec,
+    /// 64-bit FNV-1a hash of `bytes`.
+    pub checksum: u64,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FileBodyV1<N> {
+    header: ChunkMapFileHeader<N>,
+    ambient_value: Vec<u8>,
+    default_chunk_metadata: Vec<u8>,
+    chunks: Vec<ChunkRecord<N>>,
+}
+
+#[derive(Debug)]
+pub enum ChunkMapFileError {
+    /// The bytes don't start with `MAGIC`.
+    BadMagic,
+    /// The file was written by a newer version of this crate.
+    UnsupportedFormatVersion(u32),
+    /// The saved type doesn't match the type being loaded.
+    TypeMismatch {
+        expected: TypeTag,
+        found: TypeTag,
+    },
+    /// The chunk shape in the header isn't positive in every dimension.
+    InvalidChunkShape,
+    /// The chunk record at this index has a key or extent that doesn't line up with the chunk
+    /// shape.
+    MisalignedChunk {
+        chunk_index: usize,
+    },
+    /// The bytes of the chunk record at this index are corrupt.
+    ChecksumMismatch {
+        chunk_index: usize,
+    },
+    /// The chunk record at this index doesn't have one value for every point in its extent.
+    CorruptChunk {
+        chunk_index: usize,
+    },
+    Decode(bincode::Error),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ChunkMapFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a chunk map file"),
+            Self::UnsupportedFormatVersion(v) => write!(f, "unsupported format version {}", v),
+            Self::TypeMismatch { expected, found } => write!(
+                f,
+                "expected type {} v{}, found {} v{}",
+                expected.name, expected.version, found.name, found.version
+            ),
+            Self::InvalidChunkShape => write!(f, "invalid chunk shape"),
+            Self::MisalignedChunk { chunk_index } => {
+                write!(f, "chunk {} is not aligned to the chunk shape", chunk_index)
+            }
+            Self::ChecksumMismatch { chunk_index } => {
+                write!(f, "checksum mismatch in chunk {}", chunk_index)
+            }
+            Self::CorruptChunk { chunk_index } => {
+                write!(f, "chunk {} has the wrong number of values", chunk_index)
+            }
+            Self::Decode(e) => write!(f, "decode error: {}", e),
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ChunkMapFileError {}
+
+impl From<bincode::Error> for ChunkMapFileError {
+    fn from(e: bincode::Error) -> Self {
+        Self::Decode(e)
+    }
+}
+
+impl From<std::io::Error> for ChunkMapFileError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Reads only the format version and header of a chunk map file.
+pub fn read_chunk_map_header<N>(bytes: &[u8]) -> Result<ChunkMapFileHeader<N>, ChunkMapFileError>
+where
+    N: DeserializeOwned,
+{
+    let body = read_body_bytes(bytes)?;
+
+    // bincode allows trailing bytes, so we can stop after the header.
+    Ok(bincode::deserialize(body)?)
+}
+
+// Checks the magic number and format version, then returns the body.
+fn read_body_bytes(bytes: &[u8]) -> Result<&[u8], ChunkMapFileError> {
+    if bytes.len() < 8 || bytes[0..4] != MAGIC {
+        return Err(ChunkMapFileError::BadMagic);
+    }
+    let mut version_bytes = [0; 4];
+    version_bytes.copy_from_slice(&bytes[4..8]);
+    match u32::from_le_bytes(version_bytes) {
+        // Older versions should be upgraded to the latest body here.
+        FORMAT_VERSION => Ok(&bytes[8..]),
+        v => Err(ChunkMapFileError::UnsupportedFormatVersion(v)),
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(bytes);
+
+    hasher.finish()
+}
+
+fn check_type(expected: TypeTag, found: &TypeTag) -> Result<(), ChunkMapFileError> {
+    if expected != *found {
+        return Err(ChunkMapFileError::TypeMismatch {
+            expected,
+            found: found.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+pub(crate) fn encode_chunk<C: Serialize>(
+    chunk: &C,
+    compression: Option<BincodeLz4>,
+) -> Result<(ChunkCodec, Vec<u8>), ChunkMapFileError> {
+    let bytes = bincode::serialize(chunk)?;
+    match compression {
+        None => Ok((ChunkCodec::Bincode, bytes)),
+        Some(params) => {
+            let mut encoder = lz4::EncoderBuilder::new()
+                .level(params.level)
+                .build(Vec::new())?;
+            encoder.write_all(&bytes)?;
+            let (compressed, result) = encoder.finish();
+            result?;
+
+            Ok((ChunkCodec::BincodeLz4, compressed))
+        }
+    }
+}
+
+pub(crate) fn decode_chunk<C: DeserializeOwned>(
+    codec: ChunkCodec,
+    bytes: &[u8],
+) -> Result<C, ChunkMapFileError> {
+    match codec {
+        ChunkCodec::Bincode => Ok(bincode::deserialize(bytes)?),
+        ChunkCodec::BincodeLz4 => {
+            let mut decompressed = Vec::new();
+            lz4::Decoder::new(bytes)?.read_to_end(&mut decompressed)?;
+
+            Ok(bincode::deserialize(&decompressed)?)
+        }
+    }
+}
+
+impl<N, T, M> ChunkMap<N, T, M>
+where
+    N: DeserializeOwned + Serialize,
+    T: Copy + TypeTagged + DeserializeOwned + Serialize,
+    M: Clone + TypeTagged + DeserializeOwned + Serialize,
+    Chunk<N, T, M>: DeserializeOwned + Serialize,
+    PointN<N>: IntegerPoint + ChunkShape<N> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Writes this map in the versioned container format described in the `chunk_map_file`
+    /// module. Each chunk is LZ4-compressed if `compression` is given. Chunks are written in
+    /// Hilbert order, which limits 3D chunk coordinates as described in
+    /// `ChunkMap::chunk_keys_in_hilbert_order`.
+    pub fn to_file_bytes(&self, compression: Option<BincodeLz4>) -> Vec<u8>
+    where
+        PointN<N>: HilbertEncode,
+    {
+        let mut chunks: Vec<_> = self.chunks.iter_maybe_compressed().collect();
+        chunks.sort_by_cached_key(|(key, _chunk)| self.chunk_hilbert_index(key));
+        let chunks = chunks
+            .into_iter()
+            .map(|(key, chunk)| {
+                let (codec, bytes) = match chunk {
+                    MaybeCompressed::Compressed(compressed_chunk) => {
+                        encode_chunk(&compressed_chunk.decompress(), compression)
+                    }
+                    MaybeCompressed::Decompressed(chunk) => encode_chunk(chunk, compression),
+                }
+                .expect("Failed to encode chunk");
+
+                ChunkRecord {
+                    key: *key,
+                    codec,
+                    checksum: checksum(&bytes),
+                    bytes,
+                }
+            })
+            .collect();
+
+        let body = FileBodyV1 {
+            header: ChunkMapFileHeader {
+                voxel_type: T::type_tag(),
+                metadata_type: M::type_tag(),
+                chunk_shape: *self.chunk_shape(),
+            },
+            ambient_value: bincode::serialize(self.ambient_value()).unwrap(),
+            default_chunk_metadata: bincode::serialize(self.default_chunk_metadata()).unwrap(),
+            chunks,
+        };
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &body).unwrap();
+
+        bytes
+    }
+
+    /// Reads a map written by `to_file_bytes`. The saved voxel and metadata types must match `T`
+    /// and `M`.
+    pub fn from_file_bytes(bytes: &[u8], params: FastLz4) -> Result<Self, ChunkMapFileError> {
+        Self::from_file_bytes_with_migration::<T, M>(bytes, params, |v| v, |m| m)
+    }
+
+    /// Reads a map that was saved with the voxel type `OldT` and metadata type `OldM`, converting
+    /// every value with `migrate_value` and every chunk's metadata with `migrate_metadata`.
+    pub fn from_file_bytes_with_migration<OldT, OldM>(
+        bytes: &[u8],
+        params: FastLz4,
+        migrate_value: impl Fn(OldT) -> T,
+        migrate_metadata: impl Fn(OldM) -> M,
+    ) -> Result<Self, ChunkMapFileError>
+    where
+        OldT: TypeTagged + DeserializeOwned,
+        OldM: TypeTagged + DeserializeOwned,
+        Chunk<N, OldT, OldM>: DeserializeOwned,
+    {
+        let body: FileBodyV1<N> = bincode::deserialize(read_body_bytes(bytes)?)?;
+        check_type(OldT::type_tag(), &body.header.voxel_type)?;
+        check_type(OldM::type_tag(), &body.header.metadata_type)?;
+        let chunk_shape = body.header.chunk_shape;
+        if chunk_shape.meet(&PointN::ONES) != PointN::ONES {
+            return Err(ChunkMapFileError::InvalidChunkShape);
+        }
+
+        let ambient_value = migrate_value(bincode::deserialize(&body.ambient_value)?);
+        let default_chunk_metadata =
+            migrate_metadata(bincode::deserialize(&body.default_chunk_metadata)?);
+
+        let mut map = Self::new(chunk_shape, ambient_value, default_chunk_metadata, params);
+        for (chunk_index, record) in body.chunks.into_iter().enumerate() {
+            if checksum(&record.bytes) != record.checksum {
+                return Err(ChunkMapFileError::ChecksumMismatch { chunk_index });
+            }
+            let old_chunk: Chunk<N, OldT, OldM> = decode_chunk(record.codec, &record.bytes)?;
+            let (extent, values) = old_chunk.map.into_parts();
+            if (record.key / chunk_shape) * chunk_shape != record.key
+                || extent.minimum != record.key
+                || extent.shape != chunk_shape
+            {
+                return Err(ChunkMapFileError::MisalignedChunk { chunk_index });
+            }
+            if values.len() != extent.num_points() {
+                return Err(ChunkMapFileError::CorruptChunk { chunk_index });
+            }
+            let chunk = Chunk {
+                metadata: migrate_metadata(old_chunk.metadata),
+                map: ArrayN::new(extent, values.into_iter().map(&migrate_value).collect()),
+            };
+            map.chunks.insert(record.key, chunk);
+            map.chunks.compress_lru();
+        }
+
+        Ok(map)
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{copy_extent, Array3, ChunkMap3, ChunkMapReader3, Get, GetMut, LocalChunkCache};
+
+    #[test]
+    fn round_trip_with_and_without_compression() {
+        let extent = Extent3i::from_min_and_shape(PointN([-10; 3]), PointN([20; 3]));
+        let array = Array3::fill_with(extent, |p| p.x() + 2 * p.y() + 3 * p.z());
+        let mut map = ChunkMap3::new(PointN([8; 3]), -1, (), FastLz4 { level: 10 });
+        copy_extent(&extent, &array, &mut map);
+
+        for compression in [None, Some(BincodeLz4 { level: 10 })].iter() {
+            let bytes = map.to_file_bytes(*compression);
+            let loaded =
+                ChunkMap3::<i32, ()>::from_file_bytes(&bytes, FastLz4 { level: 10 }).unwrap();
+            assert_eq!(loaded.chunk_keys().count(), map.chunk_keys().count());
+
+            let local_cache = LocalChunkCache::new();
+            let reader = ChunkMapReader3::new(&loaded, &local_cache);
+            assert_eq!(reader.get(&PointN([100; 3])), -1);
+            for p in extent.iter_points() {
+                assert_eq!(reader.get(&p), array.get(&p));
+            }
+        }
+    }
+
+    #[test]
+    fn detects_bad_files() {
+        let mut map = ChunkMap3::new(PointN([8; 3]), 0u32, (), FastLz4 { level: 10 });
+        *map.get_mut(&PointN([0; 3])) = 1;
+        let bytes = map.to_file_bytes(None);
+
+        let load =
+            |bytes: &[u8]| ChunkMap3::<u32, ()>::from_file_bytes(bytes, FastLz4 { level: 10 });
+
+        assert!(matches!(load(b"nope"), Err(ChunkMapFileError::BadMagic)));
+
+        let mut future = bytes.clone();
+        future[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            load(&future),
+            Err(ChunkMapFileError::UnsupportedFormatVersion(2))
+        ));
+
+        assert!(matches!(
+            ChunkMap3::<i32, ()>::from_file_bytes(&bytes, FastLz4 { level: 10 }),
+            Err(ChunkMapFileError::TypeMismatch { .. })
+        ));
+
+        // Flip a bit in the last chunk's data.
+        let mut corrupt = bytes.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        assert!(matches!(
+            load(&corrupt),
+            Err(ChunkMapFileError::ChecksumMismatch { chunk_index: 0 })
+        ));
+
+        assert!(load(&bytes).is_ok());
+    }
+
+    #[test]
+    fn detects_bad_chunk_shapes_and_keys() {
+        let mut map = ChunkMap3::new(PointN([8; 3]), 0u32, (), FastLz4 { level: 10 });
+        *map.get_mut(&PointN([0; 3])) = 1;
+        let bytes = map.to_file_bytes(None);
+
+        let rewrite = |edit: &dyn Fn(&mut FileBodyV1<[i32; 3]>)| {
+            let mut body: FileBodyV1<[i32; 3]> = bincode::deserialize(&bytes[8..]).unwrap();
+            edit(&mut body);
+            let mut new_bytes = bytes[..8].to_vec();
+            bincode::serialize_into(&mut new_bytes, &body).unwrap();
+
+            ChunkMap3::<u32, ()>::from_file_bytes(&new_bytes, FastLz4 { level: 10 })
+        };
+
+        assert!(matches!(
+            rewrite(&|body| body.header.chunk_shape = PointN([8, 0, 8])),
+            Err(ChunkMapFileError::InvalidChunkShape)
+        ));
+        assert!(matches!(
+            rewrite(&|body| body.header.chunk_shape = PointN([8, -8, 8])),
+            Err(ChunkMapFileError::InvalidChunkShape)
+        ));
+        assert!(matches!(
+            rewrite(&|body| body.chunks[0].key = PointN([3, 0, 0])),
+            Err(ChunkMapFileError::MisalignedChunk { chunk_index: 0 })
+        ));
+        // The key is aligned, but the chunk's array is somewhere else.
+        assert!(matches!(
+            rewrite(&|body| body.chunks[0].key = PointN([8, 0, 0])),
+            Err(ChunkMapFileError::MisalignedChunk { chunk_index: 0 })
+        ));
+        // A different chunk shape doesn't match the shape of the saved chunks.
+        assert!(matches!(
+            rewrite(&|body| body.header.chunk_shape = PointN([4; 3])),
+            Err(ChunkMapFileError::MisalignedChunk { chunk_index: 0 })
+        ));
+        // The chunk's array is missing values, but the record is otherwise intact.
+        assert!(matches!(
+            rewrite(&|body| {
+                #[derive(Serialize)]
+                struct TruncatedArray {
+                    values: Vec<u32>,
+                    extent: Extent3i,
+                }
+                let array = TruncatedArray {
+                    values: vec![0; 10],
+                    extent: Extent3i::from_min_and_shape(PointN([0; 3]), PointN([8; 3])),
+                };
+                let (codec, bytes) = encode_chunk(&((), array), None).unwrap();
+                let record = &mut body.chunks[0];
+                record.codec = codec;
+                record.checksum = checksum(&bytes);
+                record.bytes = bytes;
+            }),
+            Err(ChunkMapFileError::CorruptChunk { chunk_index: 0 })
+        ));
+    }
+}
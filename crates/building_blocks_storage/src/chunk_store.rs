@@ -0,0 +1,623 @@
+//! Backing stores for `ChunkMap` chunks, so a map can page its chunks in and out of storage.
+//!
+//! A `ChunkStore` is a simple key-value store from chunk key to compressed chunk bytes. The chunks
+//! are encoded with bincode and compressed with LZ4. This crate provides a `MemoryChunkStore` and a
+//! `FileChunkStore`, which keeps one file per chunk in a directory.
+//!
+//! A `ChunkMap` can load missing chunks from a store when they are accessed with
+//! `get_mut_or_load`, or ahead of time with `load_extent`. To write changes back, `save_changes`
+//! writes every chunk that was inserted or modified since the last save in a single batch, and
+//! deletes every chunk that was erased, e.g. with `remove_chunk` or by filling a whole chunk with
+//! the ambient value. To page a chunk out of memory without erasing it, use `unload_chunk`.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, chunk_store::MemoryChunkStore};
+//!
+//! let mut store = MemoryChunkStore::default();
+//!
+//! let mut map = ChunkMap3::new(PointN([16; 3]), 0, (), FastLz4 { level: 10 });
+//! *map.get_mut(&PointN([1; 3])) = 1;
+//! map.save_changes(&mut store, BincodeLz4 { level: 10 }).unwrap();
+//!
+//! // Unloading doesn't erase the chunk from the store, so it's paged back in when accessed.
+//! map.unload_chunk(PointN([0; 3]));
+//! map.save_changes(&mut store, BincodeLz4 { level: 10 }).unwrap();
+//! assert_eq!(*map.get_mut_or_load(&store, &PointN([1; 3])).unwrap(), 1);
+//! ```
+//!
+//! # Async
+//!
+//! For stores with slow I/O, the `AsyncChunkStore` trait returns futures instead, so chunks can be
+//! fetched on any executor with `prefetch_chunks` and then handed to `ChunkMap::insert_prefetched`.
+//! `prefetch_chunks` polls all of its fetches concurrently.
+//!
+//! Stores that can actually overlap their I/O should implement `AsyncChunkStore` themselves. Any
+//! `ChunkStore + Sync` can be borrowed as an `AsyncChunkStore` with `BlockingAsync`, but its futures
+//! just do the blocking I/O of the `ChunkStore` when they're polled, one at a time.
+
+use crate::{
+    chunk_map::ChunkShape,
+    chunk_map_file::{decode_chunk, encode_chunk, ChunkCodec},
+    Chunk, ChunkMap, GetMut, LocalChunkCache,
+};
+
+use building_blocks_core::prelude::*;
+
+use compressible_map::BincodeLz4;
+use core::future::Future;
+use core::hash::Hash;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use fnv::{FnvHashMap, FnvHashSet};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// A key-value store of compressed chunks.
+pub trait ChunkStore<N> {
+    /// Returns the bytes of the chunk at `key`, if it's stored.
+    fn get(&self, key: &PointN<N>) -> io::Result<Option<Vec<u8>>>;
+
+    /// Stores the `bytes` of the chunk at `key`, replacing any old bytes.
+    fn put(&mut self, key: PointN<N>, bytes: Vec<u8>) -> io::Result<()>;
+
+    /// Removes the chunk at `key`, if it's stored.
+    fn delete(&mut self, key: &PointN<N>) -> io::Result<()>;
+
+    /// Returns the keys of all stored chunks.
+    fn list(&self) -> io::Result<Vec<PointN<N>>>;
+
+    /// Stores many chunks at once. Stores that can batch their writes should override this.
+    fn put_batch(&mut self, chunks: Vec<(PointN<N>, Vec<u8>)>) -> io::Result<()> {
+        for (key, bytes) in chunks.into_iter() {
+            self.put(key, bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A `ChunkStore` that just keeps the compressed chunks in memory.
+pub struct MemoryChunkStore<N> {
+    chunks: FnvHashMap<PointN<N>, Vec<u8>>,
+}
+
+impl<N> Default for MemoryChunkStore<N> {
+    fn default() -> Self {
+        Self {
+            chunks: FnvHashMap::default(),
+        }
+    }
+}
+
+impl<N> ChunkStore<N> for MemoryChunkStore<N>
+where
+    PointN<N>: Clone + Eq + Hash,
+{
+    fn get(&self, key: &PointN<N>) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.chunks.get(key).cloned())
+    }
+
+    fn put(&mut self, key: PointN<N>, bytes: Vec<u8>) -> io::Result<()> {
+        self.chunks.insert(key, bytes);
+
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &PointN<N>) -> io::Result<()> {
+        self.chunks.remove(key);
+
+        Ok(())
+    }
+
+    fn list(&self) -> io::Result<Vec<PointN<N>>> {
+        Ok(self.chunks.keys().cloned().collect())
+    }
+}
+
+/// A `ChunkStore` with one file per chunk in a directory. The file name is the hex-encoded bincode
+/// representation of the chunk key, with a ".chunk" extension. Files are written to a temporary
+/// path first and then renamed, so a chunk file is never partially written.
+pub struct FileChunkStore {
+    dir: PathBuf,
+}
+
+const CHUNK_FILE_EXTENSION: &str = "chunk";
+
+impl FileChunkStore {
+    /// Uses the directory at `dir`, creating it if it doesn't exist.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self { dir })
+    }
+
+    fn chunk_path<N>(&self, key: &PointN<N>) -> PathBuf
+    where
+        PointN<N>: Serialize,
+    {
+        let name: String = bincode::serialize(key)
+            .unwrap()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        self.dir.join(name).with_extension(CHUNK_FILE_EXTENSION)
+    }
+}
+
+fn key_from_file_name<N>(name: &str) -> Option<PointN<N>>
+where
+    PointN<N>: DeserializeOwned,
+{
+    // An odd-length name fails on the last byte.
+    let bytes = (0..name.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(name.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    bincode::deserialize(&bytes).ok()
+}
+
+impl<N> ChunkStore<N> for FileChunkStore
+where
+    PointN<N>: DeserializeOwned + Serialize,
+{
+    fn get(&self, key: &PointN<N>) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.chunk_path(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn put(&mut self, key: PointN<N>, bytes: Vec<u8>) -> io::Result<()> {
+        let path = self.chunk_path(&key);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, bytes)?;
+
+        fs::rename(temp_path, path)
+    }
+
+    fn delete(&mut self, key: &PointN<N>) -> io::Result<()> {
+        match fs::remove_file(self.chunk_path(key)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn list(&self) -> io::Result<Vec<PointN<N>>> {
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(CHUNK_FILE_EXTENSION) {
+                continue;
+            }
+            if let Some(key) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(key_from_file_name)
+            {
+                keys.push(key);
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+/// A boxed future returned by an `AsyncChunkStore`.
+pub type ChunkStoreFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+/// The read-only part of a `ChunkStore`, for stores whose I/O should happen on an executor.
+pub trait AsyncChunkStore<N> {
+    /// Returns the bytes of the chunk at `key`, if it's stored.
+    fn get_async(&self, key: PointN<N>) -> ChunkStoreFuture<'_, Option<Vec<u8>>>;
+
+    /// Returns the keys of all stored chunks.
+    fn list_async(&self) -> ChunkStoreFuture<'_, Vec<PointN<N>>>;
+}
+
+/// Borrows a blocking `ChunkStore` as an `AsyncChunkStore`. The futures do the blocking I/O when
+/// they're polled.
+pub struct BlockingAsync<'a, S: ?Sized>(pub &'a S);
+
+impl<'a, N, S> AsyncChunkStore<N> for BlockingAsync<'a, S>
+where
+    N: 'static,
+    S: ChunkStore<N> + Sync + ?Sized,
+    PointN<N>: Send,
+{
+    fn get_async(&self, key: PointN<N>) -> ChunkStoreFuture<'_, Option<Vec<u8>>> {
+        let store = self.0;
+
+        Box::pin(async move { store.get(&key) })
+    }
+
+    fn list_async(&self) -> ChunkStoreFuture<'_, Vec<PointN<N>>> {
+        let store = self.0;
+
+        Box::pin(async move { store.list() })
+    }
+}
+
+/// Compressed chunks that were fetched from a store, ready to be inserted into a `ChunkMap`.
+pub struct PrefetchedChunks<N> {
+    pub chunks: Vec<(PointN<N>, Vec<u8>)>,
+}
+
+/// Fetches the chunks at `keys` from `store`, all at once. Keys that aren't stored are skipped.
+pub async fn prefetch_chunks<N, S>(
+    store: &S,
+    keys: Vec<PointN<N>>,
+) -> io::Result<PrefetchedChunks<N>>
+where
+    S: AsyncChunkStore<N>,
+    PointN<N>: Clone,
+{
+    let fetches = keys
+        .iter()
+        .map(|key| store.get_async(key.clone()))
+        .collect();
+    let results = JoinAll::new(fetches).await;
+
+    let mut chunks = Vec::with_capacity(keys.len());
+    for (key, result) in keys.into_iter().zip(results) {
+        if let Some(bytes) = result? {
+            chunks.push((key, bytes));
+        }
+    }
+
+    Ok(PrefetchedChunks { chunks })
+}
+
+// Polls all of the futures until they're all ready, like `futures::future::join_all`.
+struct JoinAll<'a, T> {
+    futures: Vec<Option<ChunkStoreFuture<'a, T>>>,
+    results: Vec<Option<io::Result<T>>>,
+}
+
+impl<'a, T> JoinAll<'a, T> {
+    fn new(futures: Vec<ChunkStoreFuture<'a, T>>) -> Self {
+        let results = futures.iter().map(|_| None).collect();
+
+        Self {
+            futures: futures.into_iter().map(Some).collect(),
+            results,
+        }
+    }
+}
+
+impl<'a, T: Unpin> Future for JoinAll<'a, T> {
+    type Output = Vec<io::Result<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let JoinAll { futures, results } = &mut *self;
+        let mut all_ready = true;
+        for (future, result) in futures.iter_mut().zip(results.iter_mut()) {
+            if let Some(pending) = future {
+                match pending.as_mut().poll(cx) {
+                    Poll::Ready(output) => {
+                        *result = Some(output);
+                        *future = None;
+                    }
+                    Poll::Pending => all_ready = false,
+                }
+            }
+        }
+
+        if all_ready {
+            Poll::Ready(results.drain(..).map(Option::unwrap).collect())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+fn invalid_data(e: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl<N, T, M> ChunkMap<N, T, M>
+where
+    T: Copy,
+    M: Clone,
+    Chunk<N, T, M>: DeserializeOwned + Serialize,
+    PointN<N>: IntegerPoint + ChunkShape<N> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Loads the chunk at `key` from `store`, unless it's already in this map. Returns `true` if the
+    /// chunk is in the map afterwards. Loaded chunks are not dirty, and they don't cause any
+    /// `ChunkEvent`s.
+    ///
+    /// Checking whether the chunk is already in this map takes time linear in the number of chunks,
+    /// so prefer `load_extent` for loading many chunks.
+    pub fn load_chunk<S: ChunkStore<N>>(&mut self, store: &S, key: PointN<N>) -> io::Result<bool> {
+        if self.chunks.keys().any(|k| *k == key) {
+            return Ok(true);
+        }
+
+        self.load_missing_chunk(store, key)
+    }
+
+    /// Loads every chunk overlapping `extent` that is in `store` but not in this map.
+    pub fn load_extent<S: ChunkStore<N>>(
+        &mut self,
+        store: &S,
+        extent: &ExtentN<N>,
+    ) -> io::Result<()> {
+        let existing_keys: FnvHashSet<_> = self.chunks.keys().cloned().collect();
+        for key in self.key_iter(extent).collect::<Vec<_>>().into_iter() {
+            if !existing_keys.contains(&key) {
+                self.load_missing_chunk(store, key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like `get_mut`, but if the chunk containing `p` is missing, it's loaded from `store` before
+    /// falling back to inserting an ambient chunk.
+    pub fn get_mut_or_load<S: ChunkStore<N>>(
+        &mut self,
+        store: &S,
+        p: &PointN<N>,
+    ) -> io::Result<&mut T>
+    where
+        Self: for<'r> GetMut<&'r PointN<N>, Data = T>,
+    {
+        // The chunk is about to be used anyway, so there's no harm in decompressing it now.
+        let key = self.chunk_key(p);
+        if self.chunks.get_mut(key).is_some() {
+            self.chunk_touched(key);
+        } else {
+            self.load_missing_chunk(store, key)?;
+        }
+
+        Ok(self.get_mut(p))
+    }
+
+    /// Inserts chunks fetched with `prefetch_chunks`, skipping any that are already in this map.
+    pub fn insert_prefetched(&mut self, prefetched: PrefetchedChunks<N>) -> io::Result<()> {
+        let existing_keys: FnvHashSet<_> = self.chunks.keys().cloned().collect();
+        for (key, bytes) in prefetched.chunks.into_iter() {
+            if !existing_keys.contains(&key) {
+                self.insert_loaded_chunk(key, &bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes every chunk that was inserted or modified since the last save to `store` in one
+    /// batch, and deletes every chunk that was erased. Returns the number of chunks written.
+    ///
+    /// Chunks evicted with `unload_chunk` are not deleted, but any unsaved changes to them are
+    /// lost.
+    pub fn save_changes<S: ChunkStore<N>>(
+        &mut self,
+        store: &mut S,
+        params: BincodeLz4,
+    ) -> io::Result<usize> {
+        // Compressed chunks are decompressed into a local cache, so saving doesn't change which
+        // chunks are cached.
+        let local_cache = LocalChunkCache::new();
+        let mut batch = Vec::new();
+        for key in self.unsaved_chunk_keys() {
+            match self.get_chunk(*key, &local_cache) {
+                Some(chunk) => {
+                    let (_codec, bytes) =
+                        encode_chunk(chunk, Some(params)).map_err(invalid_data)?;
+                    batch.push((*key, bytes));
+                }
+                None => store.delete(key)?,
+            }
+        }
+        let num_written = batch.len();
+        store.put_batch(batch)?;
+        self.clear_unsaved_chunks();
+
+        Ok(num_written)
+    }
+
+    fn load_missing_chunk<S: ChunkStore<N>>(
+        &mut self,
+        store: &S,
+        key: PointN<N>,
+    ) -> io::Result<bool> {
+        match store.get(&key)? {
+            Some(bytes) => {
+                self.insert_loaded_chunk(key, &bytes)?;
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn insert_loaded_chunk(&mut self, key: PointN<N>, bytes: &[u8]) -> io::Result<()> {
+        let chunk = decode_chunk(ChunkCodec::BincodeLz4, bytes).map_err(invalid_data)?;
+        self.chunks.insert(key, chunk);
+        self.chunk_touched(key);
+
+        Ok(())
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{ChunkMap3, FastLz4};
+
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    // Enough of an executor for futures that are always ready.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    fn save_load_and_delete(store: &mut (impl ChunkStore<[i32; 3]> + Sync)) {
+        let params = BincodeLz4 { level: 10 };
+        let mut map = ChunkMap3::new(PointN([4; 3]), 0, (), FastLz4 { level: 10 });
+        map.record_events(true);
+        *map.get_mut(&PointN([0; 3])) = 1;
+        *map.get_mut(&PointN([-5; 3])) = 2;
+        *map.get_mut(&PointN([4, 0, 0])) = 3;
+        assert_eq!(map.save_changes(store, params).unwrap(), 3);
+        assert_eq!(map.save_changes(store, params).unwrap(), 0);
+        // Saving leaves the events for other consumers.
+        assert_eq!(map.drain_events().len(), 3);
+
+        // Unloading a chunk doesn't erase it, but filling it with the ambient value does.
+        map.unload_chunk(PointN([4, 0, 0]));
+        assert_eq!(map.save_changes(store, params).unwrap(), 0);
+        assert_eq!(store.list().unwrap().len(), 3);
+        let chunk_extent = map.extent_for_chunk_at_key(&PointN([4, 0, 0]));
+        map.load_extent(store, &chunk_extent).unwrap();
+        assert_eq!(*map.get_mut(&PointN([4, 0, 0])), 3);
+        map.fill_extent(&chunk_extent, 0);
+        assert_eq!(map.save_changes(store, params).unwrap(), 0);
+
+        let mut keys = store.list().unwrap();
+        keys.sort_by_key(|k| k.0);
+        assert_eq!(keys, vec![PointN([-8; 3]), PointN([0; 3])]);
+
+        let mut loaded = ChunkMap3::new(PointN([4; 3]), 0, (), FastLz4 { level: 10 });
+        assert_eq!(*loaded.get_mut_or_load(store, &PointN([0; 3])).unwrap(), 1);
+        assert_eq!(*loaded.get_mut_or_load(store, &PointN([-5; 3])).unwrap(), 2);
+        assert_eq!(*loaded.get_mut_or_load(store, &PointN([9; 3])).unwrap(), 0);
+
+        map.remove_chunk(PointN([0; 3]));
+        *map.get_mut(&PointN([-5; 3])) = 3;
+        assert_eq!(map.save_changes(store, params).unwrap(), 1);
+        assert_eq!(store.list().unwrap(), vec![PointN([-8; 3])]);
+
+        let mut loaded = ChunkMap3::new(PointN([4; 3]), 0, (), FastLz4 { level: 10 });
+        let prefetched = block_on(prefetch_chunks(
+            &BlockingAsync(&*store),
+            vec![PointN([-8; 3]), PointN([0; 3])],
+        ))
+        .unwrap();
+        assert_eq!(prefetched.chunks.len(), 1);
+        loaded.insert_prefetched(prefetched).unwrap();
+        assert_eq!(loaded.chunk_keys().count(), 1);
+        assert_eq!(*loaded.get_mut(&PointN([-5; 3])), 3);
+    }
+
+    // Each fetch is pending the first time it's polled.
+    struct SlowStore {
+        polls: std::sync::Mutex<Vec<(i32, bool)>>,
+    }
+
+    struct SlowFetch<'a> {
+        store: &'a SlowStore,
+        key: Point3i,
+        polled: bool,
+    }
+
+    impl<'a> Future for SlowFetch<'a> {
+        type Output = io::Result<Option<Vec<u8>>>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let ready = self.polled;
+            self.store.polls.lock().unwrap().push((self.key.x(), ready));
+            if ready {
+                Poll::Ready(Ok(Some(vec![self.key.x() as u8])))
+            } else {
+                self.polled = true;
+                cx.waker().wake_by_ref();
+
+                Poll::Pending
+            }
+        }
+    }
+
+    impl AsyncChunkStore<[i32; 3]> for SlowStore {
+        fn get_async(&self, key: Point3i) -> ChunkStoreFuture<'_, Option<Vec<u8>>> {
+            Box::pin(SlowFetch {
+                store: self,
+                key,
+                polled: false,
+            })
+        }
+
+        fn list_async(&self) -> ChunkStoreFuture<'_, Vec<Point3i>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+    }
+
+    // A store can be both blocking and async.
+    impl ChunkStore<[i32; 3]> for SlowStore {
+        fn get(&self, key: &Point3i) -> io::Result<Option<Vec<u8>>> {
+            Ok(Some(vec![key.x() as u8]))
+        }
+
+        fn put(&mut self, _key: Point3i, _bytes: Vec<u8>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn delete(&mut self, _key: &Point3i) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn list(&self) -> io::Result<Vec<Point3i>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn prefetch_fetches_concurrently() {
+        let store = SlowStore {
+            polls: Default::default(),
+        };
+        let keys = vec![PointN([1, 0, 0]), PointN([2, 0, 0])];
+        let prefetched = block_on(prefetch_chunks(&store, keys)).unwrap();
+        assert_eq!(
+            prefetched.chunks,
+            vec![(PointN([1, 0, 0]), vec![1]), (PointN([2, 0, 0]), vec![2])]
+        );
+        // Both fetches were started before either finished.
+        assert_eq!(
+            *store.polls.lock().unwrap(),
+            vec![(1, false), (2, false), (1, true), (2, true)]
+        );
+    }
+
+    #[test]
+    fn memory_store() {
+        save_load_and_delete(&mut MemoryChunkStore::default());
+    }
+
+    #[test]
+    fn file_store() {
+        let dir = std::env::temp_dir().join(format!("chunk_store_test_{}", std::process::id()));
+        let mut store = FileChunkStore::open(&dir).unwrap();
+        save_load_and_delete(&mut store);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+//! Per-chunk summaries of `ChunkMap` data, stored as chunk metadata.
+//!
+//! When the metadata type `M` of a `ChunkMap` implements `ChunkSummary`, the map can keep it up to
+//! date. Every chunk that is mutated through the `ChunkMap` API is marked dirty, and its summary is
+//! only recomputed when requested with `update_chunk_summary`, `update_chunk_summaries`, or
+//! `chunk_keys_with_summary`. Then queries like "which chunks contain a surface?" can skip most
+//! chunks without looking at their data.
+//!
+//! The provided summaries are `ValueRange`, `OccupiedCount`, and `ContentHash`, and tuples of
+//! summaries are also summaries.
+//!
+//! # Example Usage
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, chunk_summary::ValueRange};
+//!
+//! let chunk_shape = PointN([16; 3]);
+//! let mut map = ChunkMap3::new(chunk_shape, 0, ValueRange::default(), FastLz4 { level: 10 });
+//!
+//! *map.get_mut(&PointN([1; 3])) = 5;
+//! *map.get_mut(&PointN([20; 3])) = -2;
+//!
+//! map.update_chunk_summaries();
+//! let range = map.update_chunk_summary(PointN([0; 3])).unwrap();
+//! assert_eq!(range.min_max(), Some((0, 5)));
+//!
+//! let query_extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([32; 3]));
+//! let negative_chunks = map.chunk_keys_with_summary(&query_extent, |range| {
+//!     range.min_max().map(|(min, _)| min < 0).unwrap_or(false)
+//! });
+//! assert_eq!(negative_chunks, vec![PointN([16; 3])]);
+//! ```
+
+use crate::{ArrayN, IsEmpty};
+
+use core::hash::{Hash, Hasher};
+use fnv::FnvHasher;
+use serde::{Deserialize, Serialize};
+
+/// A summary of the data in one chunk, recomputed from the chunk's array after it changes.
+pub trait ChunkSummary<N, T> {
+    /// Recompute this summary from all of the values in `map`.
+    fn summarize(&mut self, map: &ArrayN<N, T>);
+
+    /// If the summary shows that every value in the chunk is the same, returns that value. This lets
+    /// reductions (see the `reduce` module) skip over uniform chunks.
+    fn uniform_value(&self) -> Option<&T> {
+        None
+    }
+}
+
+impl<N, T> ChunkSummary<N, T> for () {
+    fn summarize(&mut self, _map: &ArrayN<N, T>) {}
+}
+
+macro_rules! impl_tuple_summary {
+    ( $( $var:ident : $t:ident ),+ ) => {
+        impl<N, T, $($t),+> ChunkSummary<N, T> for ($($t,)+)
+        where
+            $($t: ChunkSummary<N, T>),+
+        {
+            fn summarize(&mut self, map: &ArrayN<N, T>) {
+                let ($($var,)+) = self;
+                $( $var.summarize(map); )+
+            }
+
+            fn uniform_value(&self) -> Option<&T> {
+                let ($($var,)+) = self;
+                None$(.or_else(|| $var.uniform_value()))+
+            }
+        }
+    };
+}
+
+impl_tuple_summary! { a: A, b: B }
+impl_tuple_summary! { a: A, b: B, c: C }
+impl_tuple_summary! { a: A, b: B, c: C, d: D }
+
+/// The minimum and maximum values in a chunk.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ValueRange<T> {
+    min_max: Option<(T, T)>,
+}
+
+impl<T: Copy> ValueRange<T> {
+    /// `None` only if the summary has never been computed.
+    pub fn min_max(&self) -> Option<(T, T)> {
+        self.min_max
+    }
+}
+
+impl<N, T> ChunkSummary<N, T> for ValueRange<T>
+where
+    T: Copy + PartialOrd,
+{
+    fn summarize(&mut self, map: &ArrayN<N, T>) {
+        self.min_max = map.values_slice().iter().fold(None, |min_max, v| {
+            Some(match min_max {
+                None => (*v, *v),
+                Some((min, max)) => (
+                    if *v < min { *v } else { min },
+                    if *v > max { *v } else { max },
+                ),
+            })
+        });
+    }
+
+    fn uniform_value(&self) -> Option<&T> {
+        self.min_max
+            .as_ref()
+            .filter(|(min, max)| min == max)
+            .map(|(min, _)| min)
+    }
+}
+
+/// The number of non-empty points in a chunk.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OccupiedCount(pub usize);
+
+impl<N, T> ChunkSummary<N, T> for OccupiedCount
+where
+    T: IsEmpty,
+{
+    fn summarize(&mut self, map: &ArrayN<N, T>) {
+        self.0 = map.values_slice().iter().filter(|v| !v.is_empty()).count();
+    }
+}
+
+/// A 64-bit hash of all of the values in a chunk. Comparing hashes is a cheap way to tell if a chunk
+/// changed, e.g. to skip re-uploading a mesh or to diff two saves, since the hash is serialized
+/// with the rest of the chunk metadata. Chunks with equal hashes very likely have equal values.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ContentHash {
+    hash: Option<u64>,
+}
+
+impl ContentHash {
+    /// `None` only if the summary has never been computed.
+    pub fn get(&self) -> Option<u64> {
+        self.hash
+    }
+}
+
+impl<N, T> ChunkSummary<N, T> for ContentHash
+where
+    T: Hash,
+{
+    fn summarize(&mut self, map: &ArrayN<N, T>) {
+        let mut hasher = FnvHasher::default();
+        map.values_slice().hash(&mut hasher);
+        self.hash = Some(hasher.finish());
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{ChunkMap3, FastLz4, GetMut, WriteExtent};
+
+    use building_blocks_core::{Extent3i, PointN};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Voxel(u8);
+
+    impl IsEmpty for Voxel {
+        fn is_empty(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    #[test]
+    fn summaries_are_recomputed_only_for_dirty_chunks() {
+        let chunk_shape = PointN([8; 3]);
+        let mut map = ChunkMap3::new(
+            chunk_shape,
+            Voxel(0),
+            OccupiedCount::default(),
+            FastLz4 { level: 10 },
+        );
+
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16, 8, 8]));
+        map.write_extent(&extent, |_p: &_| Voxel(1));
+        assert_eq!(map.dirty_chunk_keys().count(), 2);
+
+        map.update_chunk_summaries();
+        assert_eq!(map.dirty_chunk_keys().count(), 0);
+        assert_eq!(
+            map.update_chunk_summary(PointN([0; 3])),
+            Some(&OccupiedCount(512))
+        );
+
+        // Only the mutated chunk becomes dirty.
+        *map.get_mut(&PointN([9, 0, 0])) = Voxel(0);
+        let dirty: Vec<_> = map.dirty_chunk_keys().cloned().collect();
+        assert_eq!(dirty, vec![PointN([8, 0, 0])]);
+        assert_eq!(
+            map.update_chunk_summary(PointN([8, 0, 0])),
+            Some(&OccupiedCount(511))
+        );
+
+        let full_chunks = map.chunk_keys_with_summary(&extent, |count| count.0 == 512);
+        assert_eq!(full_chunks, vec![PointN([0; 3])]);
+    }
+
+    #[test]
+    fn content_hash_detects_changes() {
+        let chunk_shape = PointN([8; 3]);
+        let mut map = ChunkMap3::new(
+            chunk_shape,
+            0,
+            ContentHash::default(),
+            FastLz4 { level: 10 },
+        );
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16, 8, 8]));
+        map.write_extent(&extent, |_p: &_| 1);
+
+        let hash_at = |map: &mut ChunkMap3<i32, ContentHash>, key| {
+            map.update_chunk_summary(key).unwrap().get()
+        };
+        let before = hash_at(&mut map, PointN([0; 3]));
+        assert!(before.is_some());
+        assert_eq!(before, hash_at(&mut map, PointN([8, 0, 0])));
+
+        *map.get_mut(&PointN([1; 3])) = 2;
+        assert_ne!(before, hash_at(&mut map, PointN([0; 3])));
+        *map.get_mut(&PointN([1; 3])) = 1;
+        assert_eq!(before, hash_at(&mut map, PointN([0; 3])));
+    }
+}
@@ -0,0 +1,286 @@
+//! Image-processing style filters for arrays: convolution, blurs, morphology and gradients.
+//!
+//! All filters return a new array with the same extent as the input. Points near the boundary of
+//! the array see the nearest in-bounds value for any neighbor that would be out of bounds, i.e.
+//! `Boundary::Clamp`. Interior points use the `Stride` fast path of the `kernel` module.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, kernel::Kernel};
+//!
+//! let extent = Extent3i::from_min_and_shape(PointN([-8; 3]), PointN([16; 3]));
+//! let sdf = Array3::fill_with(extent, |p| (p.dot(p) as f32).sqrt() - 5.0);
+//!
+//! // Smooth out the SDF after an edit.
+//! let smooth_sdf = sdf.gaussian_blur(1.0);
+//!
+//! // Surface normals point along the gradient.
+//! let gradient = smooth_sdf.gradient();
+//! assert!(gradient.get(&PointN([5, 0, 0])).x() > 0.0);
+//!
+//! // Grow the solid region by one voxel in each axis direction.
+//! let solid = Array3::fill_with(extent, |p| sdf.get(p) < 0.0);
+//! let grown = solid.dilate(&Kernel::von_neumann());
+//! assert!(!solid.get(&PointN([5, 0, 0])));
+//! assert!(grown.get(&PointN([5, 0, 0])));
+//! ```
+
+use crate::{
+    access::{GetUncheckedMut, GetUncheckedMutRelease, GetUncheckedRef},
+    kernel::{Boundary, Kernel},
+    Array, Array2, Array3, ArrayN, GetMut, GetRef, IsEmpty, Stride,
+};
+
+use building_blocks_core::prelude::*;
+
+use num::Zero;
+
+impl<N> ArrayN<N, f32>
+where
+    Self: Array<N>
+        + Clone
+        + GetRef<Stride, Data = f32>
+        + GetUncheckedRef<Stride, Data = f32>
+        + GetMut<Stride, Data = f32>
+        + GetUncheckedMut<Stride, Data = f32>,
+    PointN<N>: IntegerPoint<Scalar = i32>,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Convolves this array with the 1-dimensional `weights` along `axis`. The weights are centered
+    /// on each point, so there should be an odd number of them.
+    pub fn convolve_axis(&self, axis: usize, weights: &[f32]) -> Self {
+        let radius = (weights.len() / 2) as i32;
+        let axis_vector = PointN::basis()[axis];
+        let kernel = Kernel::new(
+            (0..weights.len() as i32)
+                .map(|i| axis_vector * (i - radius))
+                .collect(),
+        );
+
+        let mut output = self.clone();
+        self.for_each_neighborhood_with_boundary(
+            &kernel,
+            self.extent(),
+            &Boundary::Clamp,
+            |_p, neighborhood| {
+                let sum = weights
+                    .iter()
+                    .zip(neighborhood.iter())
+                    .map(|(w, v)| w * v)
+                    .sum();
+                *output.get_unchecked_mut_release(neighborhood.center_stride()) = sum;
+            },
+        );
+
+        output
+    }
+
+    /// Convolves this array with the same 1-dimensional `weights` along every axis. This is
+    /// equivalent to convolving with the N-dimensional outer product of `weights`, but much faster.
+    pub fn convolve_separable(&self, weights: &[f32]) -> Self {
+        let num_axes = PointN::<N>::basis().len();
+        let mut output = self.convolve_axis(0, weights);
+        for axis in 1..num_axes {
+            output = output.convolve_axis(axis, weights);
+        }
+
+        output
+    }
+
+    /// Replaces each value with the average of the values in the cube of the given `radius`.
+    pub fn box_blur(&self, radius: u32) -> Self {
+        let width = 2 * radius as usize + 1;
+
+        self.convolve_separable(&vec![1.0 / width as f32; width])
+    }
+
+    /// Blurs with a Gaussian kernel of standard deviation `sigma`, truncated at 3 sigma. A `sigma`
+    /// that isn't positive leaves the values unchanged.
+    pub fn gaussian_blur(&self, sigma: f32) -> Self {
+        self.convolve_separable(&gaussian_weights(sigma))
+    }
+}
+
+/// Normalized weights of the 1-dimensional Gaussian with standard deviation `sigma`, sampled at
+/// integer offsets in `[-3 sigma, 3 sigma]`.
+///
+/// As `sigma` approaches 0, the Gaussian approaches an impulse, so any `sigma` that isn't positive
+/// gives the identity kernel `[1.0]`.
+pub fn gaussian_weights(sigma: f32) -> Vec<f32> {
+    if sigma.is_nan() || sigma <= 0.0 {
+        return vec![1.0];
+    }
+
+    let radius = (3.0 * sigma).ceil() as i32;
+    let mut weights: Vec<f32> = (-radius..=radius)
+        .map(|x| (-((x * x) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = weights.iter().sum();
+    for w in weights.iter_mut() {
+        *w /= sum;
+    }
+
+    weights
+}
+
+impl<N, T> ArrayN<N, T>
+where
+    T: Clone + IsEmpty,
+    Self: Array<N>
+        + Clone
+        + GetRef<Stride, Data = T>
+        + GetUncheckedRef<Stride, Data = T>
+        + GetMut<Stride, Data = T>
+        + GetUncheckedMut<Stride, Data = T>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Each empty point takes the value of the first non-empty neighbor in `kernel`, if any.
+    pub fn dilate(&self, kernel: &Kernel<N>) -> Self {
+        self.morphology(kernel, false)
+    }
+
+    /// Each non-empty point takes the value of the first empty neighbor in `kernel`, if any.
+    pub fn erode(&self, kernel: &Kernel<N>) -> Self {
+        self.morphology(kernel, true)
+    }
+
+    /// Erosion followed by dilation. Removes non-empty features smaller than the kernel.
+    pub fn open(&self, kernel: &Kernel<N>) -> Self {
+        self.erode(kernel).dilate(kernel)
+    }
+
+    /// Dilation followed by erosion. Fills empty holes smaller than the kernel.
+    pub fn close(&self, kernel: &Kernel<N>) -> Self {
+        self.dilate(kernel).erode(kernel)
+    }
+
+    // Replaces each point whose emptiness differs from `replace_empty` with the first neighbor
+    // that has the opposite emptiness.
+    fn morphology(&self, kernel: &Kernel<N>, replace_empty: bool) -> Self {
+        let mut output = self.clone();
+        self.for_each_neighborhood_with_boundary(
+            kernel,
+            self.extent(),
+            &Boundary::Clamp,
+            |_p, neighborhood| {
+                if neighborhood.center().is_empty() == replace_empty {
+                    return;
+                }
+                if let Some(v) = neighborhood.iter().find(|v| v.is_empty() == replace_empty) {
+                    *output.get_unchecked_mut_release(neighborhood.center_stride()) = v.clone();
+                }
+            },
+        );
+
+        output
+    }
+}
+
+macro_rules! impl_gradient {
+    ($array:ident, $int_point:ident, $float_point:ident, [$($axis:tt),+]) => {
+        impl $array<f32> {
+            /// Estimates the gradient at every point using central differences. On the boundary of
+            /// the array, the out-of-bounds neighbor is clamped, so the difference is one-sided (and
+            /// half as large).
+            pub fn gradient(&self) -> $array<$float_point> {
+                // Pairs of offsets in the negative and positive directions of each axis.
+                let kernel = Kernel::new(
+                    $int_point::basis()
+                        .into_iter()
+                        .flat_map(|b| vec![b * -1, b])
+                        .collect(),
+                );
+
+                let mut output = $array::fill(*self.extent(), $float_point::zero());
+                self.for_each_neighborhood_with_boundary(
+                    &kernel,
+                    self.extent(),
+                    &Boundary::Clamp,
+                    |_p, n| {
+                        *output.get_unchecked_mut_release(n.center_stride()) = PointN([$(
+                            0.5 * (n.get(2 * $axis + 1) - n.get(2 * $axis))
+                        ),+]);
+                    },
+                );
+
+                output
+            }
+        }
+    };
+}
+
+impl_gradient!(Array2, Point2i, Point2f, [0, 1]);
+impl_gradient!(Array3, Point3i, Point3f, [0, 1, 2]);
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{ForEachRef, Get};
+
+    #[test]
+    fn blurs_preserve_constants_and_mass() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([9; 3]));
+        let constant = Array3::fill(extent, 2.0);
+        constant
+            .box_blur(1)
+            .for_each_ref(&extent, |_s: Stride, v| assert!((v - 2.0).abs() < 1e-5));
+
+        let weights = gaussian_weights(1.0);
+        assert_eq!(weights.len(), 7);
+        assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert_eq!(weights[0], weights[6]);
+        assert_eq!(gaussian_weights(0.0), vec![1.0]);
+        assert_eq!(gaussian_weights(-1.0), vec![1.0]);
+
+        let mut impulse = Array3::fill(extent, 0.0);
+        *impulse.get_mut(&PointN([4; 3])) = 1.0;
+        let blurred = impulse.gaussian_blur(1.0);
+        let mut sum = 0.0;
+        blurred.for_each_ref(&extent, |_s: Stride, v| sum += v);
+        assert!((sum - 1.0).abs() < 1e-4);
+        assert!(blurred.get(&PointN([4; 3])) > blurred.get(&PointN([5, 4, 4])));
+    }
+
+    #[test]
+    fn morphology_of_a_single_point() {
+        let extent = Extent2i::from_min_and_shape(PointN([0; 2]), PointN([5; 2]));
+        let mut mask = Array2::fill(extent, false);
+        *mask.get_mut(&PointN([2, 2])) = true;
+
+        let kernel = Kernel::von_neumann();
+        let dilated = mask.dilate(&kernel);
+        let mut count = 0;
+        dilated.for_each_ref(&extent, |_s: Stride, v| count += *v as usize);
+        assert_eq!(count, 5);
+
+        assert_eq!(dilated.erode(&kernel), mask);
+        assert_eq!(mask.open(&kernel), Array2::fill(extent, false));
+
+        let mut holey = Array2::fill(extent, true);
+        *holey.get_mut(&PointN([2, 2])) = false;
+        assert_eq!(holey.close(&kernel), Array2::fill(extent, true));
+    }
+
+    #[test]
+    fn gradient_of_linear_function() {
+        let extent = Extent3i::from_min_and_shape(PointN([-4; 3]), PointN([8; 3]));
+        let f = Array3::fill_with(extent, |p| (2 * p.x() + 3 * p.y() - p.z()) as f32);
+        let gradient = f.gradient();
+        assert_eq!(gradient.get(&PointN([0; 3])), PointN([2.0, 3.0, -1.0]));
+        // One-sided difference on the boundary.
+        assert_eq!(gradient.get(&PointN([-4, 0, 0])), PointN([1.0, 3.0, -1.0]));
+
+        let extent = Extent2i::from_min_and_shape(PointN([0; 2]), PointN([4; 2]));
+        let f = Array2::fill_with(extent, |p| (p.x() * p.x()) as f32);
+        assert_eq!(f.gradient().get(&PointN([2, 1])), PointN([4.0, 0.0]));
+    }
+}
@@ -0,0 +1,476 @@
+//! A lattice map for extremely sparse data, stored in a hash map.
+//!
+//! `ChunkMap` allocates a whole chunk when a single point is written. When only a handful of points
+//! are ever written, like markers, entity positions or light sources, a `HashMapN` is much smaller.
+//! Any point that isn't stored takes the ambient value.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, HashMap3};
+//!
+//! let mut lights = HashMap3::new(0u8);
+//! *lights.get_mut(&PointN([1, 2, 3])) = 15;
+//! lights.insert(PointN([-100, 0, 100]), 7);
+//!
+//! assert_eq!(lights.get(&PointN([1, 2, 3])), 15);
+//! assert_eq!(lights.get(&PointN([0; 3])), 0);
+//! assert_eq!(lights.len(), 2);
+//!
+//! // Copy into a dense array. Points that aren't stored are written with the ambient value.
+//! let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([8; 3]));
+//! let mut dense = Array3::fill(extent, 1);
+//! copy_extent(&extent, &lights, &mut dense);
+//! assert_eq!(dense.get(&PointN([1, 2, 3])), 15);
+//! assert_eq!(dense.get(&PointN([0; 3])), 0);
+//!
+//! // And back again. Writing the ambient value removes a point.
+//! *dense.get_mut(&PointN([1, 2, 3])) = 0;
+//! *dense.get_mut(&PointN([4, 4, 4])) = 9;
+//! copy_extent(&extent, &dense, &mut lights);
+//! assert_eq!(lights.len(), 2);
+//! assert_eq!(lights.get(&PointN([4, 4, 4])), 9);
+//! ```
+//!
+//! Copying a `HashMapN` overwrites the whole destination extent, so the ambient value is written at
+//! every point that isn't stored. For a `ChunkMap` destination, that inserts a chunk for every chunk
+//! key in the extent. To only touch the chunks containing stored points, write the stored points
+//! one at a time instead:
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::{prelude::*, HashMap3};
+//! # let mut lights = HashMap3::new(0u8);
+//! # lights.insert(PointN([1, 2, 3]), 15);
+//! let mut map = ChunkMap3::new(PointN([16; 3]), 0, (), FastLz4 { level: 10 });
+//! for (p, value) in lights.iter() {
+//!     *map.get_mut(p) = *value;
+//! }
+//! assert_eq!(map.chunk_keys().count(), 1);
+//! ```
+
+use crate::{
+    access::{GetUnchecked, GetUncheckedRelease},
+    array::ArrayCopySrc,
+    chunk_map::{AmbientExtent, ChunkCopySrc, ChunkCopySrcIter},
+    Array, ArrayExtent, ArrayN, ForEachRef, Get, GetMut, GetRef, ReadExtent, Stride, WriteExtent,
+};
+
+use building_blocks_core::{bounding_extent, prelude::*};
+
+use core::hash::Hash;
+use core::iter::once;
+use core::ops::Deref;
+use either::Either;
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+
+/// A map from lattice location `PointN<N>` to data `T`, where only the points that have been
+/// written are stored. Every other point has the ambient value.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HashMapN<N, T>
+where
+    PointN<N>: Eq + Hash,
+{
+    points: FnvHashMap<PointN<N>, T>,
+    ambient_value: T,
+}
+
+pub type HashMap2<T> = HashMapN<[i32; 2], T>;
+pub type HashMap3<T> = HashMapN<[i32; 3], T>;
+
+impl<N, T> HashMapN<N, T>
+where
+    PointN<N>: Eq + Hash,
+{
+    /// Creates an empty map where every point has the `ambient_value`.
+    pub fn new(ambient_value: T) -> Self {
+        Self {
+            points: FnvHashMap::default(),
+            ambient_value,
+        }
+    }
+
+    pub fn ambient_value(&self) -> &T {
+        &self.ambient_value
+    }
+
+    /// The number of stored points.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns `true` iff no points are stored.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Stores `value` at `p`, returning the old value if `p` was already stored.
+    pub fn insert(&mut self, p: PointN<N>, value: T) -> Option<T> {
+        self.points.insert(p, value)
+    }
+
+    /// Removes `p` so that it takes the ambient value again.
+    pub fn remove(&mut self, p: &PointN<N>) -> Option<T> {
+        self.points.remove(p)
+    }
+
+    /// Removes every stored point.
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Iterates over the stored points in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&PointN<N>, &T)> {
+        self.points.iter()
+    }
+
+    /// The smallest extent that bounds all stored points.
+    pub fn bounding_extent(&self) -> ExtentN<N>
+    where
+        PointN<N>: IntegerPoint,
+        ExtentN<N>: IntegerExtent<N>,
+    {
+        bounding_extent(self.points.keys().cloned())
+    }
+
+    // Either writes `value` at `p` or removes `p` if `value` is ambient, so that copying dense data
+    // into the map doesn't store the ambient points.
+    fn store(&mut self, p: PointN<N>, value: T)
+    where
+        T: PartialEq,
+    {
+        if value == self.ambient_value {
+            self.points.remove(&p);
+        } else {
+            self.points.insert(p, value);
+        }
+    }
+}
+
+//  ██████╗ ███████╗████████╗████████╗███████╗██████╗ ███████╗
+// ██╔════╝ ██╔════╝╚══██╔══╝╚══██╔══╝██╔════╝██╔══██╗██╔════╝
+// ██║  ███╗█████╗     ██║      ██║   █████╗  ██████╔╝███████╗
+// ██║   ██║██╔══╝     ██║      ██║   ██╔══╝  ██╔══██╗╚════██║
+// ╚██████╔╝███████╗   ██║      ██║   ███████╗██║  ██║███████║
+//  ╚═════╝ ╚══════╝   ╚═╝      ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
+
+impl<N, T> Get<&PointN<N>> for HashMapN<N, T>
+where
+    T: Clone,
+    PointN<N>: Eq + Hash,
+{
+    type Data = T;
+
+    fn get(&self, p: &PointN<N>) -> Self::Data {
+        self.get_ref(p).clone()
+    }
+}
+
+impl<N, T> GetRef<&PointN<N>> for HashMapN<N, T>
+where
+    PointN<N>: Eq + Hash,
+{
+    type Data = T;
+
+    fn get_ref(&self, p: &PointN<N>) -> &Self::Data {
+        self.points.get(p).unwrap_or(&self.ambient_value)
+    }
+}
+
+impl<N, T> GetMut<&PointN<N>> for HashMapN<N, T>
+where
+    T: Clone,
+    PointN<N>: Copy + Eq + Hash,
+{
+    type Data = T;
+
+    /// Stores the ambient value at `p` first if `p` isn't already stored.
+    fn get_mut(&mut self, p: &PointN<N>) -> &mut Self::Data {
+        let HashMapN {
+            points,
+            ambient_value,
+        } = self;
+
+        points.entry(*p).or_insert_with(|| ambient_value.clone())
+    }
+}
+
+// ███████╗ ██████╗ ██████╗     ███████╗ █████╗  ██████╗██╗  ██╗
+// ██╔════╝██╔═══██╗██╔══██╗    ██╔════╝██╔══██╗██╔════╝██║  ██║
+// █████╗  ██║   ██║██████╔╝    █████╗  ███████║██║     ███████║
+// ██╔══╝  ██║   ██║██╔══██╗    ██╔══╝  ██╔══██║██║     ██╔══██║
+// ██║     ╚██████╔╝██║  ██║    ███████╗██║  ██║╚██████╗██║  ██║
+// ╚═╝      ╚═════╝ ╚═╝  ╚═╝    ╚══════╝╚═╝  ╚═╝ ╚═════╝╚═╝  ╚═╝
+
+impl<N, T> ForEachRef<N, PointN<N>> for HashMapN<N, T>
+where
+    PointN<N>: IntegerPoint + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    /// Visits every point of `extent`, including the points that take the ambient value. Use
+    /// `iter` to visit only the stored points.
+    fn for_each_ref(&self, extent: &ExtentN<N>, mut f: impl FnMut(PointN<N>, &Self::Data)) {
+        for p in extent.iter_points() {
+            f(p, self.get_ref(&p));
+        }
+    }
+}
+
+//  ██████╗ ██████╗ ██████╗ ██╗   ██╗
+// ██╔════╝██╔═══██╗██╔══██╗╚██╗ ██╔╝
+// ██║     ██║   ██║██████╔╝ ╚████╔╝
+// ██║     ██║   ██║██╔═══╝   ╚██╔╝
+// ╚██████╗╚██████╔╝██║        ██║
+//  ╚═════╝ ╚═════╝ ╚═╝        ╚═╝
+
+/// A single point and its value, acting as a 1-point array. This lets a `HashMapN` be copied into
+/// any map that can be written from an `ArrayN`.
+#[derive(Clone, Copy)]
+pub struct PointValue<N, T> {
+    extent: ExtentN<N>,
+    value: T,
+}
+
+impl<N, T> PointValue<N, T>
+where
+    PointN<N>: IntegerPoint,
+{
+    pub fn new(p: PointN<N>, value: T) -> Self {
+        Self {
+            extent: ExtentN::from_min_and_shape(p, PointN::ONES),
+            value,
+        }
+    }
+}
+
+impl<N, T> Deref for ArrayCopySrc<PointValue<N, T>> {
+    type Target = PointValue<N, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<N, T> ArrayExtent<N> for PointValue<N, T> {
+    fn extent(&self) -> &ExtentN<N> {
+        &self.extent
+    }
+}
+
+impl<N, T> Array<N> for PointValue<N, T>
+where
+    ArrayN<N, T>: Array<N>,
+{
+    #[inline]
+    fn stride_from_point(shape: &PointN<N>, point: &PointN<N>) -> Stride {
+        ArrayN::<N, T>::stride_from_point(shape, point)
+    }
+
+    fn for_each_point_and_stride(
+        array_extent: &ExtentN<N>,
+        extent: &ExtentN<N>,
+        f: impl FnMut(PointN<N>, Stride),
+    ) {
+        ArrayN::<N, T>::for_each_point_and_stride(array_extent, extent, f)
+    }
+
+    fn for_each_stride_parallel(
+        iter_extent: &ExtentN<N>,
+        array1_extent: &ExtentN<N>,
+        array2_extent: &ExtentN<N>,
+        f: impl FnMut(Stride, Stride),
+    ) {
+        ArrayN::<N, T>::for_each_stride_parallel(iter_extent, array1_extent, array2_extent, f)
+    }
+}
+
+impl<N, T: Clone> Get<Stride> for PointValue<N, T> {
+    type Data = T;
+
+    #[inline]
+    fn get(&self, stride: Stride) -> Self::Data {
+        assert_eq!(stride.0, 0);
+
+        self.value.clone()
+    }
+}
+
+impl<N, T: Clone> GetUnchecked<Stride> for PointValue<N, T> {
+    type Data = T;
+
+    #[inline]
+    unsafe fn get_unchecked(&self, _stride: Stride) -> Self::Data {
+        self.value.clone()
+    }
+}
+
+pub type HashMapCopySrc<N, T> = ChunkCopySrc<PointValue<N, T>, N, T>;
+
+impl<'a, N: 'a, T> ReadExtent<'a, N> for HashMapN<N, T>
+where
+    T: 'a + Clone,
+    PointN<N>: IntegerPoint + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Src = HashMapCopySrc<N, T>;
+    type SrcIter = ChunkCopySrcIter<PointValue<N, T>, N, T>;
+
+    /// The whole `extent` is first filled with the ambient value, then each stored point in
+    /// `extent` is written on its own. Filling the extent is as expensive as for a dense source; a
+    /// `ChunkMap` destination gets a chunk for every chunk key in `extent`.
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        let ambient = (
+            *extent,
+            Either::Right(AmbientExtent::new(self.ambient_value.clone())),
+        );
+        let stored = self
+            .points
+            .iter()
+            .filter(|(p, _)| extent.contains(p))
+            .map(|(p, value)| {
+                let src = PointValue::new(*p, value.clone());
+
+                (src.extent, Either::Left(ArrayCopySrc(src)))
+            });
+
+        once(ambient).chain(stored).collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<N, T, M, Ms> WriteExtent<N, ArrayCopySrc<Ms>> for HashMapN<N, T>
+where
+    T: PartialEq,
+    ArrayCopySrc<Ms>: Deref<Target = M>,
+    M: Array<N> + GetUncheckedRelease<Stride, T>,
+    PointN<N>: IntegerPoint + Eq + Hash,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src_array: ArrayCopySrc<Ms>) {
+        let in_bounds_extent = extent.intersection(src_array.extent());
+        M::for_each_point_and_stride(src_array.extent(), &in_bounds_extent, |p, s| {
+            self.store(p, src_array.get_unchecked_release(s));
+        });
+    }
+}
+
+impl<M, N, T> WriteExtent<N, ChunkCopySrc<M, N, T>> for HashMapN<N, T>
+where
+    T: Clone + PartialEq,
+    Self: WriteExtent<N, ArrayCopySrc<M>>,
+    PointN<N>: IntegerPoint + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: ChunkCopySrc<M, N, T>) {
+        match src {
+            Either::Left(array) => self.write_extent(extent, array),
+            Either::Right(ambient) => {
+                if ambient.value == self.ambient_value {
+                    // Only visit the stored points, since the others are already ambient.
+                    self.points.retain(|p, _| !extent.contains(p));
+                } else {
+                    for p in extent.iter_points() {
+                        self.points.insert(p, ambient.get());
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<N, F, T> WriteExtent<N, F> for HashMapN<N, T>
+where
+    F: Fn(&PointN<N>) -> T,
+    T: PartialEq,
+    PointN<N>: IntegerPoint + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: F) {
+        for p in extent.iter_points() {
+            self.store(p, (src)(&p));
+        }
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{copy_extent, Array3, ChunkMap3, ChunkMapReader3, FastLz4, LocalChunkCache};
+
+    #[test]
+    fn copy_to_and_from_chunk_map() {
+        let mut sparse = HashMap3::new(0);
+        sparse.insert(PointN([-20, 3, 5]), 1);
+        sparse.insert(PointN([30, -3, 0]), 2);
+        sparse.insert(PointN([1000; 3]), 3);
+
+        let extent = Extent3i::from_min_and_shape(PointN([-32; 3]), PointN([64; 3]));
+        let mut chunk_map = ChunkMap3::new(PointN([16; 3]), 0, (), FastLz4 { level: 10 });
+        copy_extent(&extent, &sparse, &mut chunk_map);
+
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&chunk_map, &local_cache);
+        assert_eq!(reader.get(&PointN([-20, 3, 5])), 1);
+        assert_eq!(reader.get(&PointN([30, -3, 0])), 2);
+        assert_eq!(reader.get(&PointN([0; 3])), 0);
+
+        // Only the in-bounds, non-ambient points come back.
+        let mut other_sparse = HashMap3::new(0);
+        copy_extent(&extent, &reader, &mut other_sparse);
+        assert_eq!(other_sparse.len(), 2);
+        assert_eq!(other_sparse.get(&PointN([30, -3, 0])), 2);
+        assert_eq!(other_sparse.get(&PointN([1000; 3])), 0);
+    }
+
+    #[test]
+    fn copy_sparse_map_fills_every_chunk_in_extent() {
+        let mut sparse = HashMap3::new(0);
+        sparse.insert(PointN([-20, 3, 5]), 1);
+        sparse.insert(PointN([30, -3, 0]), 2);
+
+        // The ambient fill covers all 4x4x4 chunks of the extent, not just the 2 occupied ones.
+        let extent = Extent3i::from_min_and_shape(PointN([-32; 3]), PointN([64; 3]));
+        let mut chunk_map = ChunkMap3::new(PointN([16; 3]), 0, (), FastLz4 { level: 10 });
+        copy_extent(&extent, &sparse, &mut chunk_map);
+        assert_eq!(chunk_map.chunk_keys().count(), 64);
+
+        let mut chunk_map = ChunkMap3::new(PointN([16; 3]), 0, (), FastLz4 { level: 10 });
+        for (p, value) in sparse.iter() {
+            *chunk_map.get_mut(p) = *value;
+        }
+        assert_eq!(chunk_map.chunk_keys().count(), 2);
+    }
+
+    #[test]
+    fn copy_between_hash_maps_overwrites_extent() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([4; 3]));
+        let mut src = HashMap3::new(0);
+        src.insert(PointN([1; 3]), 5);
+
+        let mut dst = HashMap3::new(0);
+        dst.insert(PointN([2; 3]), 6);
+        dst.insert(PointN([10; 3]), 7);
+        copy_extent(&extent, &src, &mut dst);
+
+        let mut stored: Vec<_> = dst.iter().map(|(p, v)| (*p, *v)).collect();
+        stored.sort_by_key(|(p, _)| p.x());
+        assert_eq!(stored, vec![(PointN([1; 3]), 5), (PointN([10; 3]), 7)]);
+        assert_eq!(
+            dst.bounding_extent(),
+            Extent3i::from_min_and_max(PointN([1; 3]), PointN([10; 3]))
+        );
+
+        let mut dense = Array3::fill(extent, 9);
+        copy_extent(&extent, &dst, &mut dense);
+        dense.for_each_ref(&extent, |p: Point3i, value| {
+            assert_eq!(*value, if p == PointN([1; 3]) { 5 } else { 0 });
+        });
+    }
+}
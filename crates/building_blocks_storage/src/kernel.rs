@@ -0,0 +1,380 @@
+//! Neighborhood kernels for iterating over arrays.
+//!
+//! A `Kernel` is a set of offsets, like the Von Neumann or Moore neighborhoods. For each point in
+//! an extent, `ArrayN::for_each_neighborhood` gives a closure a `Neighborhood` accessor, where the
+//! `i`th neighbor is the value at `p + kernel.offsets()[i]`. Strides to the neighbors are computed
+//! once, so this is just as fast as doing the `Stride` arithmetic by hand.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, kernel::Kernel};
+//!
+//! let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16; 3]));
+//! let array = Array3::fill_with(extent, |p| if p.x() % 2 == 0 { 1 } else { 0 });
+//!
+//! // Sum up the values in the Von Neumann neighborhood of each point. Only points whose whole
+//! // neighborhood is in bounds of the array are visited.
+//! let kernel = Kernel::von_neumann();
+//! let mut sums = Array3::fill(extent, 0);
+//! array.for_each_neighborhood(&kernel, &extent, |p, neighborhood| {
+//!     *sums.get_mut(&p) = *neighborhood.center() + neighborhood.iter().sum::<i32>();
+//! });
+//! assert_eq!(sums.get(&PointN([2; 3])), 5);
+//! assert_eq!(sums.get(&PointN([0; 3])), 0);
+//! ```
+//!
+//! Points near the boundary of the array can also be visited by choosing a `Boundary` mode for the
+//! out-of-bounds neighbors:
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::{prelude::*, kernel::Kernel};
+//! # let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16; 3]));
+//! # let array = Array3::fill_with(extent, |p| if p.x() % 2 == 0 { 1 } else { 0 });
+//! use building_blocks_storage::kernel::Boundary;
+//!
+//! let kernel = Kernel::von_neumann();
+//! array.for_each_neighborhood_with_boundary(
+//!     &kernel, &extent, &Boundary::Ambient(0), |p, neighborhood| {
+//!         if p == PointN([0; 3]) {
+//!             // (-1, 0, 0) is out of bounds, so it takes the ambient value.
+//!             assert_eq!(neighborhood.get(0), &0);
+//!         }
+//!     }
+//! );
+//! ```
+
+use crate::{
+    access::{GetUncheckedRef, GetUncheckedRefRelease},
+    Array, ArrayN, GetRef, Stride,
+};
+
+use building_blocks_core::prelude::*;
+
+use num::Zero;
+
+/// A set of offsets that defines the neighborhood of a point.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Kernel<N> {
+    offsets: Vec<PointN<N>>,
+    // Bounds on all offsets, including the zero offset of the center point.
+    min_offset: PointN<N>,
+    max_offset: PointN<N>,
+}
+
+impl<N> Kernel<N>
+where
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    pub fn new(offsets: Vec<PointN<N>>) -> Self {
+        let zero = PointN::zero();
+        let (min_offset, max_offset) = offsets
+            .iter()
+            .fold((zero, zero), |(min, max), o| (min.meet(o), max.join(o)));
+
+        Self {
+            offsets,
+            min_offset,
+            max_offset,
+        }
+    }
+
+    /// The nearest neighbors along each axis, excluding the center.
+    pub fn von_neumann() -> Self {
+        Self::new(PointN::von_neumann_offsets())
+    }
+
+    /// All neighbors in the unit cube around the center, excluding the center.
+    pub fn moore() -> Self {
+        Self::new(PointN::moore_offsets())
+    }
+
+    /// All offsets in the cube `[-radius, radius]^N`, including the center.
+    pub fn cube(radius: i32) -> Self
+    where
+        PointN<N>: Point<Scalar = i32>,
+    {
+        let max = PointN::ONES * radius;
+
+        Self::new(
+            ExtentN::from_min_and_max(max * -1, max)
+                .iter_points()
+                .collect(),
+        )
+    }
+
+    /// All offsets within Euclidean distance `radius` of the center, including the center.
+    pub fn ball(radius: i32) -> Self
+    where
+        PointN<N>: Point<Scalar = i32> + DotProduct<Scalar = i32>,
+    {
+        let max = PointN::ONES * radius;
+
+        Self::new(
+            ExtentN::from_min_and_max(max * -1, max)
+                .iter_points()
+                .filter(|o| o.dot(o) <= radius * radius)
+                .collect(),
+        )
+    }
+
+    pub fn offsets(&self) -> &[PointN<N>] {
+        &self.offsets
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// The subset of `extent` containing the points whose entire neighborhood is also in `extent`.
+    pub fn interior_extent(&self, extent: &ExtentN<N>) -> ExtentN<N> {
+        let minimum = extent.minimum - self.min_offset;
+        let lub = extent.least_upper_bound() - self.max_offset;
+
+        ExtentN::from_min_and_shape(minimum, (lub - minimum).join(&PointN::zero()))
+    }
+}
+
+/// How to find the value of a neighbor that lies outside of the array.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Boundary<T> {
+    /// Use the value at the closest point in the array.
+    Clamp,
+    /// Use the value at the point found by wrapping around the array, like a torus.
+    Wrap,
+    /// Use the given value.
+    Ambient(T),
+}
+
+enum NeighborStrides<'a, T> {
+    // Offsets from the center stride, valid when the whole neighborhood is in bounds.
+    Relative(&'a [Stride]),
+    // Absolute strides, with `None` for neighbors that take the ambient value.
+    Resolved(&'a [Option<Stride>], Option<&'a T>),
+}
+
+/// The values in the neighborhood of one point, indexed the same as the `Kernel` offsets.
+pub struct Neighborhood<'a, N, T> {
+    array: &'a ArrayN<N, T>,
+    center: Stride,
+    strides: NeighborStrides<'a, T>,
+}
+
+impl<'a, N, T> Neighborhood<'a, N, T>
+where
+    ArrayN<N, T>: GetUncheckedRefRelease<Stride, T>,
+{
+    /// The value at the center of the neighborhood.
+    pub fn center(&self) -> &'a T {
+        self.array.get_unchecked_ref_release(self.center)
+    }
+
+    /// The `Stride` of the center point. This is also valid for any other array with the same
+    /// extent.
+    pub fn center_stride(&self) -> Stride {
+        self.center
+    }
+
+    /// The value at the `i`th kernel offset from the center.
+    pub fn get(&self, i: usize) -> &'a T {
+        match self.strides {
+            NeighborStrides::Relative(offsets) => self
+                .array
+                .get_unchecked_ref_release(self.center + offsets[i]),
+            NeighborStrides::Resolved(strides, ambient) => match strides[i] {
+                Some(s) => self.array.get_unchecked_ref_release(s),
+                None => ambient.unwrap(),
+            },
+        }
+    }
+
+    /// The number of neighbors.
+    pub fn len(&self) -> usize {
+        match self.strides {
+            NeighborStrides::Relative(offsets) => offsets.len(),
+            NeighborStrides::Resolved(strides, _) => strides.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the neighbor values in kernel order.
+    pub fn iter(&self) -> impl '_ + Iterator<Item = &'a T> {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+}
+
+impl<N, T> ArrayN<N, T>
+where
+    Self: Array<N> + GetRef<Stride, Data = T> + GetUncheckedRef<Stride, Data = T>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Calls `f` with the neighborhood of every point `p` in `extent` such that all of the
+    /// neighbors of `p` are in bounds of this array. Other points are skipped.
+    pub fn for_each_neighborhood(
+        &self,
+        kernel: &Kernel<N>,
+        extent: &ExtentN<N>,
+        mut f: impl FnMut(PointN<N>, &Neighborhood<N, T>),
+    ) {
+        let mut offset_strides = vec![Stride(0); kernel.len()];
+        self.strides_from_points(kernel.offsets(), &mut offset_strides);
+
+        let iter_extent = kernel.interior_extent(self.extent()).intersection(extent);
+        Self::for_each_point_and_stride(self.extent(), &iter_extent, |p, s| {
+            let neighborhood = Neighborhood {
+                array: self,
+                center: s,
+                strides: NeighborStrides::Relative(&offset_strides),
+            };
+            f(p, &neighborhood)
+        });
+    }
+
+    /// Like `for_each_neighborhood`, but visits every point of `extent` that is in bounds of this
+    /// array. Out-of-bounds neighbors are found using the `boundary` mode. This is slower for points
+    /// near the boundary, but interior points are just as fast.
+    pub fn for_each_neighborhood_with_boundary(
+        &self,
+        kernel: &Kernel<N>,
+        extent: &ExtentN<N>,
+        boundary: &Boundary<T>,
+        mut f: impl FnMut(PointN<N>, &Neighborhood<N, T>),
+    ) {
+        let mut offset_strides = vec![Stride(0); kernel.len()];
+        self.strides_from_points(kernel.offsets(), &mut offset_strides);
+
+        let array_extent = *self.extent();
+        let array_max = array_extent.max();
+        let shape = array_extent.shape;
+        let interior = kernel.interior_extent(&array_extent);
+        let ambient = match boundary {
+            Boundary::Ambient(value) => Some(value),
+            _ => None,
+        };
+
+        let mut resolved_strides = vec![None; kernel.len()];
+        let iter_extent = array_extent.intersection(extent);
+        Self::for_each_point_and_stride(&array_extent, &iter_extent, |p, s| {
+            let strides = if interior.contains(&p) {
+                NeighborStrides::Relative(&offset_strides)
+            } else {
+                for (resolved, offset) in resolved_strides.iter_mut().zip(kernel.offsets().iter()) {
+                    let mut q = p + *offset;
+                    if !array_extent.contains(&q) {
+                        match boundary {
+                            Boundary::Clamp => q = q.join(&array_extent.minimum).meet(&array_max),
+                            Boundary::Wrap => {
+                                // Integer point division floors, so this remainder is always
+                                // in [0, shape).
+                                let d = q - array_extent.minimum;
+                                q = array_extent.minimum + d - (d / shape) * shape;
+                            }
+                            Boundary::Ambient(_) => {
+                                *resolved = None;
+                                continue;
+                            }
+                        }
+                    }
+                    *resolved = Some(Self::stride_from_point(&shape, &(q - array_extent.minimum)));
+                }
+
+                NeighborStrides::Resolved(&resolved_strides, ambient)
+            };
+            let neighborhood = Neighborhood {
+                array: self,
+                center: s,
+                strides,
+            };
+            f(p, &neighborhood)
+        });
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Array2, Array3, Get};
+
+    #[test]
+    fn interior_extent_shrinks_by_kernel_bounds() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([8; 3]));
+        let kernel = Kernel::new(vec![PointN([-2, 0, 0]), PointN([0, 1, 0])]);
+        assert_eq!(
+            kernel.interior_extent(&extent),
+            Extent3i::from_min_and_shape(PointN([2, 0, 0]), PointN([6, 7, 8]))
+        );
+        assert_eq!(Kernel::<[i32; 3]>::cube(1).len(), 27);
+        assert_eq!(Kernel::<[i32; 3]>::ball(1).len(), 7);
+
+        let tiny = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([1; 3]));
+        assert_eq!(Kernel::moore().interior_extent(&tiny).num_points(), 0);
+    }
+
+    #[test]
+    fn neighborhoods_match_point_lookups() {
+        let extent = Extent2i::from_min_and_shape(PointN([-3, 5]), PointN([6, 4]));
+        let array = Array2::fill_with(extent, |p| 10 * p.x() + p.y());
+        let kernel = Kernel::moore();
+
+        let mut num_visited = 0;
+        array.for_each_neighborhood(&kernel, &extent, |p, neighborhood| {
+            num_visited += 1;
+            assert_eq!(*neighborhood.center(), array.get(&p));
+            for (i, o) in kernel.offsets().iter().enumerate() {
+                assert_eq!(*neighborhood.get(i), array.get(&(p + *o)));
+            }
+        });
+        assert_eq!(num_visited, 4 * 2);
+    }
+
+    #[test]
+    fn boundary_modes() {
+        let extent = Extent3i::from_min_and_shape(PointN([1; 3]), PointN([4; 3]));
+        let array = Array3::fill_with(extent, |p| p.x() + 10 * p.y() + 100 * p.z());
+        let kernel = Kernel::new(vec![PointN([-1, 0, 0]), PointN([0, 2, 0])]);
+        let corner = PointN([1, 4, 1]);
+
+        let mut num_visited = 0;
+        array.for_each_neighborhood_with_boundary(&kernel, &extent, &Boundary::Clamp, |p, n| {
+            num_visited += 1;
+            if p == corner {
+                assert_eq!(*n.get(0), array.get(&PointN([1, 4, 1])));
+                assert_eq!(*n.get(1), array.get(&PointN([1, 4, 1])));
+            }
+        });
+        assert_eq!(num_visited, 64);
+
+        array.for_each_neighborhood_with_boundary(&kernel, &extent, &Boundary::Wrap, |p, n| {
+            if p == corner {
+                assert_eq!(*n.get(0), array.get(&PointN([4, 4, 1])));
+                assert_eq!(*n.get(1), array.get(&PointN([1, 2, 1])));
+            }
+        });
+
+        let boundary = Boundary::Ambient(-1);
+        array.for_each_neighborhood_with_boundary(&kernel, &extent, &boundary, |p, n| {
+            if p == corner {
+                assert_eq!(n.iter().cloned().collect::<Vec<_>>(), vec![-1, -1]);
+            } else if p == PointN([2; 3]) {
+                assert_eq!(*n.get(0), array.get(&PointN([1, 2, 2])));
+            }
+        });
+    }
+}
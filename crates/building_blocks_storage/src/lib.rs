@@ -3,19 +3,52 @@
 //!
 //! The core storage types are:
 //!   - `ArrayN`: N-dimensional, dense array
-//!   - `ChunkMap`: N-dimensional, sparse array
+//!   - `ChunkMap`: N-dimensional, sparse array, with optional per-chunk summaries (`ChunkSummary`)
+//!     that can be saved in a versioned format (`chunk_map_file`), paged in and out of a backing
+//!     store (`chunk_store`), and combined like layers (`merge`)
+//!   - `BitArrayN`: N-dimensional, dense array of bit-packed `bool`s
+//!   - `MortonArrayN`: N-dimensional, dense array stored in Morton (Z-order), for better locality
+//!   - `HashMapN`: N-dimensional, sparse map of individual points, for extremely sparse data
+//!   - `VdbMap`: N-dimensional, hierarchical sparse map with tiles and dense leaves
+//!   - `MultiArrayN`: N-dimensional, dense arrays for multiple channels, with a shared layout
+//!
+//! There are also borrowed views of dense arrays:
+//!   - `ArrayView` and `ArrayViewMut`: a sub-extent of an `ArrayN`, using the parent's layout
+//!
+//! Arrays can be reoriented and resampled; see the `array_geometry` module. Kernel-based algorithms
+//! can iterate over neighborhoods of array points; see the `kernel` module. Blurs, morphology and
+//! gradients are in the `filters` module, and constant-time sums over extents are provided by
+//! summed-area tables in the `summed_area` module. Statistics like `min_max`, `sum` and `histogram`
+//! over any extent are provided by the `Reduce` trait in the `reduce` module.
 //!
 //! Then there are "meta" lattice maps that provide some extra utility:
 //!   - `TransformMap`: a wrapper of any kind of lattice map that performs an arbitrary transformation
+//!   - `OwnedTransformMap` and `ZipMap`: owned, composable versions built with `TransformExt`
 //!   - `Fn(&PointN<N>)`: some lattice map traits are implemented for functions (like SDFs)
 
 pub mod access;
 pub mod array;
 pub mod array2;
 pub mod array3;
+pub mod array_geometry;
+pub mod array_view;
+pub mod bit_array;
+pub mod chunk_events;
 pub mod chunk_map;
+pub mod chunk_map_file;
+pub mod chunk_store;
+pub mod chunk_summary;
+pub mod filters;
 pub mod func;
+pub mod hash_map;
+pub mod kernel;
+pub mod merge;
+pub mod morton_array;
+pub mod multi_array;
+pub mod reduce;
+pub mod summed_area;
 pub mod transform_map;
+pub mod vdb;
 
 pub use access::{
     copy_extent, ForEachMut, ForEachRef, Get, GetMut, GetRef, ReadExtent, WriteExtent,
@@ -23,23 +56,41 @@ pub use access::{
 pub use array::{Array, ArrayExtent, ArrayN, FastLz4, Local, Stride};
 pub use array2::Array2;
 pub use array3::Array3;
+pub use array_view::{
+    ArrayView, ArrayView2, ArrayView3, ArrayViewMut, ArrayViewMut2, ArrayViewMut3,
+};
+pub use bit_array::{BitArray2, BitArray3, BitArrayN};
 pub use chunk_map::{
     Chunk, Chunk2, Chunk3, ChunkMap, ChunkMap2, ChunkMap3, ChunkMapReader, ChunkMapReader2,
     ChunkMapReader3, LocalChunkCache, SerializableChunkMap, SerializableChunkMap2,
     SerializableChunkMap3,
 };
-pub use transform_map::TransformMap;
+pub use chunk_summary::ChunkSummary;
+pub use hash_map::{HashMap2, HashMap3, HashMapN};
+pub use morton_array::{MortonArray2, MortonArray3, MortonArrayN};
+pub use multi_array::{
+    MultiArray2, MultiArray3, MultiArrayN, MultiChunk, MultiChunk2, MultiChunk3,
+};
+pub use transform_map::{OwnedTransformMap, TransformExt, TransformMap, ZipMap};
+pub use vdb::{VdbMap, VdbMap2, VdbMap3};
 
 // Used in many generic algorithms to check if a voxel is considered empty.
 pub trait IsEmpty {
     fn is_empty(&self) -> bool;
 }
 
+impl IsEmpty for bool {
+    fn is_empty(&self) -> bool {
+        !*self
+    }
+}
+
 pub mod prelude {
     pub use super::{
-        copy_extent, Array, Array2, Array3, ArrayExtent, ArrayN, BincodeLz4, Chunk2, Chunk3,
-        ChunkMap2, ChunkMap3, ChunkMapReader2, ChunkMapReader3, Compressible, Decompressible,
-        FastLz4, ForEachMut, ForEachRef, Get, GetMut, GetRef, LocalChunkCache, ReadExtent, Stride,
+        copy_extent, Array, Array2, Array3, ArrayExtent, ArrayN, ArrayView2, ArrayView3,
+        ArrayViewMut2, ArrayViewMut3, BincodeLz4, Chunk2, Chunk3, ChunkMap2, ChunkMap3,
+        ChunkMapReader2, ChunkMapReader3, Compressible, Decompressible, FastLz4, ForEachMut,
+        ForEachRef, Get, GetMut, GetRef, LocalChunkCache, ReadExtent, Stride, TransformExt,
         TransformMap, WriteExtent,
     };
 }
@@ -0,0 +1,327 @@
+//! Operations that combine one `ChunkMap` into another, like layers in an editor.
+//!
+//! All of these work a chunk at a time. When only one of the maps has a chunk at some key, the other
+//! map's ambient value stands in for the missing chunk, which often lets the whole chunk be skipped,
+//! removed, or copied directly. Both maps must have the same chunk shape.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::prelude::*;
+//!
+//! let chunk_shape = PointN([16; 3]);
+//! let floor = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([32, 1, 32]));
+//! let make_terrain = || {
+//!     let mut terrain = ChunkMap3::new(chunk_shape, 0u8, (), FastLz4 { level: 10 });
+//!     terrain.fill_extent(&floor, 1);
+//!     terrain
+//! };
+//! let mut buildings = ChunkMap3::new(chunk_shape, 0u8, (), FastLz4 { level: 10 });
+//! *buildings.get_mut(&PointN([1, 0, 1])) = 2;
+//! *buildings.get_mut(&PointN([1, 1, 1])) = 2;
+//!
+//! // Buildings replace terrain wherever they aren't ambient.
+//! let mut level = make_terrain();
+//! level.overlay(&buildings);
+//! assert_eq!(*level.get_mut(&PointN([1, 0, 1])), 2);
+//! assert_eq!(*level.get_mut(&PointN([2, 0, 2])), 1);
+//!
+//! // Or combine them any other way.
+//! let mut terrain = make_terrain();
+//! terrain.merge_with(&buildings, |t, b| t.max(b));
+//! assert_eq!(*terrain.get_mut(&PointN([1, 1, 1])), 2);
+//! ```
+
+use crate::{chunk_map::ChunkShape, ArrayN, Chunk, ChunkMap, IsEmpty, LocalChunkCache};
+
+use building_blocks_core::prelude::*;
+
+use core::hash::Hash;
+use fnv::FnvHashSet;
+
+impl<N, T, M> ChunkMap<N, T, M>
+where
+    N: Clone,
+    T: Copy,
+    M: Clone,
+    PointN<N>: IntegerPoint + ChunkShape<N> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Sets every point `p` that is in a chunk of either map to `combine(self[p], other[p])`.
+    /// Points outside of both maps' chunks keep this map's ambient value.
+    pub fn merge_with<U, M2>(&mut self, other: &ChunkMap<N, U, M2>, combine: impl Fn(T, U) -> T)
+    where
+        U: Copy,
+        M2: Clone,
+    {
+        assert!(self.chunk_shape() == other.chunk_shape());
+
+        let mut keys: FnvHashSet<PointN<N>> = self.chunk_keys().cloned().collect();
+        keys.extend(other.chunk_keys().cloned());
+
+        let ambient_value = *self.ambient_value();
+        let metadata = self.default_chunk_metadata().clone();
+        let other_ambient_value = *other.ambient_value();
+        for key in keys.into_iter() {
+            let local_cache = LocalChunkCache::new();
+            let other_chunk = other.get_chunk(key, &local_cache);
+            let chunk = self.get_mut_chunk_or_insert_with(key, |_key, extent| Chunk {
+                metadata: metadata.clone(),
+                map: ArrayN::fill(*extent, ambient_value),
+            });
+            let values = chunk.map.values_slice_mut().iter_mut();
+            match other_chunk {
+                Some(other_chunk) => {
+                    for (v, o) in values.zip(other_chunk.map.values_slice().iter()) {
+                        *v = combine(*v, *o);
+                    }
+                }
+                None => {
+                    for v in values {
+                        *v = combine(*v, other_ambient_value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copies every point of `other` that doesn't have `other`'s ambient value into this map.
+    /// Chunks that only exist in `other` are copied whole.
+    pub fn overlay<M2>(&mut self, other: &ChunkMap<N, T, M2>)
+    where
+        T: PartialEq,
+        M2: Clone,
+    {
+        assert!(self.chunk_shape() == other.chunk_shape());
+
+        let ambient_value = *self.ambient_value();
+        let other_ambient_value = *other.ambient_value();
+        let other_keys: Vec<_> = other.chunk_keys().cloned().collect();
+        for key in other_keys.into_iter() {
+            let local_cache = LocalChunkCache::new();
+            let other_chunk = other.get_chunk(key, &local_cache).unwrap();
+            match self.get_mut_chunk(key) {
+                Some(chunk) => {
+                    for (v, o) in chunk
+                        .map
+                        .values_slice_mut()
+                        .iter_mut()
+                        .zip(other_chunk.map.values_slice().iter())
+                    {
+                        if *o != other_ambient_value {
+                            *v = *o;
+                        }
+                    }
+                }
+                None => {
+                    let mut map = other_chunk.map.clone();
+                    if other_ambient_value != ambient_value {
+                        for v in map.values_slice_mut().iter_mut() {
+                            if *v == other_ambient_value {
+                                *v = ambient_value;
+                            }
+                        }
+                    }
+                    let metadata = self.default_chunk_metadata().clone();
+                    self.insert_chunk(key, Chunk { metadata, map });
+                }
+            }
+        }
+    }
+}
+
+/// Occupancy operations, where a point is occupied if its value is not empty. The ambient values of
+/// both maps must be empty.
+impl<N, T, M> ChunkMap<N, T, M>
+where
+    N: Clone,
+    T: Copy + IsEmpty,
+    M: Clone,
+    PointN<N>: IntegerPoint + ChunkShape<N> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Fills every point that is empty in this map but occupied in `other` with the value from
+    /// `other`. Chunks that only exist in `other` are copied whole.
+    pub fn union_with<M2: Clone>(&mut self, other: &ChunkMap<N, T, M2>) {
+        self.assert_occupancy_compatible(other);
+
+        let other_keys: Vec<_> = other.chunk_keys().cloned().collect();
+        for key in other_keys.into_iter() {
+            let local_cache = LocalChunkCache::new();
+            let other_chunk = other.get_chunk(key, &local_cache).unwrap();
+            match self.get_mut_chunk(key) {
+                Some(chunk) => {
+                    for (v, o) in chunk
+                        .map
+                        .values_slice_mut()
+                        .iter_mut()
+                        .zip(other_chunk.map.values_slice().iter())
+                    {
+                        if v.is_empty() && !o.is_empty() {
+                            *v = *o;
+                        }
+                    }
+                }
+                None => {
+                    let metadata = self.default_chunk_metadata().clone();
+                    self.insert_chunk(
+                        key,
+                        Chunk {
+                            metadata,
+                            map: other_chunk.map.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Empties every point that is empty in `other`. Chunks that don't exist in `other` are
+    /// removed whole.
+    pub fn intersect_with<M2: Clone>(&mut self, other: &ChunkMap<N, T, M2>) {
+        self.assert_occupancy_compatible(other);
+
+        let ambient_value = *self.ambient_value();
+        let keys: Vec<_> = self.chunk_keys().cloned().collect();
+        for key in keys.into_iter() {
+            let local_cache = LocalChunkCache::new();
+            match other.get_chunk(key, &local_cache) {
+                Some(other_chunk) => {
+                    let chunk = self.get_mut_chunk(key).unwrap();
+                    for (v, o) in chunk
+                        .map
+                        .values_slice_mut()
+                        .iter_mut()
+                        .zip(other_chunk.map.values_slice().iter())
+                    {
+                        if o.is_empty() {
+                            *v = ambient_value;
+                        }
+                    }
+                }
+                None => {
+                    self.remove_chunk(key);
+                }
+            }
+        }
+    }
+
+    /// Empties every point that is occupied in `other`. Chunks that don't exist in `other` are
+    /// skipped.
+    pub fn difference_with<M2: Clone>(&mut self, other: &ChunkMap<N, T, M2>) {
+        self.assert_occupancy_compatible(other);
+
+        let ambient_value = *self.ambient_value();
+        let keys: Vec<_> = self.chunk_keys().cloned().collect();
+        for key in keys.into_iter() {
+            let local_cache = LocalChunkCache::new();
+            if let Some(other_chunk) = other.get_chunk(key, &local_cache) {
+                let chunk = self.get_mut_chunk(key).unwrap();
+                for (v, o) in chunk
+                    .map
+                    .values_slice_mut()
+                    .iter_mut()
+                    .zip(other_chunk.map.values_slice().iter())
+                {
+                    if !o.is_empty() {
+                        *v = ambient_value;
+                    }
+                }
+            }
+        }
+    }
+
+    fn assert_occupancy_compatible<M2: Clone>(&self, other: &ChunkMap<N, T, M2>) {
+        assert!(self.chunk_shape() == other.chunk_shape());
+        assert!(self.ambient_value().is_empty());
+        assert!(other.ambient_value().is_empty());
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{ChunkMap3, FastLz4, GetMut};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Voxel(u8);
+
+    impl IsEmpty for Voxel {
+        fn is_empty(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    fn map_with_points(points: &[[i32; 3]], value: u8) -> ChunkMap3<Voxel, ()> {
+        let mut map = ChunkMap3::new(PointN([4; 3]), Voxel(0), (), FastLz4 { level: 10 });
+        for p in points.iter() {
+            *map.get_mut(&PointN(*p)) = Voxel(value);
+        }
+
+        map
+    }
+
+    #[test]
+    fn merge_and_overlay_with_different_ambient_values() {
+        let chunk_shape = PointN([4; 3]);
+        let make_a = || {
+            let mut a = ChunkMap3::new(chunk_shape, 1, (), FastLz4 { level: 10 });
+            *a.get_mut(&PointN([0; 3])) = 5;
+            a
+        };
+        let mut b = ChunkMap3::new(chunk_shape, 0, (), FastLz4 { level: 10 });
+        *b.get_mut(&PointN([1; 3])) = 7;
+        *b.get_mut(&PointN([10; 3])) = 3;
+
+        let mut overlaid = make_a();
+        overlaid.overlay(&b);
+        assert_eq!(overlaid.chunk_keys().count(), 2);
+        assert_eq!(*overlaid.get_mut(&PointN([0; 3])), 5);
+        assert_eq!(*overlaid.get_mut(&PointN([1; 3])), 7);
+        assert_eq!(*overlaid.get_mut(&PointN([10; 3])), 3);
+        // b's ambient points in the copied chunk become a's ambient value.
+        assert_eq!(*overlaid.get_mut(&PointN([9; 3])), 1);
+
+        let mut a = make_a();
+        a.merge_with(&b, |x, y| x + y);
+        assert_eq!(a.chunk_keys().count(), 2);
+        assert_eq!(*a.get_mut(&PointN([0; 3])), 5);
+        assert_eq!(*a.get_mut(&PointN([1; 3])), 8);
+        assert_eq!(*a.get_mut(&PointN([10; 3])), 4);
+        assert_eq!(*a.get_mut(&PointN([9; 3])), 1);
+        assert_eq!(*a.get_mut(&PointN([-10; 3])), 1);
+    }
+
+    #[test]
+    fn occupancy_set_operations() {
+        let a_points = [[0; 3], [1; 3], [10; 3]];
+        let b = map_with_points(&[[1; 3], [2; 3], [-10; 3]], 2);
+
+        let mut union = map_with_points(&a_points, 1);
+        union.union_with(&b);
+        assert_eq!(union.chunk_keys().count(), 3);
+        assert_eq!(*union.get_mut(&PointN([1; 3])), Voxel(1));
+        assert_eq!(*union.get_mut(&PointN([2; 3])), Voxel(2));
+        assert_eq!(*union.get_mut(&PointN([-10; 3])), Voxel(2));
+
+        let mut intersection = map_with_points(&a_points, 1);
+        intersection.intersect_with(&b);
+        assert_eq!(intersection.chunk_keys().count(), 1);
+        assert_eq!(*intersection.get_mut(&PointN([0; 3])), Voxel(0));
+        assert_eq!(*intersection.get_mut(&PointN([1; 3])), Voxel(1));
+
+        let mut difference = map_with_points(&a_points, 1);
+        difference.difference_with(&b);
+        assert_eq!(difference.chunk_keys().count(), 2);
+        assert_eq!(*difference.get_mut(&PointN([0; 3])), Voxel(1));
+        assert_eq!(*difference.get_mut(&PointN([1; 3])), Voxel(0));
+        assert_eq!(*difference.get_mut(&PointN([10; 3])), Voxel(1));
+    }
+}
@@ -0,0 +1,459 @@
+//! A dense N-dimensional array stored in Morton (Z-order), where N is 2 or 3.
+//!
+//! A `MortonArrayN` holds the same data as an `ArrayN` with the same extent, but the values are
+//! laid out along the Z-order curve of the array's local coordinates. Every aligned power-of-2
+//! cube of the array is then one contiguous run of values, so algorithms that recursively
+//! subdivide space (like `Octree::from_morton_array`) or that work on small neighborhoods touch
+//! far fewer cache lines than they would in row-major order.
+//!
+//! The array's extent must be a cube with a power-of-2 edge length. The layout is different from
+//! `ArrayN`, so a `Stride` into a `MortonArrayN` can't be offset to reach an adjacent point, and
+//! the type doesn't implement `Array` or `ArrayExtent`. Look up neighbors with points instead, and
+//! convert with `from_array` and `to_array` (or write into it with `copy_extent`).
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, MortonArray3};
+//!
+//! let extent = Extent3i::from_min_and_shape(PointN([-8; 3]), PointN([16; 3]));
+//! let dense = Array3::fill_with(extent, |p| p.x() + p.y() + p.z());
+//! let morton = MortonArray3::from_array(&dense);
+//! assert_eq!(morton.get(&PointN([1, 2, 3])), 6);
+//!
+//! // Iteration follows the Z-order curve, so each 2x2x2 octant is visited in one run.
+//! let mut first_points = Vec::new();
+//! morton.for_each_ref(morton.extent(), |p: Point3i, _value| {
+//!     if first_points.len() < 8 {
+//!         first_points.push(p);
+//!     }
+//! });
+//! let octant = Extent3i::from_min_and_shape(PointN([-8; 3]), PointN([2; 3]));
+//! assert!(first_points.iter().all(|p| octant.contains(p)));
+//!
+//! assert_eq!(morton.to_array(), dense);
+//! ```
+
+use crate::{
+    access::GetUncheckedRelease, array::ArrayCopySrc, chunk_map::ChunkCopySrc, Array, ArrayExtent,
+    ArrayN, ForEachMut, ForEachRef, Get, GetMut, GetRef, Local, Stride, WriteExtent,
+};
+
+use building_blocks_core::prelude::*;
+
+use core::ops::Deref;
+use either::Either;
+use num::Zero;
+use serde::{Deserialize, Serialize};
+
+/// A map from lattice location `PointN<N>` to data `T`, stored as a flat array on the heap in
+/// Morton order.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MortonArrayN<N, T> {
+    values: Vec<T>,
+    extent: ExtentN<N>,
+}
+
+pub type MortonArray2<T> = MortonArrayN<[i32; 2], T>;
+pub type MortonArray3<T> = MortonArrayN<[i32; 3], T>;
+
+impl<N, T> MortonArrayN<N, T> {
+    pub fn extent(&self) -> &ExtentN<N> {
+        &self.extent
+    }
+
+    /// Returns the entire slice of values, in Morton order.
+    pub fn values_slice(&self) -> &[T] {
+        &self.values[..]
+    }
+
+    /// Returns the entire mutable slice of values, in Morton order.
+    pub fn values_slice_mut(&mut self) -> &mut [T] {
+        &mut self.values[..]
+    }
+}
+
+impl<N, T> MortonArrayN<N, T>
+where
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Creates a map that fills the entire `extent` with the same `value`. `extent` must be a cube
+    /// with a power-of-2 edge length.
+    pub fn fill(extent: ExtentN<N>, value: T) -> Self
+    where
+        T: Clone,
+    {
+        assert_morton_shape(&extent);
+
+        Self {
+            values: vec![value; extent.num_points()],
+            extent,
+        }
+    }
+
+    pub fn fill_with(extent: ExtentN<N>, filler: impl Fn(&PointN<N>) -> T) -> Self {
+        assert_morton_shape(&extent);
+        // Local Morton order is the same as the layout.
+        let values = (extent - extent.minimum)
+            .iter_points_morton()
+            .map(|p| filler(&(p + extent.minimum)))
+            .collect();
+
+        Self { values, extent }
+    }
+
+    /// Copies `array` into Morton order. The extent of `array` must be a cube with a power-of-2
+    /// edge length.
+    pub fn from_array(array: &ArrayN<N, T>) -> Self
+    where
+        T: Clone,
+        ArrayN<N, T>: for<'r> GetRef<&'r PointN<N>, Data = T>,
+    {
+        Self::fill_with(*array.extent(), |p| array.get_ref(p).clone())
+    }
+
+    /// Copies this array into the row-major layout of `ArrayN`.
+    pub fn to_array(&self) -> ArrayN<N, T>
+    where
+        T: Clone,
+    {
+        let values = self
+            .extent
+            .iter_points()
+            .map(|p| self.get_ref(&p).clone())
+            .collect();
+
+        ArrayN::new(self.extent, values)
+    }
+
+    /// The `Stride` of the point at `p`, in local coordinates.
+    #[inline]
+    pub fn stride_from_local_point(p: &Local<N>) -> Stride {
+        // Local coordinates are never negative, so the bias applied by `MortonEncode` only sets
+        // bits above the code of any point in the array.
+        Stride((p.0.morton_encode() ^ PointN::<N>::zero().morton_encode()) as usize)
+    }
+
+    /// Calls `f` on every point in `extent` that's also in the array, along with the point's
+    /// `Stride`, in Morton order.
+    fn for_each_point_and_stride(&self, extent: &ExtentN<N>, mut f: impl FnMut(PointN<N>, Stride)) {
+        let minimum = self.extent.minimum;
+        let local_extent = extent.intersection(&self.extent) - minimum;
+        for p in local_extent.iter_points_morton() {
+            f(p + minimum, Self::stride_from_local_point(&Local(p)));
+        }
+    }
+}
+
+fn assert_morton_shape<N>(extent: &ExtentN<N>)
+where
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    // The local codes are dense exactly when the greatest one is the last index.
+    let max_local = extent.shape - PointN::ONES;
+    let max_stride = MortonArrayN::<N, ()>::stride_from_local_point(&Local(max_local));
+    assert!(
+        extent.shape > PointN::zero() && max_stride.0 + 1 == extent.num_points(),
+        "MortonArrayN requires a cube-shaped extent with a power-of-2 edge length"
+    );
+}
+
+//  ██████╗ ███████╗████████╗████████╗███████╗██████╗ ███████╗
+// ██╔════╝ ██╔════╝╚══██╔══╝╚══██╔══╝██╔════╝██╔══██╗██╔════╝
+// ██║  ███╗█████╗     ██║      ██║   █████╗  ██████╔╝███████╗
+// ██║   ██║██╔══╝     ██║      ██║   ██╔══╝  ██╔══██╗╚════██║
+// ╚██████╔╝███████╗   ██║      ██║   ███████╗██║  ██║███████║
+//  ╚═════╝ ╚══════╝   ╚═╝      ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
+
+impl<N, T> Get<Stride> for MortonArrayN<N, T>
+where
+    T: Clone,
+{
+    type Data = T;
+
+    #[inline]
+    fn get(&self, stride: Stride) -> Self::Data {
+        self.values[stride.0].clone()
+    }
+}
+
+impl<N, T> GetRef<Stride> for MortonArrayN<N, T> {
+    type Data = T;
+
+    #[inline]
+    fn get_ref(&self, stride: Stride) -> &Self::Data {
+        &self.values[stride.0]
+    }
+}
+
+impl<N, T> GetMut<Stride> for MortonArrayN<N, T> {
+    type Data = T;
+
+    #[inline]
+    fn get_mut(&mut self, stride: Stride) -> &mut Self::Data {
+        &mut self.values[stride.0]
+    }
+}
+
+impl<N, T> Get<&Local<N>> for MortonArrayN<N, T>
+where
+    T: Clone,
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get(&self, p: &Local<N>) -> Self::Data {
+        self.get(Self::stride_from_local_point(p))
+    }
+}
+
+impl<N, T> GetRef<&Local<N>> for MortonArrayN<N, T>
+where
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get_ref(&self, p: &Local<N>) -> &Self::Data {
+        self.get_ref(Self::stride_from_local_point(p))
+    }
+}
+
+impl<N, T> GetMut<&Local<N>> for MortonArrayN<N, T>
+where
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get_mut(&mut self, p: &Local<N>) -> &mut Self::Data {
+        self.get_mut(Self::stride_from_local_point(p))
+    }
+}
+
+impl<N, T> Get<&PointN<N>> for MortonArrayN<N, T>
+where
+    T: Clone,
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get(&self, p: &PointN<N>) -> Self::Data {
+        self.get(&Local(*p - self.extent.minimum))
+    }
+}
+
+impl<N, T> GetRef<&PointN<N>> for MortonArrayN<N, T>
+where
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get_ref(&self, p: &PointN<N>) -> &Self::Data {
+        self.get_ref(&Local(*p - self.extent.minimum))
+    }
+}
+
+impl<N, T> GetMut<&PointN<N>> for MortonArrayN<N, T>
+where
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    #[inline]
+    fn get_mut(&mut self, p: &PointN<N>) -> &mut Self::Data {
+        let local_p = *p - self.extent.minimum;
+
+        self.get_mut(&Local(local_p))
+    }
+}
+
+// ███████╗ ██████╗ ██████╗     ███████╗ █████╗  ██████╗██╗  ██╗
+// ██╔════╝██╔═══██╗██╔══██╗    ██╔════╝██╔══██╗██╔════╝██║  ██║
+// █████╗  ██║   ██║██████╔╝    █████╗  ███████║██║     ███████║
+// ██╔══╝  ██║   ██║██╔══██╗    ██╔══╝  ██╔══██║██║     ██╔══██║
+// ██║     ╚██████╔╝██║  ██║    ███████╗██║  ██║╚██████╗██║  ██║
+// ╚═╝      ╚═════╝ ╚═╝  ╚═╝    ╚══════╝╚═╝  ╚═╝ ╚═════╝╚═╝  ╚═╝
+
+macro_rules! impl_morton_array_for_each {
+    (coords: $coords:ty; forwarder = |$p:ident, $stride:ident| $forward_coords:expr;) => {
+        impl<N, T> ForEachRef<N, $coords> for MortonArrayN<N, T>
+        where
+            PointN<N>: IntegerPoint + MortonEncode,
+            ExtentN<N>: IntegerExtent<N>,
+        {
+            type Data = T;
+
+            fn for_each_ref(&self, extent: &ExtentN<N>, mut f: impl FnMut($coords, &T)) {
+                self.for_each_point_and_stride(extent, |$p, $stride| {
+                    f($forward_coords, &self.values[$stride.0])
+                })
+            }
+        }
+
+        impl<N, T> ForEachMut<N, $coords> for MortonArrayN<N, T>
+        where
+            PointN<N>: IntegerPoint + MortonEncode,
+            ExtentN<N>: IntegerExtent<N>,
+        {
+            type Data = T;
+
+            fn for_each_mut(&mut self, extent: &ExtentN<N>, mut f: impl FnMut($coords, &mut T)) {
+                let minimum = self.extent.minimum;
+                let local_extent = extent.intersection(&self.extent) - minimum;
+                for local_p in local_extent.iter_points_morton() {
+                    let $p = local_p + minimum;
+                    let $stride = Self::stride_from_local_point(&Local(local_p));
+                    f($forward_coords, &mut self.values[$stride.0]);
+                }
+            }
+        }
+    };
+}
+
+impl_morton_array_for_each!(
+    coords: (PointN<N>, Stride);
+    forwarder = |p, stride| (p, stride);
+);
+impl_morton_array_for_each!(
+    coords: Stride;
+    forwarder = |_p, stride| stride;
+);
+impl_morton_array_for_each!(
+    coords: PointN<N>;
+    forwarder = |p, stride| p;
+);
+
+//  ██████╗ ██████╗ ██████╗ ██╗   ██╗
+// ██╔════╝██╔═══██╗██╔══██╗╚██╗ ██╔╝
+// ██║     ██║   ██║██████╔╝ ╚████╔╝
+// ██║     ██║   ██║██╔═══╝   ╚██╔╝
+// ╚██████╗╚██████╔╝██║        ██║
+//  ╚═════╝ ╚═════╝ ╚═╝        ╚═╝
+
+// Sources always have the `ArrayN` layout, so each point is mapped to a source stride separately.
+
+impl<N, T, M, Ms> WriteExtent<N, ArrayCopySrc<Ms>> for MortonArrayN<N, T>
+where
+    ArrayCopySrc<Ms>: Deref<Target = M>,
+    M: ArrayExtent<N> + GetUncheckedRelease<Stride, T>,
+    ArrayN<N, T>: Array<N>,
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src_array: ArrayCopySrc<Ms>) {
+        let src_extent = *src_array.extent();
+        self.for_each_mut(extent, |p: PointN<N>, value| {
+            let s_src =
+                ArrayN::<N, T>::stride_from_point(&src_extent.shape, &(p - src_extent.minimum));
+            *value = src_array.get_unchecked_release(s_src);
+        });
+    }
+}
+
+impl<M, N, T> WriteExtent<N, ChunkCopySrc<M, N, T>> for MortonArrayN<N, T>
+where
+    T: Clone,
+    Self: WriteExtent<N, ArrayCopySrc<M>>,
+    PointN<N>: IntegerPoint + MortonEncode,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: ChunkCopySrc<M, N, T>) {
+        match src {
+            Either::Left(array) => self.write_extent(extent, array),
+            Either::Right(ambient) => {
+                let src_value = ambient.get();
+                self.for_each_mut(extent, |_s: Stride, value| *value = src_value.clone());
+            }
+        }
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        copy_extent, Array2, Array3, ChunkMap3, ChunkMapReader3, FastLz4, LocalChunkCache,
+    };
+
+    #[test]
+    fn round_trip_through_dense_array() {
+        let extent = Extent3i::from_min_and_shape(PointN([-3, 5, -8]), PointN([8; 3]));
+        let dense = Array3::fill_with(extent, |p| p.x() + 10 * p.y() + 100 * p.z());
+        let morton = MortonArray3::from_array(&dense);
+
+        for p in extent.iter_points() {
+            assert_eq!(morton.get(&p), dense.get(&p));
+        }
+        assert_eq!(morton.to_array(), dense);
+
+        let extent = Extent2i::from_min_and_shape(PointN([1, 2]), PointN([4, 4]));
+        let dense = Array2::fill_with(extent, |p| p.x() * p.y());
+        assert_eq!(MortonArray2::from_array(&dense).to_array(), dense);
+    }
+
+    #[test]
+    fn values_follow_the_z_order_curve() {
+        let extent = Extent3i::from_min_and_shape(PointN([4; 3]), PointN([4; 3]));
+        let morton = MortonArray3::fill_with(extent, |p| *p);
+        // Each octant is a contiguous run of values.
+        for (i, octant) in morton.values_slice().chunks(8).enumerate() {
+            let octant_min = octant[0];
+            assert_eq!(octant_min.right_shift(1).left_shift(1), octant_min);
+            for p in octant.iter() {
+                let offset = *p - octant_min;
+                assert!(PointN([0; 3]) <= offset && offset < PointN([2; 3]));
+            }
+            assert_eq!(morton.get(Stride(8 * i)), octant_min);
+        }
+
+        let mut visited = Vec::new();
+        let subextent = Extent3i::from_min_and_shape(PointN([5; 3]), PointN([2; 3]));
+        morton.for_each_ref(&subextent, |s: Stride, p| visited.push((s, *p)));
+        assert_eq!(visited.len(), 8);
+        for pair in visited.windows(2) {
+            assert!(pair[0].0 .0 < pair[1].0 .0);
+        }
+    }
+
+    #[test]
+    fn copy_from_chunk_map() {
+        let extent = Extent3i::from_min_and_shape(PointN([-8; 3]), PointN([16; 3]));
+        let mut map = ChunkMap3::new(PointN([4; 3]), 0, (), FastLz4 { level: 10 });
+        let written = Extent3i::from_min_and_shape(PointN([-2; 3]), PointN([6; 3]));
+        map.fill_extent(&written, 1);
+
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&map, &local_cache);
+        let mut morton = MortonArray3::fill(extent, 5);
+        copy_extent(&extent, &reader, &mut morton);
+
+        morton.for_each_ref(&extent, |p: Point3i, value| {
+            assert_eq!(*value, if written.contains(&p) { 1 } else { 0 });
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_cube_extent_panics() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([4, 4, 3]));
+        MortonArray3::fill(extent, 0);
+    }
+}
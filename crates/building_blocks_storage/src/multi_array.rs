@@ -0,0 +1,386 @@
+//! Structure-of-arrays storage for voxels with several fields.
+//!
+//! A `MultiArrayN` stores each field (or "channel") in its own `ArrayN`, and all of the channels
+//! share the same extent, and therefore the same `Stride`s. Algorithms that only read one field can
+//! borrow just that channel, which is a plain `ArrayN`, so it can be passed straight to any
+//! existing algorithm. For example, `surface_nets` can read only the distance channel, which keeps
+//! the other fields out of the cache.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, MultiArray3};
+//!
+//! let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([16; 3]));
+//! // Channels are (material, distance, light).
+//! let mut voxels = MultiArray3::fill(extent, (0u8, 1.0f32, 0u8));
+//!
+//! // Write a whole voxel at once.
+//! let mut neighbor_strides = [Stride(0); 1];
+//! voxels.strides_from_points(&[PointN([1, 0, 0])], &mut neighbor_strides);
+//! voxels.set(neighbor_strides[0], (2, -1.0, 15));
+//! assert_eq!(voxels.get(&PointN([1, 0, 0])), (2, -1.0, 15));
+//!
+//! // Or mutably borrow all channels at once and touch only some of them.
+//! let (mut materials, _distances, mut lights) = voxels.channels_mut();
+//! let subextent = Extent3i::from_min_and_shape(PointN([8; 3]), PointN([8; 3]));
+//! materials.for_each_mut(&subextent, |_s: Stride, m| *m = 1);
+//! lights.for_each_mut(&subextent, |_s: Stride, l| *l = 7);
+//!
+//! // Each channel is an `ArrayN` with the same layout as the multi-array.
+//! let (_materials, distances, _lights) = voxels.channels();
+//! assert_eq!(distances.get(neighbor_strides[0]), -1.0);
+//! ```
+
+use crate::{
+    array::FastLz4CompressedArrayN, Array, ArrayExtent, ArrayN, ArrayViewMut, FastLz4, Get, GetMut,
+    Stride,
+};
+
+use building_blocks_core::prelude::*;
+
+use compressible_map::{Compressible, Decompressible};
+use serde::{Deserialize, Serialize};
+
+/// A map from lattice location `PointN<N>` to a tuple of values, where each tuple element is stored
+/// in a separate `ArrayN` "channel." `C` is the tuple of channels, for example
+/// `(ArrayN<N, u8>, ArrayN<N, f32>)`. Up to 4 channels are supported.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MultiArrayN<N, C> {
+    extent: ExtentN<N>,
+    channels: C,
+}
+
+pub type MultiArray2<C> = MultiArrayN<[i32; 2], C>;
+pub type MultiArray3<C> = MultiArrayN<[i32; 3], C>;
+
+impl<N, C> MultiArrayN<N, C> {
+    pub fn extent(&self) -> &ExtentN<N> {
+        &self.extent
+    }
+}
+
+// All channels use the same layout as an `ArrayN`.
+
+impl<N, C> ArrayExtent<N> for MultiArrayN<N, C> {
+    fn extent(&self) -> &ExtentN<N> {
+        self.extent()
+    }
+}
+
+impl<N, C> Array<N> for MultiArrayN<N, C>
+where
+    ArrayN<N, ()>: Array<N>,
+{
+    #[inline]
+    fn stride_from_point(shape: &PointN<N>, point: &PointN<N>) -> Stride {
+        ArrayN::<N, ()>::stride_from_point(shape, point)
+    }
+
+    fn for_each_point_and_stride(
+        array_extent: &ExtentN<N>,
+        extent: &ExtentN<N>,
+        f: impl FnMut(PointN<N>, Stride),
+    ) {
+        ArrayN::<N, ()>::for_each_point_and_stride(array_extent, extent, f)
+    }
+
+    fn for_each_stride_parallel(
+        iter_extent: &ExtentN<N>,
+        array1_extent: &ExtentN<N>,
+        array2_extent: &ExtentN<N>,
+        f: impl FnMut(Stride, Stride),
+    ) {
+        ArrayN::<N, ()>::for_each_stride_parallel(iter_extent, array1_extent, array2_extent, f)
+    }
+}
+
+/// A compressed `MultiArrayN`, where each channel is compressed separately.
+#[derive(Clone)]
+pub struct FastLz4CompressedMultiArrayN<N, C> {
+    pub extent: ExtentN<N>,
+    pub channels: C,
+}
+
+/// One chunk of multi-channel data, along with some generic metadata. Like `Chunk`, it can be
+/// stored in a `CompressibleMap`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct MultiChunk<N, C, M = ()> {
+    pub metadata: M,
+    pub map: MultiArrayN<N, C>,
+}
+
+pub type MultiChunk2<C, M> = MultiChunk<[i32; 2], C, M>;
+pub type MultiChunk3<C, M> = MultiChunk<[i32; 3], C, M>;
+
+impl<N, C> MultiChunk<N, C, ()> {
+    /// Constructs a chunk without metadata.
+    pub fn with_map(map: MultiArrayN<N, C>) -> Self {
+        MultiChunk { metadata: (), map }
+    }
+}
+
+pub struct FastCompressedMultiChunk<N, C, M = ()>
+where
+    MultiArrayN<N, C>: Compressible<FastLz4>,
+{
+    pub metadata: M, // metadata doesn't get compressed, hope it's small!
+    pub compressed_map: <MultiArrayN<N, C> as Compressible<FastLz4>>::Compressed,
+}
+
+impl<N, C, M> Decompressible<FastLz4> for FastCompressedMultiChunk<N, C, M>
+where
+    M: Clone,
+    MultiArrayN<N, C>: Compressible<FastLz4>,
+{
+    type Decompressed = MultiChunk<N, C, M>;
+
+    fn decompress(&self) -> Self::Decompressed {
+        MultiChunk {
+            metadata: self.metadata.clone(),
+            map: self.compressed_map.decompress(),
+        }
+    }
+}
+
+impl<N, C, M> Compressible<FastLz4> for MultiChunk<N, C, M>
+where
+    M: Clone,
+    MultiArrayN<N, C>: Compressible<FastLz4>,
+{
+    type Compressed = FastCompressedMultiChunk<N, C, M>;
+
+    fn compress(&self, params: FastLz4) -> Self::Compressed {
+        FastCompressedMultiChunk {
+            metadata: self.metadata.clone(),
+            compressed_map: self.map.compress(params),
+        }
+    }
+}
+
+/// A tuple of `ArrayN`s that can be used as the channels of a `MultiArrayN`.
+pub trait Channels<N> {
+    /// The extent of the first channel.
+    fn extent(&self) -> &ExtentN<N>;
+
+    /// Returns `true` iff all channels have the same extent.
+    fn extents_match(&self) -> bool;
+}
+
+/// A tuple with one value for each channel of a `MultiArrayN`.
+pub trait ChannelValues<N> {
+    type Channels: Channels<N>;
+
+    fn fill_channels(self, extent: ExtentN<N>) -> Self::Channels;
+}
+
+impl<N, C> MultiArrayN<N, C>
+where
+    C: Channels<N>,
+    ExtentN<N>: Copy,
+{
+    /// Combines the `channels`, which must all have the same extent.
+    pub fn from_channels(channels: C) -> Self {
+        assert!(channels.extents_match());
+
+        Self {
+            extent: *channels.extent(),
+            channels,
+        }
+    }
+
+    /// Creates a map that fills the entire `extent` with the same `values`.
+    pub fn fill<V>(extent: ExtentN<N>, values: V) -> Self
+    where
+        V: ChannelValues<N, Channels = C>,
+    {
+        Self {
+            extent,
+            channels: values.fill_channels(extent),
+        }
+    }
+
+    /// Moves the channels out of `self`.
+    pub fn into_channels(self) -> C {
+        self.channels
+    }
+}
+
+macro_rules! impl_multi_array {
+    ($($t:ident: $i:tt),+) => {
+        impl<N, $($t),+> Channels<N> for ($(ArrayN<N, $t>,)+)
+        where
+            ExtentN<N>: PartialEq,
+        {
+            fn extent(&self) -> &ExtentN<N> {
+                self.0.extent()
+            }
+
+            fn extents_match(&self) -> bool {
+                $(self.0.extent() == self.$i.extent())&&+
+            }
+        }
+
+        impl<N, $($t),+> ChannelValues<N> for ($($t,)+)
+        where
+            $($t: Clone,)+
+            ExtentN<N>: IntegerExtent<N> + PartialEq,
+        {
+            type Channels = ($(ArrayN<N, $t>,)+);
+
+            fn fill_channels(self, extent: ExtentN<N>) -> Self::Channels {
+                ($(ArrayN::fill(extent, self.$i),)+)
+            }
+        }
+
+        impl<N, $($t),+> MultiArrayN<N, ($(ArrayN<N, $t>,)+)> {
+            /// Borrows every channel. Each channel has the same extent as `self`.
+            pub fn channels(&self) -> ($(&ArrayN<N, $t>,)+) {
+                ($(&self.channels.$i,)+)
+            }
+
+            /// Writes all of the `values` at `stride`.
+            pub fn set(&mut self, stride: Stride, values: ($($t,)+)) {
+                $(*self.channels.$i.get_mut(stride) = values.$i;)+
+            }
+        }
+
+        impl<N, $($t),+> MultiArrayN<N, ($(ArrayN<N, $t>,)+)>
+        where
+            $(ArrayN<N, $t>: Array<N>,)+
+            PointN<N>: IntegerPoint,
+            ExtentN<N>: IntegerExtent<N>,
+        {
+            /// Mutably borrows every channel at once. The views can't change the extent of a
+            /// channel, so they can't break the shared layout.
+            pub fn channels_mut(&mut self) -> ($(ArrayViewMut<'_, N, $t>,)+) {
+                let extent = self.extent;
+
+                ($(self.channels.$i.view_mut(&extent),)+)
+            }
+        }
+
+        impl<N, $($t),+> Get<Stride> for MultiArrayN<N, ($(ArrayN<N, $t>,)+)>
+        where
+            $($t: Clone),+
+        {
+            type Data = ($($t,)+);
+
+            #[inline]
+            fn get(&self, stride: Stride) -> Self::Data {
+                ($(self.channels.$i.get(stride),)+)
+            }
+        }
+
+        impl<N, $($t),+> Get<&PointN<N>> for MultiArrayN<N, ($(ArrayN<N, $t>,)+)>
+        where
+            Self: Array<N>,
+            PointN<N>: Point,
+            $($t: Clone),+
+        {
+            type Data = ($($t,)+);
+
+            #[inline]
+            fn get(&self, p: &PointN<N>) -> Self::Data {
+                let stride =
+                    Self::stride_from_point(&self.extent.shape, &(*p - self.extent.minimum));
+
+                self.get(stride)
+            }
+        }
+
+        impl<N, $($t),+> Decompressible<FastLz4>
+            for FastLz4CompressedMultiArrayN<N, ($(FastLz4CompressedArrayN<N, $t>,)+)>
+        where
+            $($t: Copy,)+
+            ExtentN<N>: IntegerExtent<N>,
+        {
+            type Decompressed = MultiArrayN<N, ($(ArrayN<N, $t>,)+)>;
+
+            fn decompress(&self) -> Self::Decompressed {
+                MultiArrayN {
+                    extent: self.extent,
+                    channels: ($(self.channels.$i.decompress(),)+),
+                }
+            }
+        }
+
+        impl<N, $($t),+> Compressible<FastLz4> for MultiArrayN<N, ($(ArrayN<N, $t>,)+)>
+        where
+            $($t: Copy,)+
+            ExtentN<N>: IntegerExtent<N>,
+        {
+            type Compressed =
+                FastLz4CompressedMultiArrayN<N, ($(FastLz4CompressedArrayN<N, $t>,)+)>;
+
+            fn compress(&self, params: FastLz4) -> Self::Compressed {
+                FastLz4CompressedMultiArrayN {
+                    extent: self.extent,
+                    channels: ($(self.channels.$i.compress(params),)+),
+                }
+            }
+        }
+    };
+}
+
+impl_multi_array!(A: 0, B: 1);
+impl_multi_array!(A: 0, B: 1, C: 2);
+impl_multi_array!(A: 0, B: 1, C: 2, D: 3);
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{access::GetUncheckedRefRelease, Array3, ForEachMut};
+
+    // Stand-in for an algorithm that only knows about single-channel arrays.
+    fn sum_at<A>(array: &A, strides: &[Stride]) -> f32
+    where
+        A: Array<[i32; 3]> + GetUncheckedRefRelease<Stride, f32>,
+    {
+        strides
+            .iter()
+            .map(|s| *array.get_unchecked_ref_release(*s))
+            .sum()
+    }
+
+    #[test]
+    fn channels_share_strides() {
+        let extent = Extent3i::from_min_and_shape(PointN([-2; 3]), PointN([4, 5, 6]));
+        let materials = Array3::fill_with(extent, |p| p.x() as u8);
+        let distances = Array3::fill_with(extent, |p| p.y() as f32);
+        let mut voxels = MultiArray3::from_channels((materials, distances));
+
+        let points = [PointN([-2, 0, 1]), PointN([1, 2, 3])];
+        let mut strides = [Stride(0); 2];
+        voxels.strides_from_points(
+            &[points[0] - extent.minimum, points[1] - extent.minimum],
+            &mut strides,
+        );
+        assert_eq!(voxels.get(strides[1]), (1, 2.0));
+        assert_eq!(voxels.get(&points[0]), (254, 0.0));
+
+        let (_materials, mut distances) = voxels.channels_mut();
+        distances.for_each_mut(&extent, |p: Point3i, d| *d += p.z() as f32);
+
+        let (_materials, distances) = voxels.channels();
+        assert_eq!(sum_at(distances, &strides), 1.0 + 5.0);
+    }
+
+    #[test]
+    fn compress_round_trip() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([8; 3]));
+        let mut voxels = MultiArray3::fill(extent, (0u16, 0.5f32, 1u8));
+        voxels.set(Stride(17), (3, -0.5, 2));
+        let chunk = MultiChunk::with_map(voxels.clone());
+
+        let decompressed = chunk.compress(FastLz4 { level: 10 }).decompress();
+
+        assert_eq!(decompressed.map, voxels);
+    }
+}
@@ -0,0 +1,324 @@
+//! Reductions and statistics over extents of lattice maps.
+//!
+//! The `Reduce` trait only requires a way to visit the values in an extent, each with the number of
+//! points that share it. Maps that can tell when a whole region has the same value, like the
+//! ambient space of a `ChunkMap`, visit that value once with a large count. Then `min_max`, `sum`,
+//! `histogram`, `count_if`, `any` and `all` come for free, and `any` and `all` stop as soon as the
+//! answer is known.
+//!
+//! `Reduce` is implemented for `ArrayN`, `ChunkMapReader`, and functions like SDFs.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, reduce::Reduce};
+//!
+//! let extent = Extent3i::from_min_and_shape(PointN([-8; 3]), PointN([16; 3]));
+//! let sdf = |p: &Point3i| p.dot(p) - 25;
+//! assert_eq!(sdf.min_max(&extent), Some((-25, 167)));
+//! assert!(sdf.any(&extent, |d| *d < 0));
+//!
+//! let sampled = Array3::fill_with(extent, |p| sdf(p) < 0);
+//! let inside = sampled.count_if(&extent, |solid| *solid);
+//! assert_eq!(sampled.histogram(&extent).get(&true), Some(&inside));
+//!
+//! // Chunks that are missing (ambient) are visited all at once.
+//! let mut map = ChunkMap3::new(PointN([16; 3]), 0u32, (), FastLz4 { level: 10 });
+//! *map.get_mut(&PointN([1; 3])) = 7;
+//! let local_cache = LocalChunkCache::new();
+//! let reader = ChunkMapReader3::new(&map, &local_cache);
+//! let big_extent = Extent3i::from_min_and_shape(PointN([-64; 3]), PointN([128; 3]));
+//! assert_eq!(reader.sum::<u64>(&big_extent), 7);
+//! assert!(reader.all(&big_extent, |v| *v <= 7));
+//! ```
+//!
+//! When the chunk metadata is a `ChunkSummary` that can detect uniform chunks, like `ValueRange`,
+//! the `ChunkMapReader` also visits the values of clean, uniform chunks all at once.
+
+use crate::{chunk_map::ChunkShape, Array, ArrayN, ChunkMapReader, ChunkSummary, Stride};
+
+use building_blocks_core::prelude::*;
+
+use core::hash::Hash;
+use core::ops::{Add, Mul};
+use fnv::FnvHashMap;
+use num::{NumCast, Zero};
+
+/// Reductions over the values of a lattice map in some extent.
+pub trait Reduce<N> {
+    type Data;
+
+    /// Calls `f` with every value in `extent` and the number of points with that value, stopping
+    /// early if `f` returns `false`. The same value may be visited more than once.
+    fn for_each_value_count(&self, extent: &ExtentN<N>, f: impl FnMut(&Self::Data, usize) -> bool);
+
+    /// The minimum and maximum values in `extent`, or `None` if it's empty.
+    fn min_max(&self, extent: &ExtentN<N>) -> Option<(Self::Data, Self::Data)>
+    where
+        Self::Data: Clone + PartialOrd,
+    {
+        let mut min_max: Option<(Self::Data, Self::Data)> = None;
+        self.for_each_value_count(extent, |v, _count| {
+            match &mut min_max {
+                None => min_max = Some((v.clone(), v.clone())),
+                Some((min, max)) => {
+                    if v < min {
+                        *min = v.clone();
+                    }
+                    if v > max {
+                        *max = v.clone();
+                    }
+                }
+            }
+
+            true
+        });
+
+        min_max
+    }
+
+    /// The sum of the values in `extent`, each converted into `S`.
+    fn sum<S>(&self, extent: &ExtentN<N>) -> S
+    where
+        Self::Data: Clone + Into<S>,
+        S: Copy + Add<Output = S> + Mul<Output = S> + NumCast + Zero,
+    {
+        let mut sum = S::zero();
+        self.for_each_value_count(extent, |v, count| {
+            let value: S = v.clone().into();
+            sum = if count == 1 {
+                sum + value
+            } else {
+                sum + value * NumCast::from(count).unwrap()
+            };
+
+            true
+        });
+
+        sum
+    }
+
+    /// The number of points in `extent` with each value.
+    fn histogram(&self, extent: &ExtentN<N>) -> FnvHashMap<Self::Data, usize>
+    where
+        Self::Data: Clone + Eq + Hash,
+    {
+        let mut histogram = FnvHashMap::default();
+        self.for_each_value_count(extent, |v, count| {
+            *histogram.entry(v.clone()).or_insert(0) += count;
+
+            true
+        });
+
+        histogram
+    }
+
+    /// The number of points in `extent` whose values satisfy `predicate`.
+    fn count_if(
+        &self,
+        extent: &ExtentN<N>,
+        mut predicate: impl FnMut(&Self::Data) -> bool,
+    ) -> usize {
+        let mut num = 0;
+        self.for_each_value_count(extent, |v, count| {
+            if predicate(v) {
+                num += count;
+            }
+
+            true
+        });
+
+        num
+    }
+
+    /// Returns `true` if any value in `extent` satisfies `predicate`.
+    fn any(&self, extent: &ExtentN<N>, mut predicate: impl FnMut(&Self::Data) -> bool) -> bool {
+        let mut found = false;
+        self.for_each_value_count(extent, |v, _count| {
+            found = predicate(v);
+
+            !found
+        });
+
+        found
+    }
+
+    /// Returns `true` if every value in `extent` satisfies `predicate`.
+    fn all(&self, extent: &ExtentN<N>, mut predicate: impl FnMut(&Self::Data) -> bool) -> bool {
+        !self.any(extent, |v| !predicate(v))
+    }
+}
+
+impl<N, T> Reduce<N> for ArrayN<N, T>
+where
+    Self: Array<N>,
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    fn for_each_value_count(&self, extent: &ExtentN<N>, mut f: impl FnMut(&T, usize) -> bool) {
+        // `ForEachRef` can't break out of the loop, so we find the strides ourselves.
+        let array_extent = *self.extent();
+        let values = self.values_slice();
+        for p in extent.intersection(&array_extent).iter_points() {
+            let Stride(s) =
+                Self::stride_from_point(&array_extent.shape, &(p - array_extent.minimum));
+            if !f(&values[s], 1) {
+                return;
+            }
+        }
+    }
+}
+
+impl<F, N, T> Reduce<N> for F
+where
+    F: Fn(&PointN<N>) -> T,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Data = T;
+
+    fn for_each_value_count(&self, extent: &ExtentN<N>, mut f: impl FnMut(&T, usize) -> bool) {
+        for p in extent.iter_points() {
+            if !f(&(self)(&p), 1) {
+                return;
+            }
+        }
+    }
+}
+
+impl<'a, N, T, M> Reduce<N> for ChunkMapReader<'a, N, T, M>
+where
+    T: Copy,
+    M: Clone + ChunkSummary<N, T>,
+    PointN<N>: IntegerPoint + ChunkShape<N> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N> + Reduce<N, Data = T>,
+{
+    type Data = T;
+
+    fn for_each_value_count(&self, extent: &ExtentN<N>, mut f: impl FnMut(&T, usize) -> bool) {
+        for key in self.map.key_iter(extent) {
+            let overlap = self.map.extent_for_chunk_at_key(&key).intersection(extent);
+            let keep_going = match self.map.get_chunk(key, self.local_cache) {
+                None => f(self.map.ambient_value(), overlap.num_points()),
+                Some(chunk) => {
+                    // A dirty summary might be out of date.
+                    let uniform_value = if self.map.chunk_is_dirty(&key) {
+                        None
+                    } else {
+                        chunk.metadata.uniform_value()
+                    };
+                    match uniform_value {
+                        Some(v) => f(v, overlap.num_points()),
+                        None => {
+                            let mut keep_going = true;
+                            chunk.map.for_each_value_count(&overlap, |v, count| {
+                                keep_going = f(v, count);
+
+                                keep_going
+                            });
+
+                            keep_going
+                        }
+                    }
+                }
+            };
+            if !keep_going {
+                return;
+            }
+        }
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        chunk_summary::ValueRange, Array3, ChunkMap3, ChunkMapReader3, FastLz4, GetMut,
+        LocalChunkCache, WriteExtent,
+    };
+
+    #[test]
+    fn array_and_function_reductions_agree() {
+        let extent = Extent3i::from_min_and_shape(PointN([-4; 3]), PointN([8; 3]));
+        let f = |p: &Point3i| p.x() * p.y() - p.z();
+        let array = Array3::fill_with(extent, |p| f(p));
+
+        let query = Extent3i::from_min_and_shape(PointN([-2; 3]), PointN([5; 3]));
+        assert_eq!(array.min_max(&query), f.min_max(&query));
+        assert_eq!(array.min_max(&query), Some((-6, 6)));
+        assert_eq!(array.sum::<i64>(&query), f.sum::<i64>(&query));
+        assert_eq!(array.histogram(&query), f.histogram(&query));
+        assert_eq!(
+            array.count_if(&query, |v| *v > 0),
+            f.count_if(&query, |v| *v > 0)
+        );
+        assert!(array.any(&query, |v| *v == 6));
+        assert!(!f.all(&query, |v| *v == 6));
+
+        // Out of bounds points are ignored by arrays.
+        let outside = Extent3i::from_min_and_shape(PointN([10; 3]), PointN([2; 3]));
+        assert_eq!(array.min_max(&outside), None);
+        assert_eq!(array.count_if(&outside, |_| true), 0);
+
+        // Arrays stop visiting values as soon as `f` returns `false`.
+        let mut num_visited = 0;
+        array.for_each_value_count(&query, |_v, _count| {
+            num_visited += 1;
+
+            num_visited < 3
+        });
+        assert_eq!(num_visited, 3);
+    }
+
+    #[test]
+    fn chunk_map_reader_skips_ambient_and_uniform_chunks() {
+        let chunk_shape = PointN([4; 3]);
+        let mut map = ChunkMap3::new(
+            chunk_shape,
+            1u8,
+            ValueRange::default(),
+            FastLz4 { level: 10 },
+        );
+        let uniform = Extent3i::from_min_and_shape(PointN([0; 3]), chunk_shape);
+        map.write_extent(&uniform, |_p: &_| 3);
+        *map.get_mut(&PointN([4, 0, 0])) = 5;
+        map.update_chunk_summaries();
+
+        let extent = Extent3i::from_min_and_shape(PointN([-4; 3]), PointN([12; 3]));
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&map, &local_cache);
+
+        let mut num_visits = 0;
+        reader.for_each_value_count(&extent, |_v, _count| {
+            num_visits += 1;
+
+            true
+        });
+        // One visit per ambient or uniform chunk, plus the 64 points of the mixed chunk.
+        assert_eq!(num_visits, 26 + 64);
+
+        assert_eq!(reader.min_max(&extent), Some((1, 5)));
+        assert_eq!(reader.sum::<u32>(&extent), 12 * 12 * 12 + 2 * 64 + 4);
+        let histogram = reader.histogram(&extent);
+        assert_eq!(histogram.get(&3), Some(&64));
+        assert_eq!(histogram.get(&5), Some(&1));
+        assert_eq!(reader.count_if(&extent, |v| *v == 1), 12 * 12 * 12 - 65);
+
+        let mut num_visits = 0;
+        let found = reader.any(&extent, |v| {
+            num_visits += 1;
+            *v == 1
+        });
+        assert!(found);
+        assert_eq!(num_visits, 1);
+    }
+}
@@ -0,0 +1,366 @@
+//! Summed-area tables (a.k.a. integral images or volumes), for constant-time sums over extents.
+//!
+//! A `SummedAreaTable` stores, for every point `p` of an array's extent, the sum of all values in the
+//! box from the extent minimum to `p`. Then the sum over any sub-extent only takes `2^N` lookups.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, summed_area::SummedAreaTable3};
+//!
+//! let extent = Extent3i::from_min_and_shape(PointN([-8; 3]), PointN([16; 3]));
+//! let mut solid = Array3::fill_with(extent, |p| p.y() < 0);
+//!
+//! // Count the solid voxels in any box.
+//! let mut table = SummedAreaTable3::from_array_with(&solid, |s| *s as u32);
+//! let query = Extent3i::from_min_and_shape(PointN([0, -2, 0]), PointN([4; 3]));
+//! assert_eq!(table.sum(&query), 2 * 4 * 4);
+//!
+//! // Average density is just the sum divided by the volume.
+//! let density = table.sum(&query) as f32 / query.num_points() as f32;
+//! assert_eq!(density, 0.5);
+//!
+//! // After an edit, only the part of the table that depends on the edited extent is recomputed.
+//! let edit = Extent3i::from_min_and_shape(PointN([2, 0, 2]), PointN([1; 3]));
+//! solid.for_each_mut(&edit, |_s: Stride, v| *v = true);
+//! table.update_extent_with(&solid, &edit, |s| *s as u32);
+//! assert_eq!(table.sum(&query), 2 * 4 * 4 + 1);
+//! ```
+//!
+//! A `SummedAreaTable` can also be used as the `ChunkSummary` metadata of a `ChunkMap`, keeping one
+//! table per chunk. The tables are only rebuilt for chunks that have changed.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::{prelude::*, summed_area::SummedAreaTable3};
+//! let mut map = ChunkMap3::new(
+//!     PointN([16; 3]), 1u32, SummedAreaTable3::<u32>::default(), FastLz4 { level: 10 }
+//! );
+//! *map.get_mut(&PointN([1; 3])) = 5;
+//!
+//! // The missing chunks contribute the ambient value at every point.
+//! let query = Extent3i::from_min_and_shape(PointN([-2; 3]), PointN([4; 3]));
+//! assert_eq!(map.sum_extent(&query), 64 + 4);
+//! ```
+
+use crate::{
+    access::{GetUnchecked, GetUncheckedMut, GetUncheckedMutRelease, GetUncheckedRelease},
+    array_view::extent_is_empty,
+    chunk_map::ChunkShape,
+    Array, ArrayN, ChunkMap, ChunkSummary, Get, GetMut, Stride,
+};
+
+use building_blocks_core::prelude::*;
+
+use core::hash::Hash;
+use core::ops::{Add, Mul, Sub};
+use num::{NumCast, Zero};
+use serde::{Deserialize, Serialize};
+
+/// For each point `p` in its extent, stores the sum of the source values over the extent from the
+/// minimum to `p` (inclusive).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SummedAreaTable<N, S> {
+    table: ArrayN<N, S>,
+}
+
+pub type SummedAreaTable2<S> = SummedAreaTable<[i32; 2], S>;
+pub type SummedAreaTable3<S> = SummedAreaTable<[i32; 3], S>;
+
+impl<N, S> Default for SummedAreaTable<N, S>
+where
+    PointN<N>: IntegerPoint,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// An empty table, e.g. as the default metadata of new chunks.
+    fn default() -> Self {
+        Self {
+            table: ArrayN::new(
+                ExtentN::from_min_and_shape(PointN::zero(), PointN::zero()),
+                Vec::new(),
+            ),
+        }
+    }
+}
+
+impl<N, S> SummedAreaTable<N, S>
+where
+    S: Copy + Add<Output = S> + Sub<Output = S> + Zero,
+    ArrayN<N, S>: Array<N>
+        + for<'r> Get<&'r PointN<N>, Data = S>
+        + Get<Stride, Data = S>
+        + GetUnchecked<Stride, Data = S>
+        + GetMut<Stride, Data = S>
+        + GetUncheckedMut<Stride, Data = S>,
+    PointN<N>: IntegerPoint<Scalar = i32> + DotProduct<Scalar = i32>,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// Builds the table for `array`, where each value is converted into a summand with `to_sum`.
+    pub fn from_array_with<T>(array: &ArrayN<N, T>, to_sum: impl Fn(&T) -> S) -> Self
+    where
+        ArrayN<N, T>: Array<N> + for<'r> Get<&'r PointN<N>, Data = T>,
+        T: Clone,
+    {
+        let mut table = Self {
+            table: ArrayN::fill(*array.extent(), S::zero()),
+        };
+        table.update_extent_with(array, array.extent(), to_sum);
+
+        table
+    }
+
+    /// Builds the table for `array` by converting each value into `S`.
+    pub fn from_array<T>(array: &ArrayN<N, T>) -> Self
+    where
+        ArrayN<N, T>: Array<N> + for<'r> Get<&'r PointN<N>, Data = T>,
+        T: Clone + Into<S>,
+    {
+        Self::from_array_with(array, |v| v.clone().into())
+    }
+
+    /// The extent of the source array.
+    pub fn extent(&self) -> &ExtentN<N> {
+        self.table.extent()
+    }
+
+    /// The sum over all points of `extent` that are also in this table's extent.
+    pub fn sum(&self, extent: &ExtentN<N>) -> S {
+        let extent = extent.intersection(self.extent());
+        if extent_is_empty(&extent) {
+            return S::zero();
+        }
+
+        // Accumulate the positive and negative terms separately so unsigned sums can't underflow.
+        let max = extent.max();
+        let mut positive = S::zero();
+        let mut negative = S::zero();
+        for c in PointN::corner_offsets().into_iter() {
+            let corner = max - c * extent.shape;
+            if !self.extent().contains(&corner) {
+                continue;
+            }
+            let value = self.table.get(&corner);
+            if c.dot(&PointN::ONES) % 2 == 0 {
+                positive = positive + value;
+            } else {
+                negative = negative + value;
+            }
+        }
+
+        positive - negative
+    }
+
+    /// Recomputes the table after the values of `array` have changed, but only within `extent`.
+    /// `array` must have the same extent as this table.
+    pub fn update_extent_with<T>(
+        &mut self,
+        array: &ArrayN<N, T>,
+        extent: &ExtentN<N>,
+        to_sum: impl Fn(&T) -> S,
+    ) where
+        ArrayN<N, T>: Array<N> + for<'r> Get<&'r PointN<N>, Data = T>,
+        T: Clone,
+    {
+        assert!(
+            array.extent().minimum == self.extent().minimum
+                && array.extent().shape == self.extent().shape
+        );
+
+        // Every sum over a box that contains part of `extent` must be recomputed.
+        let table_extent = *self.extent();
+        let dirty_extent =
+            ExtentN::from_min_and_lub(extent.minimum, table_extent.least_upper_bound())
+                .intersection(&table_extent);
+        if extent_is_empty(&dirty_extent) {
+            return;
+        }
+
+        // S(p) = v(p) + sum over nonzero corner offsets c of (-1)^(|c|+1) S(p - c)
+        let corners: Vec<_> = PointN::corner_offsets()
+            .into_iter()
+            .filter(|c| *c != PointN::zero())
+            .collect();
+        let negative_corners: Vec<_> = corners.iter().map(|c| *c * -1).collect();
+        let mut corner_strides = vec![Stride(0); corners.len()];
+        self.table
+            .strides_from_points(&negative_corners, &mut corner_strides);
+        let corner_adds: Vec<_> = corners
+            .iter()
+            .map(|c| c.dot(&PointN::ONES) % 2 == 1)
+            .collect();
+
+        let table = &mut self.table;
+        ArrayN::<N, S>::for_each_point_and_stride(&table_extent, &dirty_extent, |p, s| {
+            let mut positive = to_sum(&array.get(&p));
+            let mut negative = S::zero();
+            for ((c, c_stride), add) in corners
+                .iter()
+                .zip(corner_strides.iter())
+                .zip(corner_adds.iter())
+            {
+                if !table_extent.contains(&(p - *c)) {
+                    continue;
+                }
+                let value = table.get_unchecked_release(s + *c_stride);
+                if *add {
+                    positive = positive + value;
+                } else {
+                    negative = negative + value;
+                }
+            }
+            *table.get_unchecked_mut_release(s) = positive - negative;
+        });
+    }
+
+    /// Same as `update_extent_with`, converting each value into `S`.
+    pub fn update_extent<T>(&mut self, array: &ArrayN<N, T>, extent: &ExtentN<N>)
+    where
+        ArrayN<N, T>: Array<N> + for<'r> Get<&'r PointN<N>, Data = T>,
+        T: Clone + Into<S>,
+    {
+        self.update_extent_with(array, extent, |v| v.clone().into())
+    }
+}
+
+impl<N, T, S> ChunkSummary<N, T> for SummedAreaTable<N, S>
+where
+    Self: Clone,
+    T: Clone + Into<S>,
+    S: Copy + Add<Output = S> + Sub<Output = S> + Zero,
+    ArrayN<N, T>: Array<N> + for<'r> Get<&'r PointN<N>, Data = T>,
+    ArrayN<N, S>: Array<N>
+        + Get<Stride, Data = S>
+        + GetUnchecked<Stride, Data = S>
+        + GetMut<Stride, Data = S>
+        + GetUncheckedMut<Stride, Data = S>,
+    PointN<N>: IntegerPoint<Scalar = i32> + DotProduct<Scalar = i32>,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    fn summarize(&mut self, map: &ArrayN<N, T>) {
+        if self.extent().minimum == map.extent().minimum
+            && self.extent().shape == map.extent().shape
+        {
+            self.update_extent(map, map.extent());
+        } else {
+            *self = Self::from_array(map);
+        }
+    }
+}
+
+impl<N, T, S> ChunkMap<N, T, SummedAreaTable<N, S>>
+where
+    N: Clone,
+    T: Copy + Into<S>,
+    S: Copy + Add<Output = S> + Sub<Output = S> + Mul<Output = S> + NumCast + Zero,
+    SummedAreaTable<N, S>: ChunkSummary<N, T>,
+    ArrayN<N, S>: Array<N>
+        + for<'r> Get<&'r PointN<N>, Data = S>
+        + Get<Stride, Data = S>
+        + GetUnchecked<Stride, Data = S>
+        + GetMut<Stride, Data = S>
+        + GetUncheckedMut<Stride, Data = S>,
+    PointN<N>: IntegerPoint<Scalar = i32> + DotProduct<Scalar = i32> + ChunkShape<N> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    /// The sum of the values of all points in `extent`, using the per-chunk summed-area tables.
+    /// Points without a chunk contribute the ambient value.
+    pub fn sum_extent(&mut self, extent: &ExtentN<N>) -> S {
+        let ambient: S = (*self.ambient_value()).into();
+
+        let mut sum = S::zero();
+        for key in self.key_iter(extent).collect::<Vec<_>>().into_iter() {
+            let chunk_extent = self.extent_for_chunk_at_key(&key);
+            let overlap = chunk_extent.intersection(extent);
+            match self.update_chunk_summary(key) {
+                Some(table) => sum = sum + table.sum(&overlap),
+                None => {
+                    let num_points: S = NumCast::from(overlap.num_points()).unwrap();
+                    sum = sum + ambient * num_points;
+                }
+            }
+        }
+
+        sum
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Array2, Array3, ChunkMap3, FastLz4, ForEachMut, ForEachRef};
+
+    fn brute_force_sum<N, T>(array: &ArrayN<N, T>, extent: &ExtentN<N>) -> i64
+    where
+        T: Copy + Into<i64>,
+        ArrayN<N, T>: ForEachRef<N, Stride, Data = T>,
+    {
+        let mut sum = 0;
+        array.for_each_ref(extent, |_s: Stride, v| sum += (*v).into());
+
+        sum
+    }
+
+    #[test]
+    fn sums_match_brute_force() {
+        let extent = Extent3i::from_min_and_shape(PointN([-3, 2, 1]), PointN([7, 5, 6]));
+        let array = Array3::fill_with(extent, |p| (p.x() * 7 + p.y() * 3 - p.z()) % 5);
+        let table = SummedAreaTable3::<i64>::from_array(&array);
+
+        let queries = [
+            extent,
+            Extent3i::from_min_and_shape(PointN([-3, 2, 1]), PointN([1; 3])),
+            Extent3i::from_min_and_shape(PointN([0, 3, 2]), PointN([2, 1, 3])),
+            Extent3i::from_min_and_shape(PointN([2, 5, 5]), PointN([10; 3])),
+            Extent3i::from_min_and_shape(PointN([100; 3]), PointN([1; 3])),
+        ];
+        for q in queries.iter() {
+            assert_eq!(table.sum(q), brute_force_sum(&array, q));
+        }
+
+        let extent = Extent2i::from_min_and_shape(PointN([0; 2]), PointN([4, 3]));
+        let array = Array2::fill(extent, 2i32);
+        let table = SummedAreaTable::<_, i64>::from_array(&array);
+        let q = Extent2i::from_min_and_shape(PointN([1; 2]), PointN([2; 2]));
+        assert_eq!(table.sum(&q), 8);
+    }
+
+    #[test]
+    fn incremental_update_matches_rebuild() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([6; 3]));
+        let mut array = Array3::fill_with(extent, |p| p.x() + p.y() * p.z());
+        let mut table = SummedAreaTable3::<i64>::from_array(&array);
+
+        let edit = Extent3i::from_min_and_shape(PointN([2, 3, 1]), PointN([2, 1, 2]));
+        array.for_each_mut(&edit, |_s: Stride, v| *v = -10);
+        table.update_extent(&array, &edit);
+
+        let rebuilt = SummedAreaTable3::<i64>::from_array(&array);
+        assert_eq!(table.table, rebuilt.table);
+    }
+
+    #[test]
+    fn chunk_map_sums_use_ambient_for_missing_chunks() {
+        let mut map = ChunkMap3::new(
+            PointN([4; 3]),
+            2i32,
+            SummedAreaTable3::<i64>::default(),
+            FastLz4 { level: 10 },
+        );
+        let write = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([6; 3]));
+        map.fill_extent(&write, 3);
+
+        let query = Extent3i::from_min_and_shape(PointN([-2; 3]), PointN([10; 3]));
+        let expected = 3 * 216 + 2 * (1000 - 216);
+        assert_eq!(map.sum_extent(&query), expected);
+
+        *map.get_mut(&PointN([7; 3])) = 0;
+        assert_eq!(map.sum_extent(&query), expected - 2);
+    }
+}
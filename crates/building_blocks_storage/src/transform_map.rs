@@ -37,18 +37,38 @@
 //! let tfm = TransformMap::new(&src, &|value: i32| value + 1);
 //! copy_extent(&extent, &tfm, &mut dst);
 //! ```
+//!
+//! `TransformMap` borrows its delegate and transform. When you need to return a transformed map
+//! from a function or combine maps, the `TransformExt` combinators take ownership instead, and
+//! `ZipMap` pairs up the values of two maps:
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::prelude::*;
+//! # let extent = Extent3::from_min_and_shape(PointN([0; 3]), PointN([16; 3]));
+//! let density = Array3::fill(extent, -1.0);
+//! let material = Array3::fill(extent, 3u8);
+//! let solid_material = density
+//!     .zip(material)
+//!     .map(|(d, m): (f32, u8)| if d < 0.0 { m } else { 0 });
+//! let mut dst = Array3::fill(extent, 0);
+//! copy_extent(&extent, &solid_material, &mut dst);
+//! assert_eq!(dst.get(&PointN([1; 3])), 3);
+//! ```
 
 use crate::{
     access::{GetUnchecked, GetUncheckedRef},
     array::ArrayCopySrc,
     chunk_map::{AmbientExtent, ArrayChunkCopySrc, ArrayChunkCopySrcIter, ChunkCopySrc},
-    ArrayExtent, ArrayN, ChunkMapReader, ForEachRef, Get, GetRef, ReadExtent,
+    ArrayExtent, ArrayN, ChunkMapReader, ForEachRef, Get, GetRef, ReadExtent, Stride,
 };
 
 use building_blocks_core::prelude::*;
 
 use core::hash::Hash;
 use core::iter::{once, Once};
+use core::ops::Deref;
+use either::Either;
 
 /// A lattice map that delegates look-ups to a different lattice map, then transforms the result
 /// using some `Fn(T) -> S`.
@@ -228,6 +248,339 @@ where
     }
 }
 
+// ██████╗ ██╗    ██╗███╗   ██╗███████╗██████╗
+// ██╔═══██╗██║    ██║████╗  ██║██╔════╝██╔══██╗
+// ██║   ██║██║ █╗ ██║██╔██╗ ██║█████╗  ██║  ██║
+// ██║   ██║██║███╗██║██║╚██╗██║██╔══╝  ██║  ██║
+// ╚██████╔╝╚███╔███╔╝██║ ╚████║███████╗██████╔╝
+//  ╚═════╝  ╚══╝╚══╝ ╚═╝  ╚═══╝╚══════╝╚═════╝
+
+/// Like `TransformMap`, but it owns both the delegate lattice map and the transform, so it can be
+/// returned from functions and stored in structs. Usually constructed with `TransformExt::map`.
+#[derive(Clone, Copy)]
+pub struct OwnedTransformMap<M, F> {
+    delegate: M,
+    transform: F,
+}
+
+impl<M, F> OwnedTransformMap<M, F> {
+    pub fn new(delegate: M, transform: F) -> Self {
+        Self {
+            delegate,
+            transform,
+        }
+    }
+
+    pub fn delegate(&self) -> &M {
+        &self.delegate
+    }
+
+    pub fn into_parts(self) -> (M, F) {
+        (self.delegate, self.transform)
+    }
+}
+
+impl<M, F, T, S, Coord> Get<Coord> for OwnedTransformMap<M, F>
+where
+    F: Fn(T) -> S,
+    M: Get<Coord, Data = T>,
+{
+    type Data = S;
+
+    fn get(&self, c: Coord) -> S {
+        (self.transform)(self.delegate.get(c))
+    }
+}
+
+impl<M, F, T, S, Coord> GetUnchecked<Coord> for OwnedTransformMap<M, F>
+where
+    F: Fn(T) -> S,
+    M: GetUnchecked<Coord, Data = T>,
+{
+    type Data = S;
+
+    unsafe fn get_unchecked(&self, c: Coord) -> S {
+        (self.transform)(self.delegate.get_unchecked(c))
+    }
+}
+
+impl<N, M, F> ArrayExtent<N> for OwnedTransformMap<M, F>
+where
+    M: ArrayExtent<N>,
+{
+    fn extent(&self) -> &ExtentN<N> {
+        self.delegate.extent()
+    }
+}
+
+impl<M, F> Deref for ArrayCopySrc<&OwnedTransformMap<M, F>> {
+    type Target = OwnedTransformMap<M, F>;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+// Same problem as for `TransformMap`; without a generic impl, we cover each kind of array-like
+// delegate.
+macro_rules! impl_owned_transform_read_extent {
+    (<$($gen:ident),*> $delegate:ty) => {
+        impl<'a, N, F, $($gen),*> ReadExtent<'a, N> for OwnedTransformMap<$delegate, F>
+        where
+            Self: 'a + ArrayExtent<N>,
+            PointN<N>: IntegerPoint,
+        {
+            type Src = ArrayCopySrc<&'a Self>;
+            type SrcIter = Once<(ExtentN<N>, Self::Src)>;
+
+            fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+                let in_bounds_extent = self.extent().intersection(extent);
+
+                once((in_bounds_extent, ArrayCopySrc(self)))
+            }
+        }
+    };
+}
+
+impl_owned_transform_read_extent!(<S> ArrayN<N, S>);
+impl_owned_transform_read_extent!(<M, G> OwnedTransformMap<M, G>);
+
+impl<'a, F, S, N, T, M> ReadExtent<'a, N> for OwnedTransformMap<ChunkMapReader<'a, N, S, M>, F>
+where
+    ChunkMapReader<'a, N, S, M>: ReadExtent<
+        'a,
+        N,
+        Src = ArrayChunkCopySrc<'a, N, S>,
+        SrcIter = ArrayChunkCopySrcIter<'a, N, S>,
+    >,
+    F: 'a + Fn(S) -> T,
+    S: Copy,
+    T: 'a,
+    M: Clone,
+    PointN<N>: Point + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Src = TransformChunkCopySrc<'a, F, S, N, T>;
+    type SrcIter = TransformChunkCopySrcIter<'a, F, S, N, T>;
+
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        TransformChunkCopySrcIter {
+            chunk_iter: self.delegate.read_extent(extent),
+            transform: &self.transform,
+        }
+    }
+}
+
+// ███████╗██╗██████╗
+// ╚══███╔╝██║██╔══██╗
+//   ███╔╝ ██║██████╔╝
+//  ███╔╝  ██║██╔═══╝
+// ███████╗██║██║
+// ╚══════╝╚═╝╚═╝
+
+/// A lattice map that pairs the values of two lattice maps pointwise, e.g. density and material.
+///
+/// `ReadExtent` is supported for fast copies when both maps are arrays or both are
+/// `ChunkMapReader`s. Only the intersection of the two maps is copied. The maps don't need to share
+/// an extent or chunk shape, since each half of the pair is copied separately.
+///
+/// `Get<Stride>` uses the same `Stride` for both maps, so it only makes sense when both arrays
+/// have the same extent. For the same reason, a `ZipMap` is not an `ArrayExtent`; a mapped zip
+/// (`zip(..).map(..)`) is copied through a `MapZipCopySrc`, which looks up each half separately.
+#[derive(Clone, Copy)]
+pub struct ZipMap<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> ZipMap<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+
+    pub fn into_parts(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A, B, Coord> Get<Coord> for ZipMap<A, B>
+where
+    A: Get<Coord>,
+    B: Get<Coord>,
+    Coord: Copy,
+{
+    type Data = (A::Data, B::Data);
+
+    fn get(&self, c: Coord) -> Self::Data {
+        (self.a.get(c), self.b.get(c))
+    }
+}
+
+impl<'a, N, A, B> ReadExtent<'a, N> for ZipMap<A, B>
+where
+    Self: 'a,
+    N: 'a,
+    A: ArrayExtent<N> + Get<Stride>,
+    B: ArrayExtent<N> + Get<Stride>,
+    ArrayCopySrc<&'a A>: Deref<Target = A>,
+    ArrayCopySrc<&'a B>: Deref<Target = B>,
+    PointN<N>: IntegerPoint,
+{
+    type Src = ZipArrayCopySrc<'a, N, A, B>;
+    type SrcIter = Once<(ExtentN<N>, Self::Src)>;
+
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        let in_bounds_extent = self
+            .a
+            .extent()
+            .intersection(self.b.extent())
+            .intersection(extent);
+
+        once((
+            in_bounds_extent,
+            ZipCopySrc {
+                a: Either::Left(ArrayCopySrc(&self.a)),
+                b: Either::Left(ArrayCopySrc(&self.b)),
+            },
+        ))
+    }
+}
+
+impl<'a, N, A, B, Ma, Mb> ReadExtent<'a, N>
+    for ZipMap<ChunkMapReader<'a, N, A, Ma>, ChunkMapReader<'a, N, B, Mb>>
+where
+    ChunkMapReader<'a, N, A, Ma>: ReadExtent<
+        'a,
+        N,
+        Src = ArrayChunkCopySrc<'a, N, A>,
+        SrcIter = ArrayChunkCopySrcIter<'a, N, A>,
+    >,
+    ChunkMapReader<'a, N, B, Mb>: ReadExtent<
+        'a,
+        N,
+        Src = ArrayChunkCopySrc<'a, N, B>,
+        SrcIter = ArrayChunkCopySrcIter<'a, N, B>,
+    >,
+    ArrayChunkCopySrc<'a, N, A>: Clone,
+    A: Copy,
+    B: Copy,
+    Ma: Clone,
+    Mb: Clone,
+    PointN<N>: Point + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    type Src = ZipChunkCopySrc<'a, N, A, B>;
+    type SrcIter = std::vec::IntoIter<(ExtentN<N>, Self::Src)>;
+
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        // The chunks of `b` are only read where they overlap a chunk of `a`, so each pair of chunk
+        // sources covers the same extent.
+        let mut srcs = Vec::new();
+        for (a_extent, a_src) in self.a.read_extent(extent) {
+            for (ab_extent, b_src) in self.b.read_extent(&a_extent) {
+                srcs.push((
+                    ab_extent,
+                    ZipCopySrc {
+                        a: a_src.clone(),
+                        b: b_src,
+                    },
+                ));
+            }
+        }
+
+        srcs.into_iter()
+    }
+}
+
+/// A copy source that pairs the values of two copy sources. When copying into an array of pairs,
+/// each half is copied separately, so the sources don't need to share an extent.
+#[derive(Clone, Copy)]
+pub struct ZipCopySrc<A, B> {
+    pub a: A,
+    pub b: B,
+}
+
+pub type ZipArrayCopySrc<'a, N, A, B> = ZipCopySrc<
+    ChunkCopySrc<&'a A, N, <A as Get<Stride>>::Data>,
+    ChunkCopySrc<&'a B, N, <B as Get<Stride>>::Data>,
+>;
+
+pub type ZipChunkCopySrc<'a, N, A, B> =
+    ZipCopySrc<ArrayChunkCopySrc<'a, N, A>, ArrayChunkCopySrc<'a, N, B>>;
+
+// Each chunk of a mapped zip is the zipped source of that chunk, with the transform applied when
+// the pairs are written.
+impl<'a, N, A, B, F, Sa, Sb> ReadExtent<'a, N> for OwnedTransformMap<ZipMap<A, B>, F>
+where
+    F: 'a,
+    Sa: 'a,
+    Sb: 'a,
+    ZipMap<A, B>: ReadExtent<'a, N, Src = ZipCopySrc<Sa, Sb>>,
+{
+    type Src = MapZipCopySrc<ZipCopySrc<Sa, Sb>, &'a F>;
+    type SrcIter = MapZipCopySrcIter<'a, <ZipMap<A, B> as ReadExtent<'a, N>>::SrcIter, F>;
+
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        MapZipCopySrcIter {
+            zip_iter: self.delegate.read_extent(extent),
+            transform: &self.transform,
+        }
+    }
+}
+
+/// A copy source that applies `transform` to the pairs of values from a `ZipCopySrc`. Each half is
+/// looked up with its own layout, so the zipped maps don't need to share an extent.
+#[derive(Clone, Copy)]
+pub struct MapZipCopySrc<Z, F> {
+    pub zip: Z,
+    pub transform: F,
+}
+
+pub struct MapZipCopySrcIter<'a, I, F> {
+    zip_iter: I,
+    transform: &'a F,
+}
+
+impl<'a, I, N, Z, F> Iterator for MapZipCopySrcIter<'a, I, F>
+where
+    I: Iterator<Item = (ExtentN<N>, Z)>,
+{
+    type Item = (ExtentN<N>, MapZipCopySrc<Z, &'a F>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let transform = self.transform;
+
+        self.zip_iter
+            .next()
+            .map(|(extent, zip)| (extent, MapZipCopySrc { zip, transform }))
+    }
+}
+
+// ███████╗██╗  ██╗████████╗
+// ██╔════╝╚██╗██╔╝╚══██╔══╝
+// █████╗   ╚███╔╝    ██║
+// ██╔══╝   ██╔██╗    ██║
+// ███████╗██╔╝ ██╗   ██║
+// ╚══════╝╚═╝  ╚═╝   ╚═╝
+
+/// Combinators for any lattice map that can `Get` values by `&PointN<N>`.
+pub trait TransformExt<N>: Sized {
+    /// Transforms every value with `transform`, taking ownership of this map.
+    fn map<F, T, S>(self, transform: F) -> OwnedTransformMap<Self, F>
+    where
+        F: Fn(T) -> S,
+    {
+        OwnedTransformMap::new(self, transform)
+    }
+
+    /// Pairs every value of this map with the value of `other` at the same point.
+    fn zip<M>(self, other: M) -> ZipMap<Self, M> {
+        ZipMap::new(self, other)
+    }
+}
+
+impl<N, M> TransformExt<N> for M where M: for<'r> Get<&'r PointN<N>> {}
+
 // ████████╗███████╗███████╗████████╗
 // ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
 //    ██║   █████╗  ███████╗   ██║
@@ -290,4 +643,162 @@ mod tests {
         let mut dst = ChunkMap3::new(PointN([2; 3]), 0, (), FastLz4 { level: 10 });
         copy_extent(&dst_extent, &tfm, &mut dst);
     }
+
+    fn density_to_solid(
+        density: Array3<f32>,
+    ) -> OwnedTransformMap<Array3<f32>, impl Fn(f32) -> bool> {
+        density.map(|d: f32| d < 0.0)
+    }
+
+    #[test]
+    fn copy_from_owned_and_chained_maps() {
+        let extent = Extent3::from_min_and_shape(PointN([0; 3]), PointN([8; 3]));
+        let density = Array3::fill_with(extent, |p: &Point3i| p.x() as f32 - 3.5);
+
+        let solid = density_to_solid(density);
+        assert!(solid.get(&PointN([0; 3])));
+        assert!(!solid.get(&PointN([4, 0, 0])));
+
+        let solid_u8 = solid.map(|s: bool| s as u8);
+        let mut dst = Array3::fill(extent, 9);
+        copy_extent(&extent, &solid_u8, &mut dst);
+        dst.for_each_ref(&extent, |p: Point3i, value| {
+            assert_eq!(*value, (p.x() < 4) as u8);
+        });
+    }
+
+    #[test]
+    fn copy_from_zipped_arrays() {
+        let extent = Extent3::from_min_and_shape(PointN([0; 3]), PointN([8; 3]));
+        let density = Array3::fill_with(extent, |p: &Point3i| p.y() as f32 - 3.5);
+        let material = Array3::fill_with(extent, |p: &Point3i| p.z() as u8);
+
+        let zipped = density.zip(material);
+        assert_eq!(zipped.get(&PointN([1, 2, 3])), (-1.5, 3));
+
+        let mut dst = Array3::fill(extent, (0.0, 0));
+        copy_extent(&extent, &zipped, &mut dst);
+        dst.for_each_ref(&extent, |p: Point3i, value| {
+            assert_eq!(*value, (p.y() as f32 - 3.5, p.z() as u8));
+        });
+
+        let visible_material = zipped.map(|(d, m): (f32, u8)| if d < 0.0 { m } else { 0 });
+        let mut dst = ChunkMap3::new(PointN([4; 3]), 0, (), FastLz4 { level: 10 });
+        copy_extent(&extent, &visible_material, &mut dst);
+        assert_eq!(*dst.get_mut(&PointN([0, 0, 5])), 5);
+        assert_eq!(*dst.get_mut(&PointN([0, 5, 5])), 0);
+    }
+
+    #[test]
+    fn copy_from_mapped_zip_of_arrays_with_different_extents() {
+        let a_extent = Extent3::from_min_and_shape(PointN([0; 3]), PointN([8; 3]));
+        let b_extent = Extent3::from_min_and_shape(PointN([4; 3]), PointN([2; 3]));
+        let a = Array3::fill_with(a_extent, |p: &Point3i| p.x());
+        let b = Array3::fill_with(b_extent, |p: &Point3i| p.y());
+        let mapped = a.zip(b).map(|(x, y): (i32, i32)| x * 1000 + y);
+
+        let mut dst = Array3::fill(a_extent, -1);
+        copy_extent(&a_extent, &mapped, &mut dst);
+        dst.for_each_ref(&a_extent, |p: Point3i, value| {
+            if b_extent.contains(&p) {
+                assert_eq!(*value, p.x() * 1000 + p.y());
+            } else {
+                assert_eq!(*value, -1);
+            }
+        });
+
+        let mut dst = ChunkMap3::new(PointN([4; 3]), -1, (), FastLz4 { level: 10 });
+        copy_extent(&a_extent, &mapped, &mut dst);
+        let local_cache = LocalChunkCache::new();
+        let reader = ChunkMapReader3::new(&dst, &local_cache);
+        reader.for_each_ref(&a_extent, |p: Point3i, value| {
+            if b_extent.contains(&p) {
+                assert_eq!(*value, p.x() * 1000 + p.y());
+            } else {
+                assert_eq!(*value, -1);
+            }
+        });
+    }
+
+    #[test]
+    fn copy_from_zipped_arrays_with_different_extents() {
+        let a_extent = Extent3::from_min_and_shape(PointN([0; 3]), PointN([8; 3]));
+        let b_extent = Extent3::from_min_and_shape(PointN([2; 3]), PointN([8; 3]));
+        let a = Array3::fill_with(a_extent, |p: &Point3i| p.x());
+        let b = Array3::fill_with(b_extent, |p: &Point3i| p.y() as u8);
+        let zipped = a.zip(b);
+
+        let dst_extent = Extent3::from_min_and_shape(PointN([-4; 3]), PointN([16; 3]));
+        let mut dst = Array3::fill(dst_extent, (-1, 0));
+        copy_extent(&dst_extent, &zipped, &mut dst);
+        let overlap = a_extent.intersection(&b_extent);
+        dst.for_each_ref(&dst_extent, |p: Point3i, value| {
+            if overlap.contains(&p) {
+                assert_eq!(*value, (p.x(), p.y() as u8));
+            } else {
+                assert_eq!(*value, (-1, 0));
+            }
+        });
+    }
+
+    #[test]
+    fn copy_from_zipped_chunk_map_readers() {
+        let extent = Extent3::from_min_and_shape(PointN([0; 3]), PointN([8; 3]));
+        let mut density = ChunkMap3::new(PointN([4; 3]), 1.0, (), FastLz4 { level: 10 });
+        copy_extent(
+            &extent,
+            &Array3::fill_with(extent, |p: &Point3i| p.x() as f32),
+            &mut density,
+        );
+        // A different chunk shape, and some chunks are left ambient.
+        let material_extent = Extent3::from_min_and_shape(PointN([0; 3]), PointN([8, 8, 2]));
+        let mut material = ChunkMap3::new(PointN([2; 3]), 0u8, (), FastLz4 { level: 10 });
+        copy_extent(
+            &material_extent,
+            &Array3::fill_with(material_extent, |p: &Point3i| p.y() as u8 + 1),
+            &mut material,
+        );
+
+        let density_cache = LocalChunkCache::new();
+        let material_cache = LocalChunkCache::new();
+        let zipped = ZipMap::new(
+            ChunkMapReader3::new(&density, &density_cache),
+            ChunkMapReader3::new(&material, &material_cache),
+        );
+
+        let dst_extent = Extent3::from_min_and_shape(PointN([-2; 3]), PointN([12; 3]));
+        let mut dst = Array3::fill(dst_extent, (0.0, 0));
+        copy_extent(&dst_extent, &zipped, &mut dst);
+        dst.for_each_ref(&dst_extent, |p: Point3i, value| {
+            let d = if extent.contains(&p) {
+                p.x() as f32
+            } else {
+                1.0
+            };
+            let m = if material_extent.contains(&p) {
+                p.y() as u8 + 1
+            } else {
+                0
+            };
+            assert_eq!(*value, (d, m));
+        });
+    }
+
+    #[test]
+    fn copy_from_owned_transform_of_chunk_map_reader() {
+        let src_extent = Extent3::from_min_and_shape(PointN([0; 3]), PointN([16; 3]));
+        let src_array = Array3::fill(src_extent, 1);
+        let mut src = ChunkMap3::new(PointN([4; 3]), 0, (), FastLz4 { level: 10 });
+        copy_extent(&src_extent, &src_array, &mut src);
+
+        let local_cache = LocalChunkCache::new();
+        let tfm = ChunkMapReader3::new(&src, &local_cache).map(|value: i32| value + 1);
+
+        let dst_extent = Extent3::from_min_and_shape(PointN([-16; 3]), PointN([32; 3]));
+        let mut dst = Array3::fill(dst_extent, 0);
+        copy_extent(&dst_extent, &tfm, &mut dst);
+        dst.for_each_ref(&dst_extent, |p: Point3i, value| {
+            assert_eq!(*value, if src_extent.contains(&p) { 2 } else { 1 });
+        });
+    }
 }
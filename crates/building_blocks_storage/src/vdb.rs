@@ -0,0 +1,595 @@
+//! A hierarchical sparse lattice map, in the style of OpenVDB.
+//!
+//! A `VdbMap` has 3 levels:
+//!   - a root hash map from node keys to either a tile value or an internal node
+//!   - internal nodes, which have a child mask and either a tile value or a leaf for each slot
+//!   - leaves, which are dense `ArrayN` bricks
+//!
+//! Any region that has the same value everywhere can be stored as a single tile value at the
+//! coarsest level that fits, so huge, mostly uniform volumes only pay for the leaves that actually
+//! have detail. Points that aren't covered by the root take the background value.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, VdbMap3};
+//!
+//! let mut map = VdbMap3::new(0);
+//!
+//! // Filling a large extent only creates tiles, except on the boundary.
+//! let solid = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([256, 256, 100]));
+//! map.fill_extent(&solid, 1);
+//! assert_eq!(map.get(&PointN([200, 200, 50])), 1);
+//! assert_eq!(map.get(&PointN([0, 0, 100])), 0);
+//!
+//! // Writing a single point creates a single leaf.
+//! let leaves_before = map.num_leaves();
+//! *map.get_mut(&PointN([-1000; 3])) = 2;
+//! assert_eq!(map.num_leaves(), leaves_before + 1);
+//!
+//! // The map can be copied to and from any other lattice map, like an array for meshing.
+//! let query = Extent3i::from_min_and_shape(PointN([-8, -8, 90]), PointN([32; 3]));
+//! let mut dense = Array3::fill(query, 0);
+//! copy_extent(&query, &map, &mut dense);
+//! assert_eq!(dense.get(&PointN([0, 0, 99])), 1);
+//!
+//! // Leaves that became uniform can be collapsed back into tiles.
+//! map.fill_extent(&Extent3i::from_min_and_shape(PointN([-1000; 3]), PointN([1; 3])), 0);
+//! map.prune();
+//! assert_eq!(map.num_leaves(), leaves_before);
+//! ```
+
+use crate::{
+    array::ArrayCopySrc,
+    array_view::extent_is_empty,
+    chunk_map::{AmbientExtent, ArrayChunkCopySrc, ArrayChunkCopySrcIter, ChunkCopySrc},
+    Array, ArrayN, BitArrayN, ForEachMut, ForEachRef, Get, GetMut, GetRef, Local, ReadExtent,
+    Stride, WriteExtent,
+};
+
+use building_blocks_core::prelude::*;
+
+use core::hash::Hash;
+use either::Either;
+use fnv::FnvHashMap;
+use num::Zero;
+
+/// Log2 of the edge length of a leaf.
+pub const LEAF_LOG2: i32 = 3;
+/// Log2 of the number of leaves along each edge of an internal node.
+pub const NODE_LOG2: i32 = 4;
+
+const NODE_TOTAL_LOG2: i32 = LEAF_LOG2 + NODE_LOG2;
+
+/// A sparse map from lattice location `PointN<N>` to data `T`, stored in a shallow tree of tiles and
+/// dense leaves. See the module docs for the layout.
+pub struct VdbMap<N, T>
+where
+    PointN<N>: Eq + Hash,
+{
+    background: T,
+    root: FnvHashMap<PointN<N>, RootChild<N, T>>,
+}
+
+pub type VdbMap2<T> = VdbMap<[i32; 2], T>;
+pub type VdbMap3<T> = VdbMap<[i32; 3], T>;
+
+enum RootChild<N, T> {
+    Tile(T),
+    Node(Box<InternalNode<N, T>>),
+}
+
+// Slots are addressed in local coordinates, in units of leaves.
+struct InternalNode<N, T> {
+    child_mask: BitArrayN<N>,
+    tiles: ArrayN<N, T>,
+    leaves: Vec<Option<Box<ArrayN<N, T>>>>,
+}
+
+enum Region<'a, N, T> {
+    Tile(&'a T),
+    Leaf(&'a ArrayN<N, T>),
+}
+
+impl<N, T> InternalNode<N, T>
+where
+    T: Clone,
+    PointN<N>: IntegerPoint<Scalar = i32>,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N>,
+{
+    fn new(tile_value: T) -> Self {
+        let slot_extent =
+            ExtentN::from_min_and_shape(PointN::zero(), PointN::ONES.left_shift(NODE_LOG2));
+        let num_slots = slot_extent.num_points();
+
+        Self {
+            child_mask: BitArrayN::fill(slot_extent, false),
+            tiles: ArrayN::fill(slot_extent, tile_value),
+            leaves: (0..num_slots).map(|_| None).collect(),
+        }
+    }
+
+    fn slot(&self, node_key: &PointN<N>, leaf_key: &PointN<N>) -> Stride {
+        let local = (*leaf_key - *node_key).right_shift(LEAF_LOG2);
+
+        ArrayN::<N, T>::stride_from_point(&self.tiles.extent().shape, &local)
+    }
+
+    fn region(&self, slot: Stride) -> Region<'_, N, T> {
+        if self.child_mask.get(slot) {
+            Region::Leaf(self.leaves[slot.0].as_ref().unwrap())
+        } else {
+            Region::Tile(self.tiles.get_ref(slot))
+        }
+    }
+
+    fn leaf_mut(&mut self, slot: Stride, leaf_key: PointN<N>) -> &mut ArrayN<N, T> {
+        if !self.child_mask.get(slot) {
+            let leaf = ArrayN::fill(leaf_extent(leaf_key), self.tiles.get_ref(slot).clone());
+            self.leaves[slot.0] = Some(Box::new(leaf));
+            self.child_mask.set(slot, true);
+        }
+
+        self.leaves[slot.0].as_mut().unwrap()
+    }
+
+    fn set_tile(&mut self, slot: Stride, value: T) {
+        *self.tiles.get_mut(slot) = value;
+        self.child_mask.set(slot, false);
+        self.leaves[slot.0] = None;
+    }
+
+    // Collapses uniform leaves into tiles. Returns the tile value if the entire node is uniform.
+    fn prune(&mut self) -> Option<T>
+    where
+        T: PartialEq,
+    {
+        for i in 0..self.leaves.len() {
+            let slot = Stride(i);
+            if let Some(value) = self.leaves[i].as_ref().and_then(|leaf| uniform_value(leaf)) {
+                self.set_tile(slot, value);
+            }
+        }
+
+        if self.child_mask.any() {
+            return None;
+        }
+
+        uniform_value(&self.tiles)
+    }
+}
+
+fn uniform_value<N, T: Clone + PartialEq>(array: &ArrayN<N, T>) -> Option<T> {
+    let values = array.values_slice();
+    let first = &values[0];
+
+    if values.iter().all(|v| v == first) {
+        Some(first.clone())
+    } else {
+        None
+    }
+}
+
+fn leaf_extent<N>(leaf_key: PointN<N>) -> ExtentN<N>
+where
+    PointN<N>: IntegerPoint<Scalar = i32>,
+{
+    ExtentN::from_min_and_shape(leaf_key, PointN::ONES.left_shift(LEAF_LOG2))
+}
+
+fn node_extent<N>(node_key: PointN<N>) -> ExtentN<N>
+where
+    PointN<N>: IntegerPoint<Scalar = i32>,
+{
+    ExtentN::from_min_and_shape(node_key, PointN::ONES.left_shift(NODE_TOTAL_LOG2))
+}
+
+// Returns the keys of all blocks of edge length `2^log2` that overlap `extent`.
+fn keys_overlapping<N>(extent: &ExtentN<N>, log2: i32) -> impl Iterator<Item = PointN<N>>
+where
+    PointN<N>: IntegerPoint<Scalar = i32>,
+    ExtentN<N>: IntegerExtent<N>,
+{
+    let key_extent = if extent_is_empty(extent) {
+        ExtentN::from_min_and_shape(PointN::zero(), PointN::zero())
+    } else {
+        ExtentN::from_min_and_max(
+            extent.minimum.right_shift(log2),
+            extent.max().right_shift(log2),
+        )
+    };
+
+    key_extent.iter_points().map(move |p| p.left_shift(log2))
+}
+
+impl<N, T> VdbMap<N, T>
+where
+    PointN<N>: Eq + Hash,
+{
+    /// Creates an empty map where every point has the `background` value.
+    pub fn new(background: T) -> Self {
+        Self {
+            background,
+            root: FnvHashMap::default(),
+        }
+    }
+
+    pub fn background(&self) -> &T {
+        &self.background
+    }
+
+    /// The number of internal nodes.
+    pub fn num_nodes(&self) -> usize {
+        self.root
+            .values()
+            .filter(|child| matches!(child, RootChild::Node(_)))
+            .count()
+    }
+
+    /// The number of dense leaves.
+    pub fn num_leaves(&self) -> usize {
+        self.root
+            .values()
+            .map(|child| match child {
+                RootChild::Node(node) => node.child_mask.count_ones(),
+                RootChild::Tile(_) => 0,
+            })
+            .sum()
+    }
+}
+
+impl<N, T> VdbMap<N, T>
+where
+    T: Clone,
+    PointN<N>: IntegerPoint<Scalar = i32> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N>,
+{
+    /// Sets every point in `extent` to `value`. Whole leaves and nodes inside of `extent` become
+    /// tiles, so this is cheap even for huge extents.
+    pub fn fill_extent(&mut self, extent: &ExtentN<N>, value: T)
+    where
+        ExtentN<N>: PartialEq,
+        ArrayN<N, T>: ForEachMut<N, Stride, Data = T>,
+    {
+        for node_key in keys_overlapping(extent, NODE_TOTAL_LOG2) {
+            let node_extent = node_extent(node_key);
+            let node_intersection = extent.intersection(&node_extent);
+            if node_intersection == node_extent {
+                self.root.insert(node_key, RootChild::Tile(value.clone()));
+                continue;
+            }
+
+            let node = self.node_mut(node_key);
+            for leaf_key in keys_overlapping(&node_intersection, LEAF_LOG2) {
+                let slot = node.slot(&node_key, &leaf_key);
+                let leaf_extent = leaf_extent(leaf_key);
+                if node_intersection.intersection(&leaf_extent) == leaf_extent {
+                    node.set_tile(slot, value.clone());
+                } else {
+                    node.leaf_mut(slot, leaf_key)
+                        .for_each_mut(&node_intersection, |_s: Stride, v| *v = value.clone());
+                }
+            }
+        }
+    }
+
+    /// Collapses any leaves and nodes that have the same value everywhere into tiles, and removes
+    /// tiles that are the same as the background value.
+    pub fn prune(&mut self)
+    where
+        T: PartialEq,
+    {
+        for child in self.root.values_mut() {
+            if let RootChild::Node(node) = child {
+                if let Some(value) = node.prune() {
+                    *child = RootChild::Tile(value);
+                }
+            }
+        }
+
+        let background = &self.background;
+        self.root.retain(|_key, child| match child {
+            RootChild::Tile(value) => value != background,
+            RootChild::Node(_) => true,
+        });
+    }
+
+    fn node_mut(&mut self, node_key: PointN<N>) -> &mut InternalNode<N, T> {
+        let VdbMap { background, root } = self;
+        let child = root
+            .entry(node_key)
+            .or_insert_with(|| RootChild::Tile(background.clone()));
+        if let RootChild::Tile(value) = child {
+            *child = RootChild::Node(Box::new(InternalNode::new(value.clone())));
+        }
+
+        match child {
+            RootChild::Node(node) => node,
+            RootChild::Tile(_) => unreachable!(),
+        }
+    }
+
+    // Visits the uniform regions and leaves that overlap `extent`, along with the overlap.
+    fn for_each_region<'a>(
+        &'a self,
+        extent: &ExtentN<N>,
+        mut f: impl FnMut(ExtentN<N>, Region<'a, N, T>),
+    ) {
+        for node_key in keys_overlapping(extent, NODE_TOTAL_LOG2) {
+            let node_intersection = extent.intersection(&node_extent(node_key));
+            match self.root.get(&node_key) {
+                None => f(node_intersection, Region::Tile(&self.background)),
+                Some(RootChild::Tile(value)) => f(node_intersection, Region::Tile(value)),
+                Some(RootChild::Node(node)) => {
+                    for leaf_key in keys_overlapping(&node_intersection, LEAF_LOG2) {
+                        let slot = node.slot(&node_key, &leaf_key);
+                        f(
+                            node_intersection.intersection(&leaf_extent(leaf_key)),
+                            node.region(slot),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Visits every leaf that overlaps `extent`, creating leaves where necessary.
+    fn for_each_leaf_mut(&mut self, extent: &ExtentN<N>, mut f: impl FnMut(&mut ArrayN<N, T>)) {
+        for node_key in keys_overlapping(extent, NODE_TOTAL_LOG2) {
+            let node_intersection = extent.intersection(&node_extent(node_key));
+            let node = self.node_mut(node_key);
+            for leaf_key in keys_overlapping(&node_intersection, LEAF_LOG2) {
+                let slot = node.slot(&node_key, &leaf_key);
+                f(node.leaf_mut(slot, leaf_key));
+            }
+        }
+    }
+}
+
+//  ██████╗ ███████╗████████╗████████╗███████╗██████╗ ███████╗
+// ██╔════╝ ██╔════╝╚══██╔══╝╚══██╔══╝██╔════╝██╔══██╗██╔════╝
+// ██║  ███╗█████╗     ██║      ██║   █████╗  ██████╔╝███████╗
+// ██║   ██║██╔══╝     ██║      ██║   ██╔══╝  ██╔══██╗╚════██║
+// ╚██████╔╝███████╗   ██║      ██║   ███████╗██║  ██║███████║
+//  ╚═════╝ ╚══════╝   ╚═╝      ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
+
+impl<N, T> Get<&PointN<N>> for VdbMap<N, T>
+where
+    T: Clone,
+    Self: for<'r> GetRef<&'r PointN<N>, Data = T>,
+    PointN<N>: Eq + Hash,
+{
+    type Data = T;
+
+    fn get(&self, p: &PointN<N>) -> Self::Data {
+        self.get_ref(p).clone()
+    }
+}
+
+impl<N, T> GetRef<&PointN<N>> for VdbMap<N, T>
+where
+    T: Clone,
+    PointN<N>: IntegerPoint<Scalar = i32> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N>,
+{
+    type Data = T;
+
+    fn get_ref(&self, p: &PointN<N>) -> &Self::Data {
+        let node_key = p.right_shift(NODE_TOTAL_LOG2).left_shift(NODE_TOTAL_LOG2);
+        match self.root.get(&node_key) {
+            None => &self.background,
+            Some(RootChild::Tile(value)) => value,
+            Some(RootChild::Node(node)) => {
+                let leaf_key = p.right_shift(LEAF_LOG2).left_shift(LEAF_LOG2);
+                match node.region(node.slot(&node_key, &leaf_key)) {
+                    Region::Tile(value) => value,
+                    Region::Leaf(leaf) => leaf.get_ref(&Local(*p - leaf_key)),
+                }
+            }
+        }
+    }
+}
+
+impl<N, T> GetMut<&PointN<N>> for VdbMap<N, T>
+where
+    T: Clone,
+    PointN<N>: IntegerPoint<Scalar = i32> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N>,
+{
+    type Data = T;
+
+    /// Creates a leaf containing `p` if there isn't one already.
+    fn get_mut(&mut self, p: &PointN<N>) -> &mut Self::Data {
+        let node_key = p.right_shift(NODE_TOTAL_LOG2).left_shift(NODE_TOTAL_LOG2);
+        let leaf_key = p.right_shift(LEAF_LOG2).left_shift(LEAF_LOG2);
+        let node = self.node_mut(node_key);
+        let slot = node.slot(&node_key, &leaf_key);
+
+        node.leaf_mut(slot, leaf_key).get_mut(&Local(*p - leaf_key))
+    }
+}
+
+// ███████╗ ██████╗ ██████╗     ███████╗ █████╗  ██████╗██╗  ██╗
+// ██╔════╝██╔═══██╗██╔══██╗    ██╔════╝██╔══██╗██╔════╝██║  ██║
+// █████╗  ██║   ██║██████╔╝    █████╗  ███████║██║     ███████║
+// ██╔══╝  ██║   ██║██╔══██╗    ██╔══╝  ██╔══██║██║     ██╔══██║
+// ██║     ╚██████╔╝██║  ██║    ███████╗██║  ██║╚██████╗██║  ██║
+// ╚═╝      ╚═════╝ ╚═╝  ╚═╝    ╚══════╝╚═╝  ╚═╝ ╚═════╝╚═╝  ╚═╝
+
+impl<N, T> ForEachRef<N, PointN<N>> for VdbMap<N, T>
+where
+    T: Clone,
+    PointN<N>: IntegerPoint<Scalar = i32> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N> + ForEachRef<N, PointN<N>, Data = T>,
+{
+    type Data = T;
+
+    fn for_each_ref(&self, extent: &ExtentN<N>, mut f: impl FnMut(PointN<N>, &Self::Data)) {
+        self.for_each_region(extent, |region_extent, region| match region {
+            Region::Tile(value) => {
+                for p in region_extent.iter_points() {
+                    f(p, value);
+                }
+            }
+            Region::Leaf(leaf) => leaf.for_each_ref(&region_extent, |p, value| f(p, value)),
+        });
+    }
+}
+
+impl<N, T> ForEachMut<N, PointN<N>> for VdbMap<N, T>
+where
+    T: Clone,
+    PointN<N>: IntegerPoint<Scalar = i32> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N> + ForEachMut<N, PointN<N>, Data = T>,
+{
+    type Data = T;
+
+    /// Creates leaves for the entire `extent`.
+    fn for_each_mut(&mut self, extent: &ExtentN<N>, mut f: impl FnMut(PointN<N>, &mut Self::Data)) {
+        self.for_each_leaf_mut(extent, |leaf| {
+            leaf.for_each_mut(extent, |p, value| f(p, value))
+        });
+    }
+}
+
+//  ██████╗ ██████╗ ██████╗ ██╗   ██╗
+// ██╔════╝██╔═══██╗██╔══██╗╚██╗ ██╔╝
+// ██║     ██║   ██║██████╔╝ ╚████╔╝
+// ██║     ██║   ██║██╔═══╝   ╚██╔╝
+// ╚██████╗╚██████╔╝██║        ██║
+//  ╚═════╝ ╚═════╝ ╚═╝        ╚═╝
+
+impl<'a, N: 'a, T: 'a> ReadExtent<'a, N> for VdbMap<N, T>
+where
+    T: Clone,
+    PointN<N>: IntegerPoint<Scalar = i32> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N>,
+{
+    type Src = ArrayChunkCopySrc<'a, N, T>;
+    type SrcIter = ArrayChunkCopySrcIter<'a, N, T>;
+
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        let mut srcs = Vec::new();
+        self.for_each_region(extent, |region_extent, region| {
+            let src = match region {
+                Region::Tile(value) => Either::Right(AmbientExtent::new(value.clone())),
+                Region::Leaf(leaf) => Either::Left(ArrayCopySrc(leaf)),
+            };
+            srcs.push((region_extent, src));
+        });
+
+        srcs.into_iter()
+    }
+}
+
+impl<N, T, Ms> WriteExtent<N, ArrayCopySrc<Ms>> for VdbMap<N, T>
+where
+    T: Clone,
+    Ms: Copy,
+    PointN<N>: IntegerPoint<Scalar = i32> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N> + WriteExtent<N, ArrayCopySrc<Ms>>,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: ArrayCopySrc<Ms>) {
+        self.for_each_leaf_mut(extent, |leaf| leaf.write_extent(extent, src));
+    }
+}
+
+impl<M, N, T> WriteExtent<N, ChunkCopySrc<M, N, T>> for VdbMap<N, T>
+where
+    T: Clone,
+    Self: WriteExtent<N, ArrayCopySrc<M>>,
+    PointN<N>: IntegerPoint<Scalar = i32> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ExtentN<N>: PartialEq,
+    ArrayN<N, T>: Array<N> + ForEachMut<N, Stride, Data = T>,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: ChunkCopySrc<M, N, T>) {
+        match src {
+            Either::Left(array) => self.write_extent(extent, array),
+            // Uniform sources stay sparse.
+            Either::Right(ambient) => self.fill_extent(extent, ambient.get()),
+        }
+    }
+}
+
+impl<N, F, T> WriteExtent<N, F> for VdbMap<N, T>
+where
+    F: Fn(&PointN<N>) -> T,
+    T: Clone,
+    PointN<N>: IntegerPoint<Scalar = i32> + Eq + Hash,
+    ExtentN<N>: IntegerExtent<N>,
+    ArrayN<N, T>: Array<N> + for<'r> WriteExtent<N, &'r F>,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: F) {
+        self.for_each_leaf_mut(extent, |leaf| leaf.write_extent(extent, &src));
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{copy_extent, Array3};
+
+    #[test]
+    fn tiles_and_leaves_agree_with_dense_array() {
+        let extent = Extent3i::from_min_and_shape(PointN([-150, -20, -5]), PointN([300, 40, 10]));
+        let mut dense = Array3::fill(extent, 0);
+        let mut map = VdbMap3::new(0);
+
+        let fill = Extent3i::from_min_and_shape(PointN([-140, -10, -5]), PointN([280, 20, 5]));
+        dense.for_each_mut(&fill, |_s: Stride, v| *v = 1);
+        map.fill_extent(&fill, 1);
+
+        for p in [
+            PointN([0, 0, 0]),
+            PointN([-150, -20, -5]),
+            PointN([149, 19, 4]),
+        ]
+        .iter()
+        {
+            *dense.get_mut(p) = 2;
+            *map.get_mut(p) = 2;
+        }
+
+        map.for_each_ref(&extent, |p: Point3i, value| {
+            assert_eq!(*value, dense.get(&p), "mismatch at {:?}", p);
+        });
+
+        let mut copied = Array3::fill(extent, 9);
+        copy_extent(&extent, &map, &mut copied);
+        assert_eq!(copied, dense);
+    }
+
+    #[test]
+    fn copy_from_array_and_prune() {
+        let extent = Extent3i::from_min_and_shape(PointN([0; 3]), PointN([32; 3]));
+        let src = Array3::fill_with(extent, |p| if p.z() < 16 { 1 } else { 0 });
+
+        let mut map = VdbMap3::new(0);
+        copy_extent(&extent, &src, &mut map);
+        assert_eq!(map.num_leaves(), 4 * 4 * 4);
+
+        // Each leaf is either all 1 or all 0, so they all collapse.
+        map.prune();
+        assert_eq!(map.num_leaves(), 0);
+        assert_eq!(map.num_nodes(), 1);
+
+        map.for_each_ref(&extent, |p: Point3i, value| {
+            assert_eq!(*value, src.get(&p));
+        });
+    }
+}